@@ -26,6 +26,7 @@ impl Editor {
         Terminal::initialize()?;
         let args: Vec<String> = args().collect();
         let mut view = View::default();
+        view.load_search_history();
         if let Some(filename) = args.get(1) {
             if view.load(filename).is_err() {
                 return Err(Error::new(
@@ -87,11 +88,9 @@ impl Editor {
                                 Terminal::execute()?;
                                 thread::sleep(Duration::from_millis(300));
                             } else {
-                                if self.view.buffer.filename.is_none() {
-                                    self.view.get_file_name();
-                                }
-                                if self.view.buffer.filename.is_some() {
-                                    self.view.buffer.save();
+                                // a failed save keeps the session open so nothing is lost
+                                if !self.view.save_before_exit() {
+                                    return Ok(true);
                                 }
                             }
                         }
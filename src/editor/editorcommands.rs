@@ -1,6 +1,9 @@
 use super::terminal::Size;
 use super::terminal::{Coordinate, Position};
 use super::view::buffer::Buffer;
+use super::view::file_format::LineEnding;
+use super::view::motion::Motion;
+use super::view::substitute::Substitute;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use std::convert::TryFrom;
 use std::error::Error;
@@ -9,6 +12,21 @@ pub fn parse_highlight_normal_mode(event: Event) -> Result<HighlightCommand, Box
     Ok(HighlightCommand::try_from(event)?)
 }
 
+/// terminals report upper case chars with the shift modifier
+/// vim bindings match on the char itself
+fn strip_shift(code: KeyCode, modifiers: KeyModifiers) -> KeyModifiers {
+    match code {
+        KeyCode::Char(c) if !c.is_ascii_lowercase() => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    }
+}
+
+pub fn digit_value(c: char) -> usize {
+    c.to_digit(10)
+        .and_then(|digit| usize::try_from(digit).ok())
+        .unwrap_or_default()
+}
+
 pub fn parse_highlight_vim_mode(event: Event) -> Result<HighlightCommand, Box<dyn Error>> {
     let vim_hc = VimHighlightCommand::try_from(event)?;
     Ok(HighlightCommand::try_from(vim_hc)?)
@@ -34,13 +52,13 @@ impl Direction {
             //if the next line is shorter, snap to the end of that line
             Direction::Down => {
                 cursor_position.down(1, buffer.len().saturating_sub(1));
-                cursor_position.resolve_width(buffer.text[cursor_position.height].grapheme_len());
+                cursor_position.resolve_width(buffer.line(cursor_position.height).grapheme_len());
             }
             //if we are not in row 0, move up
             //if the line above is shorter than the previous line, snap to the end
             Direction::Up => {
                 cursor_position.up(1);
-                cursor_position.resolve_width(buffer.text[cursor_position.height].grapheme_len());
+                cursor_position.resolve_width(buffer.line(cursor_position.height).grapheme_len());
             }
             //move left
             //if we are at 0,0 no action
@@ -49,7 +67,7 @@ impl Direction {
             Direction::Left => match (cursor_position.at_left_edge(), cursor_position.at_top()) {
                 (true, false) => {
                     cursor_position.up(1);
-                    cursor_position.snap_right(buffer.text[cursor_position.height].grapheme_len());
+                    cursor_position.snap_right(buffer.line(cursor_position.height).grapheme_len());
                 }
                 _ => {
                     cursor_position.left(1);
@@ -59,7 +77,7 @@ impl Direction {
             //if we are at the end of the line, snap to position 0 on the next line
             //else move right 1 char
             Direction::Right => {
-                let grapheme_len = buffer.text[cursor_position.height].grapheme_len();
+                let grapheme_len = buffer.line(cursor_position.height).grapheme_len();
                 let text_height = buffer.len().saturating_sub(1);
 
                 match (
@@ -83,7 +101,7 @@ impl Direction {
             }
             //move to end of current line
             Direction::End => {
-                cursor_position.snap_right(buffer.text[cursor_position.height].grapheme_len());
+                cursor_position.snap_right(buffer.line(cursor_position.height).grapheme_len());
             }
             //move to start of current line
             Direction::Home => {
@@ -109,11 +127,30 @@ pub enum EditorCommand {
     Delete,
     VimMode,
     Search,
+    Replace,
     Help,
+    Undo,
+    Redo,
     None,
     Quit,
 }
 
+impl EditorCommand {
+    /// commands that put in or take back text at the cursor, any other command ends the typing
+    pub fn is_typing(self) -> bool {
+        matches!(
+            self,
+            Self::Insert(_)
+                | Self::Delete
+                | Self::Tab
+                | Self::NewLine
+                | Self::Paste
+                | Self::Resize(_)
+                | Self::None
+        )
+    }
+}
+
 impl TryFrom<Event> for EditorCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
@@ -126,7 +163,8 @@ impl TryFrom<Event> for EditorCommand {
                 (KeyCode::Char('l'), KeyModifiers::CONTROL) => Ok(Self::Move(Direction::Home)),
                 (KeyCode::Char('g'), KeyModifiers::ALT) => Ok(Self::Move(Direction::PageUp)),
                 (KeyCode::Char('g'), KeyModifiers::CONTROL) => Ok(Self::Move(Direction::PageDown)),
-                (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Move(Direction::End)),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Replace),
+                (KeyCode::End, _) => Ok(Self::Move(Direction::End)),
                 (KeyCode::Char('w'), KeyModifiers::CONTROL) => Ok(Self::Save),
                 (KeyCode::Char('h'), KeyModifiers::CONTROL) => Ok(Self::Help),
                 (KeyCode::Char('f'), KeyModifiers::CONTROL) => Ok(Self::Search),
@@ -134,6 +172,8 @@ impl TryFrom<Event> for EditorCommand {
                 (KeyCode::Char('v'), KeyModifiers::CONTROL) => Ok(Self::Paste),
                 (KeyCode::Char('c'), KeyModifiers::CONTROL) => Ok(Self::Highlight),
                 (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Self::VimMode),
+                (KeyCode::Char('z'), KeyModifiers::CONTROL) => Ok(Self::Undo),
+                (KeyCode::Char('y'), KeyModifiers::CONTROL) => Ok(Self::Redo),
                 (KeyCode::Left, KeyModifiers::SHIFT) => Ok(Self::JumpWord(Direction::Left)),
                 (KeyCode::Right, KeyModifiers::SHIFT) => Ok(Self::JumpWord(Direction::Right)),
                 (KeyCode::Up, _) => Ok(Self::Move(Direction::Up)),
@@ -161,6 +201,11 @@ impl TryFrom<Event> for EditorCommand {
 #[derive(Copy, Clone)]
 pub enum SearchCommand {
     Insert(char),
+    ToggleRegex,
+    ToggleCase,      // cycle case sensitive, ignore case and smart case
+    ToggleWholeWord, // only match whole words
+    Replace,         // replace the hits, asking at each one
+    Recall(bool),    // up arrow brings back an older search, down a newer one
     Next,
     Previous,
     BackSpace,
@@ -179,11 +224,17 @@ impl TryFrom<Event> for SearchCommand {
             }) => match (code, modifiers) {
                 (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Self::Next),
                 (KeyCode::Char('p'), KeyModifiers::CONTROL) => Ok(Self::Previous),
-                (_, KeyModifiers::CONTROL) => Ok(Self::NoAction),
+                (KeyCode::Char('r'), KeyModifiers::ALT) => Ok(Self::ToggleRegex),
+                (KeyCode::Char('c'), KeyModifiers::ALT) => Ok(Self::ToggleCase),
+                (KeyCode::Char('w'), KeyModifiers::ALT) => Ok(Self::ToggleWholeWord),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Replace),
+                (_, KeyModifiers::CONTROL | KeyModifiers::ALT) => Ok(Self::NoAction),
                 (KeyCode::Char(c), _) => Ok(Self::Insert(c)),
                 (KeyCode::Enter, _) => Ok(Self::AssumeState),
                 (KeyCode::Esc, _) => Ok(Self::RevertState),
                 (KeyCode::Backspace, _) => Ok(Self::BackSpace),
+                (KeyCode::Up, _) => Ok(Self::Recall(true)),
+                (KeyCode::Down, _) => Ok(Self::Recall(false)),
                 _ => Ok(Self::NoAction),
             },
             #[allow(clippy::as_conversions)]
@@ -205,6 +256,20 @@ pub enum HighlightCommand {
     #[default]
     NoAction,
     Delete,
+    TextObject(bool),  // select a text object, true for inner
+    Register,          // name the register for the yank or delete
+    Indent(bool),      // > and <, true indents
+    BlockInsert(bool), // I and A on a block, true appends
+    Selection(SelectionKind),
+}
+
+/// what a highlight selects between its start and end
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SelectionKind {
+    #[default]
+    Chars, // v
+    Lines, // V
+    Block, // Ctrl-v
 }
 
 impl TryFrom<Event> for HighlightCommand {
@@ -253,6 +318,11 @@ impl TryFrom<VimHighlightCommand> for HighlightCommand {
             VimHighlightCommand::RevertState => Ok(Self::RevertState),
             VimHighlightCommand::NoAction => Ok(Self::NoAction),
             VimHighlightCommand::Delete => Ok(Self::Delete),
+            VimHighlightCommand::TextObject(inner) => Ok(Self::TextObject(inner)),
+            VimHighlightCommand::Register => Ok(Self::Register),
+            VimHighlightCommand::Indent(right) => Ok(Self::Indent(right)),
+            VimHighlightCommand::BlockInsert(append) => Ok(Self::BlockInsert(append)),
+            VimHighlightCommand::Selection(kind) => Ok(Self::Selection(kind)),
         }
     }
 }
@@ -266,12 +336,22 @@ pub enum VimHighlightCommand {
     #[default]
     NoAction,
     Delete,
+    TextObject(bool),
+    Register,
+    Indent(bool),
+    BlockInsert(bool),
+    Selection(SelectionKind),
 }
 
 impl TryFrom<Event> for VimHighlightCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => Ok(Self::Selection(SelectionKind::Block)),
             Event::Key(KeyEvent { code, .. }) => match code {
                 KeyCode::Char('y') => Ok(Self::Copy),
                 KeyCode::Char('k') => Ok(Self::Move(Direction::Up)),
@@ -281,6 +361,15 @@ impl TryFrom<Event> for VimHighlightCommand {
                 KeyCode::Char('$') => Ok(Self::Move(Direction::End)),
                 KeyCode::Char('0') => Ok(Self::Move(Direction::Home)),
                 KeyCode::Char('d') => Ok(Self::Delete),
+                KeyCode::Char('i') => Ok(Self::TextObject(true)),
+                KeyCode::Char('a') => Ok(Self::TextObject(false)),
+                KeyCode::Char('"') => Ok(Self::Register),
+                KeyCode::Char('>') => Ok(Self::Indent(true)),
+                KeyCode::Char('<') => Ok(Self::Indent(false)),
+                KeyCode::Char('I') => Ok(Self::BlockInsert(false)),
+                KeyCode::Char('A') => Ok(Self::BlockInsert(true)),
+                KeyCode::Char('v') => Ok(Self::Selection(SelectionKind::Chars)),
+                KeyCode::Char('V') => Ok(Self::Selection(SelectionKind::Lines)),
                 KeyCode::Esc => Ok(Self::RevertState),
                 _ => Ok(Self::NoAction),
             },
@@ -323,8 +412,76 @@ pub enum QueueInitCommand {
     PageUp,
     PageDown,
     Colon,
+    Operator(Operator),
+}
+
+/// vim operators, these wait on a motion to act on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operator {
     Delete,
     Yank,
+    Change,
+}
+
+impl TryFrom<char> for Operator {
+    type Error = String;
+    fn try_from(val: char) -> Result<Self, Self::Error> {
+        match val {
+            'd' => Ok(Self::Delete),
+            'y' => Ok(Self::Yank),
+            'c' => Ok(Self::Change),
+            _ => Err(format!("{val} is not an operator")),
+        }
+    }
+}
+
+impl Operator {
+    /// the key for the operator, typing it twice acts on whole lines
+    pub fn key(self) -> char {
+        match self {
+            Self::Delete => 'd',
+            Self::Yank => 'y',
+            Self::Change => 'c',
+        }
+    }
+}
+
+/// where the cursor goes when vim mode is left to type text
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InsertEntry {
+    Before,    // i
+    After,     // a
+    LineEnd,   // A
+    LineStart, // I, at the first non blank
+    LineBelow, // o
+    LineAbove, // O
+}
+
+impl InsertEntry {
+    /// whether entering the insert changes the buffer on its own
+    pub fn opens_line(self) -> bool {
+        matches!(self, Self::LineBelow | Self::LineAbove)
+    }
+}
+
+/// a row of the view, where H M L move the cursor and zt zz zb put the cursor line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ViewRow {
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl TryFrom<char> for ViewRow {
+    type Error = String;
+    fn try_from(val: char) -> Result<Self, Self::Error> {
+        match val {
+            'H' | 't' => Ok(Self::Top),
+            'M' | 'z' => Ok(Self::Middle),
+            'L' | 'b' => Ok(Self::Bottom),
+            _ => Err(format!("{val} is not a view row")),
+        }
+    }
 }
 
 pub enum VimModeCommands {
@@ -334,41 +491,149 @@ pub enum VimModeCommands {
     StartOfNextWord,
     EndOfCurrentWord,
     BeginingOfCurrentWord,
-    NewLine,
-    Highlight,
+    Insert(InsertEntry),
+    Substitute(Motion), // s S C, a change over a fixed motion
+    FindChar(char),     // f F t T, the char to find is read next
+    RepeatFind(bool),   // ; and , which runs the last find the other way
+    MatchingPair,       // %, the bracket paired with the one at or after the cursor
+    ViewRow(ViewRow),   // H M L
+    Paragraph(bool),    // } forward and { back
+    Sentence(bool),     // ) forward and ( back
+    SearchNext(bool),   // n, N goes the other way
+    SearchWord(bool),   // * forward and # back, for the word under the cursor
+    DeleteChar(Motion), // x X, a delete over a fixed motion
+    ReplaceChar,        // r, the new char is read next
+    Overwrite,          // R
+    Join,               // J
+    ToggleCase,         // ~
+    Shift(bool),        // >> indents and << outdents
+    Scroll(bool),       // Ctrl-e scrolls the view down, Ctrl-y up
+    ScrollCursor,       // z, the row for the cursor line is read next
+    Highlight(SelectionKind),
     Search,
     Paste,
+    PasteBefore,
+    Register,
+    Record,
+    Replay,
+    Repeat,
+    Undo,
+    Redo,
+    Digit(usize),
     NoAction,
     Resize(Size),
     ComplexCommand(QueueInitCommand),
     Exit,
 }
 
+impl VimModeCommands {
+    /// commands that only move the cursor
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Self::Move(_)
+                | Self::StartOfNextWord
+                | Self::EndOfCurrentWord
+                | Self::BeginingOfCurrentWord
+                | Self::FindChar(_)
+                | Self::RepeatFind(_)
+                | Self::MatchingPair
+                | Self::ViewRow(_)
+                | Self::Paragraph(_)
+                | Self::Sentence(_)
+                | Self::SearchNext(_)
+                | Self::SearchWord(_)
+        )
+    }
+
+    /// commands that move the cursor or the view and change nothing
+    pub fn is_movement(&self) -> bool {
+        self.is_motion()
+            || matches!(
+                self,
+                Self::Digit(_)
+                    | Self::JumpUp
+                    | Self::JumpDown
+                    | Self::Scroll(_)
+                    | Self::ScrollCursor
+            )
+    }
+}
+
 impl TryFrom<Event> for VimModeCommands {
     type Error = String;
+    // one arm per key binding
+    #[allow(clippy::too_many_lines)]
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
             Event::Key(KeyEvent {
                 code, modifiers, ..
-            }) => match (code, modifiers) {
+            }) => match (code, strip_shift(code, modifiers)) {
                 (KeyCode::Char('u'), KeyModifiers::CONTROL) => Ok(Self::JumpUp),
                 (KeyCode::Char('d'), KeyModifiers::CONTROL) => Ok(Self::JumpDown),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Redo),
+                (KeyCode::Char('e'), KeyModifiers::CONTROL) => Ok(Self::Scroll(true)),
+                (KeyCode::Char('y'), KeyModifiers::CONTROL) => Ok(Self::Scroll(false)),
+                (KeyCode::Char('u'), KeyModifiers::NONE) => Ok(Self::Undo),
                 (KeyCode::Char('h'), KeyModifiers::NONE) => Ok(Self::Move(Direction::Left)),
                 (KeyCode::Char('k'), KeyModifiers::NONE) => Ok(Self::Move(Direction::Up)),
                 (KeyCode::Char('j'), KeyModifiers::NONE) => Ok(Self::Move(Direction::Down)),
                 (KeyCode::Char('l'), KeyModifiers::NONE) => Ok(Self::Move(Direction::Right)),
-                (KeyCode::Char('0'), KeyModifiers::NONE) => Ok(Self::Move(Direction::Home)),
+                (KeyCode::Char(c @ '0'..='9'), KeyModifiers::NONE) => {
+                    Ok(Self::Digit(digit_value(c)))
+                }
                 (KeyCode::Char('b'), KeyModifiers::NONE) => Ok(Self::BeginingOfCurrentWord),
                 (KeyCode::Char('e'), KeyModifiers::NONE) => Ok(Self::EndOfCurrentWord),
                 (KeyCode::Char('w'), KeyModifiers::NONE) => Ok(Self::StartOfNextWord),
-                (KeyCode::Char('o'), KeyModifiers::NONE) => Ok(Self::NewLine),
-                (KeyCode::Char('/'), KeyModifiers::NONE) => Ok(Self::Search),
-                (KeyCode::Char('d'), KeyModifiers::NONE) => {
-                    Ok(Self::ComplexCommand(QueueInitCommand::Delete))
+                (KeyCode::Char('i'), KeyModifiers::NONE) => Ok(Self::Insert(InsertEntry::Before)),
+                (KeyCode::Char('a'), KeyModifiers::NONE) => Ok(Self::Insert(InsertEntry::After)),
+                (KeyCode::Char('A'), KeyModifiers::NONE) => Ok(Self::Insert(InsertEntry::LineEnd)),
+                (KeyCode::Char('I'), KeyModifiers::NONE) => {
+                    Ok(Self::Insert(InsertEntry::LineStart))
+                }
+                (KeyCode::Char('o'), KeyModifiers::NONE) => {
+                    Ok(Self::Insert(InsertEntry::LineBelow))
+                }
+                (KeyCode::Char('O'), KeyModifiers::NONE) => {
+                    Ok(Self::Insert(InsertEntry::LineAbove))
+                }
+                (KeyCode::Char(c @ ('f' | 'F' | 't' | 'T')), KeyModifiers::NONE) => {
+                    Ok(Self::FindChar(c))
                 }
-                (KeyCode::Char('y'), KeyModifiers::NONE) => {
-                    Ok(Self::ComplexCommand(QueueInitCommand::Yank))
+                (KeyCode::Char(';'), KeyModifiers::NONE) => Ok(Self::RepeatFind(false)),
+                (KeyCode::Char(','), KeyModifiers::NONE) => Ok(Self::RepeatFind(true)),
+                (KeyCode::Char('%'), KeyModifiers::NONE) => Ok(Self::MatchingPair),
+                (KeyCode::Char(c @ ('H' | 'M' | 'L')), KeyModifiers::NONE) => {
+                    Ok(Self::ViewRow(ViewRow::try_from(c)?))
                 }
+                (KeyCode::Char('z'), KeyModifiers::NONE) => Ok(Self::ScrollCursor),
+                (KeyCode::Char('}'), KeyModifiers::NONE) => Ok(Self::Paragraph(true)),
+                (KeyCode::Char('{'), KeyModifiers::NONE) => Ok(Self::Paragraph(false)),
+                (KeyCode::Char(')'), KeyModifiers::NONE) => Ok(Self::Sentence(true)),
+                (KeyCode::Char(c @ ('n' | 'N')), KeyModifiers::NONE) => {
+                    Ok(Self::SearchNext(c == 'N'))
+                }
+                (KeyCode::Char(c @ ('*' | '#')), KeyModifiers::NONE) => {
+                    Ok(Self::SearchWord(c == '*'))
+                }
+                (KeyCode::Char('x'), KeyModifiers::NONE) => Ok(Self::DeleteChar(Motion::Right)),
+                (KeyCode::Char('X'), KeyModifiers::NONE) => Ok(Self::DeleteChar(Motion::Left)),
+                (KeyCode::Char('r'), KeyModifiers::NONE) => Ok(Self::ReplaceChar),
+                (KeyCode::Char('R'), KeyModifiers::NONE) => Ok(Self::Overwrite),
+                (KeyCode::Char('J'), KeyModifiers::NONE) => Ok(Self::Join),
+                (KeyCode::Char('~'), KeyModifiers::NONE) => Ok(Self::ToggleCase),
+                (KeyCode::Char('>'), KeyModifiers::NONE) => Ok(Self::Shift(true)),
+                (KeyCode::Char('<'), KeyModifiers::NONE) => Ok(Self::Shift(false)),
+                (KeyCode::Char('('), KeyModifiers::NONE) => Ok(Self::Sentence(false)),
+                (KeyCode::Char('s'), KeyModifiers::NONE) => Ok(Self::Substitute(Motion::Right)),
+                (KeyCode::Char('S'), KeyModifiers::NONE) => {
+                    Ok(Self::Substitute(Motion::CurrentLine))
+                }
+                (KeyCode::Char('C'), KeyModifiers::NONE) => Ok(Self::Substitute(Motion::LineEnd)),
+                (KeyCode::Char('/'), KeyModifiers::NONE) => Ok(Self::Search),
+                (KeyCode::Char(c @ ('d' | 'y' | 'c')), KeyModifiers::NONE) => Ok(
+                    Self::ComplexCommand(QueueInitCommand::Operator(Operator::try_from(c)?)),
+                ),
                 (KeyCode::Char('g'), KeyModifiers::NONE) => {
                     Ok(Self::ComplexCommand(QueueInitCommand::PageUp))
                 }
@@ -376,12 +641,25 @@ impl TryFrom<Event> for VimModeCommands {
                     Ok(Self::ComplexCommand(QueueInitCommand::PageDown))
                 }
                 (KeyCode::Char('p'), KeyModifiers::NONE) => Ok(Self::Paste),
-                (KeyCode::Char('v'), KeyModifiers::NONE) => Ok(Self::Highlight),
+                (KeyCode::Char('P'), KeyModifiers::NONE) => Ok(Self::PasteBefore),
+                (KeyCode::Char('"'), KeyModifiers::NONE) => Ok(Self::Register),
+                (KeyCode::Char('q'), KeyModifiers::NONE) => Ok(Self::Record),
+                (KeyCode::Char('@'), KeyModifiers::NONE) => Ok(Self::Replay),
+                (KeyCode::Char('.'), KeyModifiers::NONE) => Ok(Self::Repeat),
+                (KeyCode::Char('v'), KeyModifiers::NONE) => {
+                    Ok(Self::Highlight(SelectionKind::Chars))
+                }
+                (KeyCode::Char('V'), KeyModifiers::NONE) => {
+                    Ok(Self::Highlight(SelectionKind::Lines))
+                }
+                (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+                    Ok(Self::Highlight(SelectionKind::Block))
+                }
                 (KeyCode::Char('$'), KeyModifiers::NONE) => Ok(Self::Move(Direction::End)), //represents $
                 (KeyCode::Char(':'), KeyModifiers::NONE) => {
                     Ok(Self::ComplexCommand(QueueInitCommand::Colon))
                 }
-                (KeyCode::Esc | KeyCode::Char('i'), KeyModifiers::NONE) => Ok(Self::Exit),
+                (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::Exit),
 
                 _ => Ok(Self::NoAction),
             },
@@ -428,6 +706,44 @@ pub enum ColonQueueActions {
     Quit,
     Override,
     Jump(usize),
+    Set(SetOption),
+    Substitute(Substitute),
+}
+
+/// options that can be changed with :set
+#[derive(Copy, Clone)]
+pub enum SetOption {
+    FileFormat(LineEnding),
+    ScrollOff(usize),
+    Number(bool),         // number or nonumber
+    RelativeNumber(bool), // relativenumber or norelativenumber
+    Wrap(bool),           // wrap or nowrap
+}
+
+impl TryFrom<&str> for SetOption {
+    type Error = String;
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "number" | "nu" => return Ok(Self::Number(true)),
+            "nonumber" | "nonu" => return Ok(Self::Number(false)),
+            "relativenumber" | "rnu" => return Ok(Self::RelativeNumber(true)),
+            "norelativenumber" | "nornu" => return Ok(Self::RelativeNumber(false)),
+            "wrap" => return Ok(Self::Wrap(true)),
+            "nowrap" => return Ok(Self::Wrap(false)),
+            _ => {}
+        }
+        match val.split_once('=') {
+            Some(("fileformat" | "ff", format)) => {
+                Ok(Self::FileFormat(LineEnding::try_from(format)?))
+            }
+            Some(("scrolloff" | "so", lines)) => {
+                Ok(Self::ScrollOff(lines.parse().map_err(|_| {
+                    format!("{lines} is not a number of lines")
+                })?))
+            }
+            _ => Err(format!("unknown option {val}")),
+        }
+    }
 }
 
 impl TryFrom<char> for ColonQueueActions {
@@ -442,6 +758,39 @@ impl TryFrom<char> for ColonQueueActions {
     }
 }
 
+/// the answer at a replace prompt
+pub enum ConfirmReplace {
+    Yes,
+    No,
+    All,  // this hit and every one after it
+    Last, // this hit and then stop
+    Quit,
+    Resize(Size),
+    NoAction,
+}
+
+impl TryFrom<Event> for ConfirmReplace {
+    type Error = String;
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        match event {
+            Event::Key(KeyEvent { code, .. }) => match code {
+                KeyCode::Char('y') => Ok(Self::Yes),
+                KeyCode::Char('n') => Ok(Self::No),
+                KeyCode::Char('a') => Ok(Self::All),
+                KeyCode::Char('l') => Ok(Self::Last),
+                KeyCode::Char('q') | KeyCode::Esc => Ok(Self::Quit),
+                _ => Ok(Self::NoAction),
+            },
+            #[allow(clippy::as_conversions)]
+            Event::Resize(width_u16, height_u16) => Ok(Self::Resize(Size {
+                height: height_u16 as usize,
+                width: width_u16 as usize,
+            })),
+            _ => Err("Invalid key press read".into()),
+        }
+    }
+}
+
 pub enum JumpCommand {
     Enter(usize),
     Delete,
@@ -1,9 +1,30 @@
+use crate::editor::view::file_format::FileFormat;
+use crate::editor::view::gutter::LineNumbers;
 use crate::editor::view::{PROGRAM_NAME, PROGRAM_VERSION};
 use crossterm::cursor::{Hide, MoveTo, SetCursorStyle, Show};
-use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::style::{
+    Color, ContentStyle, Print, PrintStyledContent, SetBackgroundColor, SetForegroundColor,
+    StyledContent,
+};
 use crossterm::terminal::{self, disable_raw_mode, enable_raw_mode, size, Clear, ClearType};
 use crossterm::{queue, Command};
+use screen::Screen;
+use std::cell::RefCell;
+use std::fmt::Display;
 use std::io::{stdout, Error, Write};
+use unicode_width::UnicodeWidthChar;
+
+mod screen;
+
+thread_local! {
+    // while the editor owns the terminal everything is drawn into the screen model first
+    static SCREEN: RefCell<Option<Screen>> = const { RefCell::new(None) };
+}
+
+/// run a drawing call against the screen model, None when there is no model to draw into
+fn with_screen<R>(draw: impl FnOnce(&mut Screen) -> R) -> Option<R> {
+    SCREEN.with_borrow_mut(|screen| screen.as_mut().map(draw))
+}
 
 /// Setting the terminal size and position to usize
 /// This also handles edge cases
@@ -15,7 +36,7 @@ pub struct Size {
     pub width: usize,
 }
 
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub struct ScreenPosition {
     pub height: usize,
     pub width: usize,
@@ -113,13 +134,6 @@ impl Coordinate for Position {
 }
 
 impl Position {
-    pub fn to_screen_position(self) -> ScreenPosition {
-        ScreenPosition {
-            height: self.height,
-            width: self.width,
-        }
-    }
-
     pub fn diff_height(&self, other: &Position) -> usize {
         if self.height > other.height {
             return self.height.saturating_sub(other.height);
@@ -161,12 +175,15 @@ impl Position {
         size: &Size,
         reserved_lines: usize,
     ) -> usize {
-        let width_displacement: usize = if self.width < offset.width {
+        // a wrapped view never scrolls sideways
+        let width_displacement: usize = if offset.wrap {
+            0
+        } else if self.width < offset.width {
             offset.width.saturating_sub(self.width)
-        } else if self.width >= offset.width.saturating_add(size.width) {
+        } else if self.width >= offset.width.saturating_add(offset.text_width(size)) {
             self.width
                 .saturating_sub(offset.width)
-                .saturating_add(size.width)
+                .saturating_add(offset.text_width(size))
         } else {
             0_usize
         };
@@ -190,12 +207,15 @@ impl Position {
     pub fn relative_view_position(&self, offset: &ScreenOffset) -> ScreenPosition {
         ScreenPosition {
             height: self.height.saturating_sub(offset.height),
-            width: self.width.saturating_sub(offset.width),
+            width: self
+                .width
+                .saturating_sub(offset.width)
+                .saturating_add(offset.gutter),
         }
     }
 
     pub fn right_of_view(&self, offset: &ScreenOffset, size: &Size) -> bool {
-        self.width > offset.width.saturating_add(size.width)
+        !offset.wrap && self.width > offset.width.saturating_add(offset.text_width(size))
     }
 
     pub fn left_of_view(&self, offset: &ScreenOffset) -> bool {
@@ -223,9 +243,25 @@ impl Position {
 pub struct ScreenOffset {
     pub height: usize,
     pub width: usize,
+    pub numbers: LineNumbers,
+    pub gutter: usize, // columns left of the text taken by line numbers
+    pub wrap: bool,    // long lines carry on over the rows below rather than scroll sideways
 }
 
 impl ScreenOffset {
+    /// the columns of the screen that show text, right of the gutter
+    pub fn text_width(&self, size: &Size) -> usize {
+        size.width.saturating_sub(self.gutter)
+    }
+
+    /// size the gutter for the numbers of a buffer, returns whether its width changed
+    pub fn fit_gutter(&mut self, buffer_len: usize) -> bool {
+        let gutter = self.numbers.width(buffer_len);
+        let changed = gutter != self.gutter;
+        self.gutter = gutter;
+        changed
+    }
+
     pub fn to_position(self) -> ScreenPosition {
         ScreenPosition {
             height: self.height,
@@ -265,13 +301,59 @@ impl ScreenOffset {
             self.snap_left();
         }
 
-        if pos.width >= size.width.saturating_add(self.width) {
-            self.width = pos.width.saturating_sub(size.width).saturating_add(1);
+        if self.wrap {
+            self.width = 0;
+        } else if pos.width >= self.text_width(size).saturating_add(self.width) {
+            self.width = pos
+                .width
+                .saturating_sub(self.text_width(size))
+                .saturating_add(1);
         } else if pos.width < self.width {
             self.width = pos.width;
         }
     }
 
+    /// the rows of the view the cursor can be on, the status lines sit below them
+    pub fn cursor_rows(size: &Size) -> usize {
+        size.height.saturating_sub(2)
+    }
+
+    /// the scrolloff margin, shrunk to fit a short view
+    pub fn margin(size: &Size, scrolloff: usize) -> usize {
+        std::cmp::min(scrolloff, Self::cursor_rows(size).saturating_sub(1) / 2)
+    }
+
+    /// scroll so the cursor stays `scrolloff` rows away from the top and bottom of the view
+    /// the view does not scroll past the end of the buffer for it, returns whether it moved
+    pub fn keep_margin(
+        &mut self,
+        pos: &Position,
+        size: &Size,
+        scrolloff: usize,
+        buffer_len: usize,
+    ) -> bool {
+        let rows = Self::cursor_rows(size);
+        let margin = Self::margin(size, scrolloff);
+        let before = self.height;
+        // a view already scrolled past the end, with ctrl-e, keeps the cursor near its top
+        let past_end = self.height.saturating_add(rows) > buffer_len;
+        if pos.height < self.height.saturating_add(margin) && !past_end {
+            self.height = pos.height.saturating_sub(margin);
+        } else if pos.height.saturating_add(margin) >= self.height.saturating_add(rows) {
+            let wanted = pos
+                .height
+                .saturating_add(margin)
+                .saturating_add(1)
+                .saturating_sub(rows);
+            let last_top = std::cmp::max(
+                buffer_len.saturating_sub(rows),
+                pos.height.saturating_add(1).saturating_sub(rows),
+            );
+            self.height = std::cmp::max(self.height, std::cmp::min(wanted, last_top));
+        }
+        self.height != before
+    }
+
     pub fn update_offset_single_move(&mut self, pos: &Position, size: &Size, reserved: usize) {
         //if cursor moves beyond height + offset -> increment height offset
         if pos.below_view(self, size, reserved) {
@@ -365,12 +447,20 @@ impl Terminal {
     pub fn initialize() -> Result<(), Error> {
         enable_raw_mode()?;
         Self::enter_alternate_screen()?;
+        SCREEN.set(Some(Screen::default()));
         Self::clear_screen()?;
         Self::execute()?;
         Ok(())
     }
 
+    /// draw into a screen model that is never shown, for tests that run a mode
+    #[cfg(test)]
+    pub fn offscreen() {
+        SCREEN.set(Some(Screen::offscreen()));
+    }
+
     pub fn terminate() -> Result<(), Error> {
+        SCREEN.set(None);
         Self::leave_alternate_screen()?;
         Self::show_cursor()?;
         Self::set_cursor_style(SetCursorStyle::DefaultUserShape)?;
@@ -386,26 +476,38 @@ impl Terminal {
 
     pub fn set_background_color(color: Color) -> Result<(), Error> {
         Self::queue_command(SetBackgroundColor(color))?;
+        with_screen(|screen| screen.set_background(color));
         Ok(())
     }
 
     pub fn set_foreground_color(color: Color) -> Result<(), Error> {
         Self::queue_command(SetForegroundColor(color))?;
+        with_screen(|screen| screen.set_foreground(color));
         Ok(())
     }
 
     pub fn clear_screen() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::All))?;
+        if with_screen(|screen| screen.frame().clear()).is_none() {
+            Self::queue_command(Clear(ClearType::All))?;
+        }
         Ok(())
     }
 
     pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
+        let cleared = with_screen(|screen| {
+            let row = screen.cursor.height;
+            screen.frame().clear_row(row);
+        });
+        if cleared.is_none() {
+            Self::queue_command(Clear(ClearType::CurrentLine))?;
+        }
         Ok(())
     }
     pub fn move_cursor_to(position: ScreenPosition) -> Result<(), Error> {
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        Self::queue_command(MoveTo(position.width as u16, position.height as u16))?;
+        if with_screen(|screen| screen.cursor = position).is_none() {
+            #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+            Self::queue_command(MoveTo(position.width as u16, position.height as u16))?;
+        }
         Ok(())
     }
 
@@ -420,7 +522,7 @@ impl Terminal {
         })
     }
 
-    pub fn render_line<T: std::fmt::Display>(row: usize, line: T) -> Result<(), Error> {
+    pub fn render_line<T: Display>(row: usize, line: T) -> Result<(), Error> {
         Terminal::move_cursor_to(ScreenPosition {
             width: 0,
             height: row,
@@ -430,27 +532,50 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn print<T: std::fmt::Display>(output: T) -> Result<(), Error> {
-        Self::queue_command(Print(output))?;
+    pub fn print<T: Display>(output: T) -> Result<(), Error> {
+        let text = output.to_string();
+        if with_screen(|screen| screen.print(&text, ContentStyle::default())).is_none() {
+            Self::queue_command(Print(text))?;
+        }
+        Ok(())
+    }
+
+    pub fn print_styled<T: Display>(output: StyledContent<T>) -> Result<(), Error> {
+        let text = output.content().to_string();
+        if with_screen(|screen| screen.print(&text, *output.style())).is_none() {
+            Self::queue_command(PrintStyledContent(output))?;
+        }
         Ok(())
     }
 
+    /// send what changed since the last call to the terminal
     pub fn execute() -> Result<(), Error> {
+        with_screen(|screen| {
+            if screen.is_offscreen() {
+                return Ok(());
+            }
+            Self::size().and_then(|size| screen.flush(&mut stdout(), size))
+        })
+        .transpose()?;
         stdout().flush()?;
         Ok(())
     }
 
     pub fn hide_cursor() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
+        if with_screen(|screen| screen.cursor_visible = false).is_none() {
+            Self::queue_command(Hide)?;
+        }
         Ok(())
     }
 
     pub fn show_cursor() -> Result<(), Error> {
-        Self::queue_command(Show)?;
+        if with_screen(|screen| screen.cursor_visible = true).is_none() {
+            Self::queue_command(Show)?;
+        }
         Ok(())
     }
 
-    pub fn queue_command<T: Command>(command: T) -> Result<(), Error> {
+    fn queue_command<T: Command>(command: T) -> Result<(), Error> {
         queue!(stdout(), command)?;
         Ok(())
     }
@@ -471,23 +596,35 @@ impl Terminal {
         saved: bool,
         size: &Size,
         filename: Option<&str>,
+        format: FileFormat,
         line_pos: Option<(usize, usize)>,
     ) -> Result<(), Error> {
         let saved = if saved { "saved" } else { "modified" };
         let filename = filename.unwrap_or("-");
-        let mut render_message = if let Some((line, len)) = line_pos {
+        let render_message = if let Some((line, len)) = line_pos {
             format!(
-                "Mode: {} | Filename: {filename} | Status: {saved} | Line: {line} / {len}",
+                "Mode: {} | Filename: {filename} [{format}] | Status: {saved} | Line: {line} / {len}",
                 mode.to_string()
             )
         } else {
             format!(
-                "Mode: {} | Filename: {filename} | Status: {saved} | Line: -",
+                "Mode: {} | Filename: {filename} [{format}] | Status: {saved} | Line: -",
                 mode.to_string()
             )
         };
-        render_message.truncate(size.width);
-        Self::render_line(size.height.saturating_sub(1), render_message)?;
+        Self::render_line(
+            size.height.saturating_sub(1),
+            fit_width(&render_message, size.width),
+        )?;
+        Ok(())
+    }
+
+    /// render a one off message in place of the status line
+    pub fn render_status_message(size: &Size, message: &str) -> Result<(), Error> {
+        Self::render_line(
+            size.height.saturating_sub(1),
+            fit_width(message, size.width),
+        )?;
         Ok(())
     }
 
@@ -515,10 +652,33 @@ impl Terminal {
     }
 }
 
+/// the start of a text that fits in `width` columns, never cut inside a char
+fn fit_width(text: &str, width: usize) -> &str {
+    let mut columns: usize = 0;
+    for (index, c) in text.char_indices() {
+        columns = columns.saturating_add(c.width().unwrap_or_default());
+        if columns > width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn status_text_fits_width() {
+        assert_eq!(
+            fit_width("not a substitute command: äää", 27),
+            "not a substitute command: ä"
+        );
+        assert_eq!(fit_width("ab日本", 3), "ab");
+        assert_eq!(fit_width("ab日本", 4), "ab日");
+        assert_eq!(fit_width("short", 10), "short");
+    }
+
     #[test]
     fn test_pos_in_view() {
         //testing a position in the view
@@ -529,6 +689,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 1,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 12,
@@ -549,6 +710,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 1,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 0,
@@ -570,6 +732,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 2,
             width: 2,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 0,
@@ -591,6 +754,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 0,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 0,
@@ -612,6 +776,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 2,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 0,
@@ -632,6 +797,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 9,
             width: 8,
+            ..ScreenOffset::default()
         };
         let pos1 = Position {
             height: 0,
@@ -641,4 +807,52 @@ mod test {
 
         assert_eq!(pos1.max_displacement_from_view(&offset1, &size1, 1), 9);
     }
+
+    #[test]
+    fn keep_margin_scrolls_the_view() {
+        let size = Size {
+            height: 12,
+            width: 20,
+        };
+        let at = |height| Position {
+            height,
+            width: 0,
+            max_width: usize::default(),
+        };
+        let mut offset = ScreenOffset::default();
+        // ten cursor rows, the cursor may go down to row 7 with a margin of 2
+        assert!(!offset.keep_margin(&at(7), &size, 2, 100));
+        assert!(offset.keep_margin(&at(8), &size, 2, 100));
+        assert_eq!(offset.height, 1);
+        assert!(offset.keep_margin(&at(2), &size, 2, 100));
+        assert_eq!(offset.height, 0);
+        // the end of the buffer is reached without scrolling past it
+        offset.height = 90;
+        assert!(!offset.keep_margin(&at(99), &size, 2, 100));
+        // a margin too large for the view is kept to half of it
+        assert_eq!(ScreenOffset::margin(&size, 50), 4);
+    }
+
+    #[test]
+    fn gutter_shifts_the_text() {
+        let size = Size {
+            height: 12,
+            width: 20,
+        };
+        let mut offset = ScreenOffset::default();
+        offset.numbers.absolute = true;
+        assert!(offset.fit_gutter(1000));
+        assert!(!offset.fit_gutter(2000));
+        assert_eq!(offset.text_width(&size), 15);
+        let pos = Position {
+            height: 3,
+            width: 15,
+            max_width: usize::default(),
+        };
+        assert_eq!(pos.relative_view_position(&offset).width, 20);
+        assert!(pos.max_displacement_from_view(&offset, &size, 2) > 1);
+        offset.handle_offset_screen_snap(&pos, &size, 2, 2000);
+        assert_eq!(offset.width, 1);
+        assert_eq!(pos.relative_view_position(&offset).width, 19);
+    }
 }
@@ -0,0 +1,389 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::queue;
+use crossterm::style::{
+    Attribute, Color, ContentStyle, Print, SetAttribute, SetAttributes, SetBackgroundColor,
+    SetForegroundColor,
+};
+use crossterm::terminal::{Clear, ClearType};
+use std::io::{Error, Write};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{ScreenPosition, Size};
+
+// reprinting a short run of unchanged cells takes fewer bytes than moving the cursor past it
+const MAX_REPRINT: usize = 6;
+
+/// one column of the screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    symbol: String, // empty for the right half of a wide grapheme
+    style: ContentStyle,
+    wide: bool,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: String::from(" "),
+            style: ContentStyle::default(),
+            wide: false,
+        }
+    }
+}
+
+impl Cell {
+    fn continuation(style: ContentStyle) -> Self {
+        Self {
+            symbol: String::new(),
+            style,
+            wide: false,
+        }
+    }
+
+    fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+}
+
+/// what the screen shows, rows are only as long as what was drawn on them
+/// anything past the end of a row is blank
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Frame {
+    pub fn clear(&mut self) {
+        self.rows.clear();
+    }
+
+    pub fn clear_row(&mut self, row: usize) {
+        if let Some(cells) = self.rows.get_mut(row) {
+            cells.clear();
+        }
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> Option<&Cell> {
+        self.rows.get(row).and_then(|cells| cells.get(column))
+    }
+
+    /// draw text from a position, handing back the position after it
+    pub fn put(&mut self, at: ScreenPosition, text: &str, style: ContentStyle) -> ScreenPosition {
+        let mut column = at.width;
+        for grapheme in text.graphemes(true) {
+            // tabs and other control characters take a blank cell, like a Line draws a tab
+            let (symbol, width) = match grapheme.width() {
+                _ if grapheme.chars().any(char::is_control) => (" ", 1),
+                0 => (" ", 1),
+                width => (grapheme, width.min(2)),
+            };
+            self.set(
+                at.height,
+                column,
+                Cell {
+                    symbol: symbol.to_string(),
+                    style,
+                    wide: width == 2,
+                },
+            );
+            if width == 2 {
+                self.set(
+                    at.height,
+                    column.saturating_add(1),
+                    Cell::continuation(style),
+                );
+            }
+            column = column.saturating_add(width);
+        }
+        ScreenPosition {
+            height: at.height,
+            width: column,
+        }
+    }
+
+    /// overwrite a cell, blanking the other half of a wide grapheme it cuts through
+    fn set(&mut self, row: usize, column: usize, cell: Cell) {
+        if self.rows.len() <= row {
+            self.rows.resize_with(row.saturating_add(1), Vec::new);
+        }
+        let cells = &mut self.rows[row];
+        if cells.len() <= column {
+            cells.resize_with(column.saturating_add(1), Cell::default);
+        }
+        if cells[column].is_continuation() && !cell.is_continuation() && column > 0 {
+            cells[column.saturating_sub(1)] = Cell::default();
+        }
+        if cells[column].wide {
+            if let Some(next) = cells.get_mut(column.saturating_add(1)) {
+                *next = Cell::default();
+            }
+        }
+        cells[column] = cell;
+    }
+
+    /// the cells in a screen of the given size that differ from an earlier frame
+    pub fn changes(&self, previous: &Frame, size: &Size) -> Vec<ScreenPosition> {
+        let blank = Cell::default();
+        let mut changes = Vec::new();
+        for row in 0..size.height {
+            for column in 0..size.width {
+                let now = self.cell(row, column).unwrap_or(&blank);
+                let before = previous.cell(row, column).unwrap_or(&blank);
+                // the right half of a wide grapheme is drawn along with its left half
+                if now != before && !now.is_continuation() {
+                    changes.push(ScreenPosition {
+                        height: row,
+                        width: column,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// the frame being drawn and the one last sent to the terminal
+/// only the cells that differ between them are written out, so a redraw does not flicker
+#[derive(Default)]
+pub struct Screen {
+    next: Frame,
+    shown: Option<Frame>, // None when the terminal has to be cleared first
+    shown_size: Size,
+    pub cursor: ScreenPosition,
+    pub cursor_visible: bool,
+    foreground: Option<Color>, // the theme colors of cells drawn without a color
+    background: Option<Color>,
+    offscreen: bool, // drawn without a terminal, nothing is flushed
+}
+
+impl Screen {
+    /// a screen for tests that run a mode without a terminal
+    #[cfg(test)]
+    pub fn offscreen() -> Self {
+        Self {
+            offscreen: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_offscreen(&self) -> bool {
+        self.offscreen
+    }
+
+    pub fn frame(&mut self) -> &mut Frame {
+        &mut self.next
+    }
+
+    /// draw text at the cursor and move the cursor past it
+    pub fn print(&mut self, text: &str, style: ContentStyle) {
+        self.cursor = self.next.put(self.cursor, text, style);
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = Some(color);
+        self.shown = None;
+    }
+
+    pub fn set_background(&mut self, color: Color) {
+        self.background = Some(color);
+        self.shown = None;
+    }
+
+    /// write the changed cells and the cursor
+    pub fn flush<W: Write>(&mut self, out: &mut W, size: Size) -> Result<(), Error> {
+        if size != self.shown_size {
+            self.shown = None;
+        }
+        let shown = self.shown.take();
+        if shown.is_none() {
+            self.queue_default_style(out)?;
+            queue!(out, Clear(ClearType::All))?;
+        }
+        let changes = self.next.changes(&shown.unwrap_or_default(), &size);
+        if !changes.is_empty() {
+            queue!(out, Hide)?;
+        }
+        let mut pen: Option<ScreenPosition> = None;
+        let mut style: Option<ContentStyle> = None;
+        for at in changes {
+            if let Some(from) = pen.filter(|pen| {
+                pen.height == at.height
+                    && pen.width < at.width
+                    && at.width.saturating_sub(pen.width) <= MAX_REPRINT
+            }) {
+                for width in from.width..at.width {
+                    let gap = ScreenPosition {
+                        height: at.height,
+                        width,
+                    };
+                    let cell = self.next_cell(gap);
+                    if !cell.is_continuation() {
+                        self.queue_cell(out, &cell, gap, &mut pen, &mut style, size)?;
+                    }
+                }
+            }
+            let cell = self.next_cell(at);
+            self.queue_cell(out, &cell, at, &mut pen, &mut style, size)?;
+        }
+        if style.is_some() {
+            self.queue_default_style(out)?;
+        }
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+        queue!(
+            out,
+            MoveTo(self.cursor.width as u16, self.cursor.height as u16)
+        )?;
+        if self.cursor_visible {
+            queue!(out, Show)?;
+        } else {
+            queue!(out, Hide)?;
+        }
+        self.shown = Some(self.next.clone());
+        self.shown_size = size;
+        Ok(())
+    }
+
+    /// a cell of the frame being drawn, past the end of a row is blank
+    fn next_cell(&self, at: ScreenPosition) -> Cell {
+        self.next
+            .cell(at.height, at.width)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn queue_cell<W: Write>(
+        &self,
+        out: &mut W,
+        cell: &Cell,
+        at: ScreenPosition,
+        pen: &mut Option<ScreenPosition>,
+        style: &mut Option<ContentStyle>,
+        size: Size,
+    ) -> Result<(), Error> {
+        if *pen != Some(at) {
+            #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+            queue!(out, MoveTo(at.width as u16, at.height as u16))?;
+        }
+        if *style != Some(cell.style) {
+            self.queue_style(out, cell.style)?;
+            *style = Some(cell.style);
+        }
+        // a wide grapheme cut by the right edge would wrap onto the next row
+        let fits = !cell.wide || at.width.saturating_add(1) < size.width;
+        let (symbol, width) = match (cell.wide, fits) {
+            (true, true) => (cell.symbol.as_str(), 2),
+            (false, _) => (cell.symbol.as_str(), 1),
+            (true, false) => (" ", 1),
+        };
+        queue!(out, Print(symbol))?;
+        *pen = Some(ScreenPosition {
+            height: at.height,
+            width: at.width.saturating_add(width),
+        });
+        Ok(())
+    }
+
+    fn queue_style<W: Write>(&self, out: &mut W, style: ContentStyle) -> Result<(), Error> {
+        queue!(
+            out,
+            SetAttribute(Attribute::Reset),
+            SetForegroundColor(
+                style
+                    .foreground_color
+                    .or(self.foreground)
+                    .unwrap_or(Color::Reset)
+            ),
+            SetBackgroundColor(
+                style
+                    .background_color
+                    .or(self.background)
+                    .unwrap_or(Color::Reset)
+            ),
+        )?;
+        if !style.attributes.is_empty() {
+            queue!(out, SetAttributes(style.attributes))?;
+        }
+        Ok(())
+    }
+
+    fn queue_default_style<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        self.queue_style(out, ContentStyle::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Stylize;
+
+    fn at(height: usize, width: usize) -> ScreenPosition {
+        ScreenPosition { height, width }
+    }
+
+    const SIZE: Size = Size {
+        height: 4,
+        width: 10,
+    };
+
+    #[test]
+    fn only_changed_cells_are_damaged() {
+        let mut before = Frame::default();
+        before.put(at(0, 0), "hello", ContentStyle::default());
+        before.put(at(1, 0), "world", ContentStyle::default());
+        let mut after = before.clone();
+        // redrawing a row with the same text changes nothing
+        after.clear_row(0);
+        after.put(at(0, 0), "hello", ContentStyle::default());
+        assert!(after.changes(&before, &SIZE).is_empty());
+
+        after.put(at(0, 1), "a", ContentStyle::default());
+        after.clear_row(1);
+        after.put(at(1, 0), "word", ContentStyle::default());
+        assert_eq!(
+            after.changes(&before, &SIZE),
+            vec![at(0, 1), at(1, 3), at(1, 4)]
+        );
+
+        // the same text in another color is a change
+        let mut styled = before.clone();
+        styled.put(at(0, 0), "h", *"h".red().style());
+        assert_eq!(styled.changes(&before, &SIZE), vec![at(0, 0)]);
+    }
+
+    #[test]
+    fn wide_graphemes_take_two_cells() {
+        let mut frame = Frame::default();
+        let end = frame.put(at(0, 0), "a中b\t", ContentStyle::default());
+        assert_eq!(end, at(0, 5));
+        assert_eq!(frame.cell(0, 1).map(|cell| cell.wide), Some(true));
+        assert!(frame.cell(0, 2).is_some_and(Cell::is_continuation));
+
+        // writing over half of a wide grapheme blanks the other half
+        let before = frame.clone();
+        frame.put(at(0, 2), "x", ContentStyle::default());
+        assert_eq!(frame.cell(0, 1), Some(&Cell::default()));
+        assert_eq!(frame.changes(&before, &SIZE), vec![at(0, 1), at(0, 2)]);
+    }
+
+    #[test]
+    fn flush_writes_only_the_damage() {
+        let mut screen = Screen::default();
+        screen.print("hello", ContentStyle::default());
+        let mut first = Vec::new();
+        screen.flush(&mut first, SIZE).unwrap();
+        assert!(String::from_utf8_lossy(&first).contains("hello"));
+
+        screen.cursor = at(0, 4);
+        screen.print("!", ContentStyle::default());
+        let mut second = Vec::new();
+        screen.flush(&mut second, SIZE).unwrap();
+        let second = String::from_utf8_lossy(&second);
+        assert!(second.contains('!'));
+        assert!(!second.contains("hell"));
+
+        let mut idle = Vec::new();
+        screen.flush(&mut idle, SIZE).unwrap();
+        assert!(!String::from_utf8_lossy(&idle).contains('!'));
+    }
+}
@@ -1,22 +1,40 @@
 use super::editorcommands::{
     parse_highlight_normal_mode, Direction, EditorCommand, FileNameCommand, JumpCommand,
+    SelectionKind,
 };
 use super::terminal::{Coordinate, Mode, Position, ScreenOffset, ScreenPosition, Size, Terminal};
 use crossterm::event::read;
+use crossterm::style::Stylize;
 use std::{error::Error, path::Path};
 pub mod buffer;
 use buffer::Buffer;
+pub mod file_format;
+mod history;
 pub mod line;
 mod theme;
 use theme::Theme;
 mod search;
 use search::Search;
+pub mod gutter;
+mod keys;
+mod search_history;
+use keys::KeySource;
+mod syntax;
+use syntax::LineState;
+mod wrap;
+use search_history::SearchHistory;
 pub mod help;
+pub mod substitute;
 use help::Help;
 mod highlight;
-use highlight::Highlight;
+use highlight::{Highlight, HighlightExit};
+pub mod motion;
 mod vim_mode;
 use vim_mode::VimMode;
+mod registers;
+use registers::Registers;
+mod repeat;
+use repeat::{DotRepeat, LastChange, Repeatable};
 mod clipboard_interface;
 use clipboard_interface::ClipboardUtils;
 
@@ -30,6 +48,9 @@ enum ScreenUpdateType {
 pub const PROGRAM_NAME: &str = env!("CARGO_PKG_NAME");
 pub const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// like vim's defaults.vim, changed with :set scrolloff=
+const DEFAULT_SCROLLOFF: usize = 5;
+
 const ORIGIN_POSITION: Position = Position {
     height: 0_usize,
     width: 0_usize,
@@ -43,6 +64,11 @@ pub struct View {
     pub screen_offset: ScreenOffset,
     pub theme: Theme,
     pub buffer: Buffer,
+    pub registers: Registers,
+    pub dot_repeat: DotRepeat,
+    pub scrolloff: usize, // rows kept between the cursor and the top or bottom of the view
+    status_message: Option<String>, // shown instead of the status line until the next event
+    matched_bracket: Option<Position>, // drawn highlighted, cleared on the next event
 }
 
 impl Default for View {
@@ -53,6 +79,11 @@ impl Default for View {
             cursor_position: Position::default(),
             screen_offset: ScreenOffset::default(),
             theme: Theme::default(),
+            registers: Registers::default(),
+            dot_repeat: DotRepeat::default(),
+            scrolloff: DEFAULT_SCROLLOFF,
+            status_message: None,
+            matched_bracket: None,
         }
     }
 }
@@ -66,47 +97,79 @@ impl View {
     }
 
     pub fn render(&self, full_screen: bool) {
-        let start = if full_screen {
+        // a wrapped line moves every row below it, so a wrapped view is always drawn whole
+        let start = if full_screen || self.screen_offset.wrap {
             self.screen_offset.height
         } else {
             self.cursor_position.height.saturating_sub(1)
         };
-        #[allow(clippy::integer_division)]
-        for current_row in start
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(self.size.height)
-                .saturating_sub(1)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
-
-            if let Some(line) = self.buffer.text.get(current_row) {
-                Self::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
-                    ),
-                );
-            } else if self.buffer.is_empty() & (current_row == self.size.height / 3) {
-                Self::render_line(
-                    relative_row,
-                    Terminal::get_welcome_message(&self.size, &self.screen_offset),
-                );
-            } else {
-                Self::render_line(relative_row, "~");
-            }
+        let res = wrap::render_text(
+            &self.buffer,
+            &self.screen_offset,
+            &self.size,
+            start,
+            self.cursor_position.height,
+            &self.theme.syntax,
+        );
+        debug_assert!(res.is_ok(), "Failed to render");
+    }
+
+    /// redraw the row of the last bracket highlight, then highlight the bracket paired with
+    /// the one under the cursor, returns what was highlighted so the next call can clear it
+    fn render_matching_bracket(
+        buffer: &Buffer,
+        cursor: &Position,
+        offset: &ScreenOffset,
+        size: &Size,
+        previous: Option<Position>,
+        theme: &Theme,
+    ) -> Option<Position> {
+        let on_screen = |pos: &Position| wrap::screen_cell(buffer, pos, offset, size).is_some();
+        if let Some(previous) = previous.filter(on_screen) {
+            let res = wrap::render_line(
+                buffer,
+                offset,
+                size,
+                previous.height,
+                cursor.height,
+                &theme.syntax,
+            );
+            debug_assert!(res.is_ok());
         }
+        let pair = buffer.matching_bracket(cursor)?;
+        let cell = wrap::screen_cell(buffer, &pair, offset, size)?;
+        let bracket = buffer
+            .line(pair.height)
+            .grapheme_slice(pair.width..pair.width.saturating_add(1))
+            .to_string();
+        let res = Terminal::move_cursor_to(cell)
+            .and_then(|()| Terminal::print_styled(bracket.with(theme.text).on(theme.highlight)));
+        debug_assert!(res.is_ok());
+        Some(pair)
+    }
+
+    fn highlight_matching_bracket(&mut self) {
+        self.matched_bracket = Self::render_matching_bracket(
+            &self.buffer,
+            &self.cursor_position,
+            &self.screen_offset,
+            &self.size,
+            self.matched_bracket,
+            &self.theme,
+        );
     }
 
     #[inline] // this should be very hot
-    fn evaluate_view_state_change(&mut self) -> ScreenUpdateType {
+    /// `end_before` is what the edited line left open for the lines below before the edit
+    fn evaluate_view_state_change(&mut self, end_before: LineState) -> ScreenUpdateType {
         let view_delta = self.check_offset();
-        if view_delta == 0 {
+        if view_delta != 0 {
+            ScreenUpdateType::FullScreen
+        } else if self.buffer.end_state(self.cursor_position.height) == end_before {
             ScreenUpdateType::SingleLineRender
         } else {
-            ScreenUpdateType::FullScreen
+            // opening or closing a comment or string recolors the lines below
+            ScreenUpdateType::MultiLineRender
         }
     }
 
@@ -115,15 +178,41 @@ impl View {
         Terminal::move_cursor_to(self.screen_offset.to_position())?;
         Terminal::clear_screen()?;
         self.render(true);
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
+        Terminal::move_cursor_to(self.screen_cursor())?;
         Terminal::show_cursor()?;
         Terminal::execute()?;
         Ok(())
     }
 
+    /// where the cursor is drawn
+    fn screen_cursor(&self) -> ScreenPosition {
+        wrap::screen_position(
+            &self.buffer,
+            &self.cursor_position,
+            &self.screen_offset,
+            &self.size,
+        )
+    }
+
+    /// draw a buffer line on a screen row, colored by the syntax of the file
+    fn render_buffer_line(&self, row: usize, index: usize) {
+        if self.screen_offset.wrap {
+            self.render(true);
+            return;
+        }
+        let offset = &self.screen_offset;
+        let res =
+            gutter::start_row(row, index, self.cursor_position.height, offset).and_then(|()| {
+                syntax::print_line(
+                    &self.buffer,
+                    index,
+                    offset.width..offset.width.saturating_add(offset.text_width(&self.size)),
+                    &self.theme.syntax,
+                )
+            });
+        debug_assert!(res.is_ok(), "Failed to render line");
+    }
+
     #[inline]
     fn render_line<T: std::fmt::Display>(row: usize, line: T) {
         let result = Terminal::render_line(row, line);
@@ -148,7 +237,7 @@ impl View {
         if let Ok(buffer) = Buffer::load(filename) {
             self.buffer = buffer;
         } else {
-            self.buffer = Buffer::load_named_empty(filename, self.size.height);
+            self.buffer = Buffer::load_named_empty(filename);
         }
 
         Ok(())
@@ -233,46 +322,54 @@ impl View {
     }
 
     fn set_cursor_and_status(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::render_status_line(
-            &Mode::Insert,
-            self.buffer.is_saved,
-            &self.size,
-            self.buffer.filename.as_deref(),
-            Some((
-                self.cursor_position.height.saturating_add(1),
-                std::cmp::max(self.buffer.len(), 1),
-            )),
-        )?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
+        if let Some(message) = &self.status_message {
+            Terminal::render_status_message(&self.size, message)?;
+        } else {
+            Terminal::render_status_line(
+                &Mode::Insert,
+                self.buffer.is_saved,
+                &self.size,
+                self.buffer.filename.as_deref(),
+                self.buffer.format,
+                Some((
+                    self.cursor_position.height.saturating_add(1),
+                    std::cmp::max(self.buffer.len(), 1),
+                )),
+            )?;
+        }
+        Terminal::move_cursor_to(self.screen_cursor())?;
         Terminal::show_cursor()?;
         Ok(())
     }
 
-    fn save(&mut self) -> Result<(), Box<dyn Error>> {
-        // no need to render here
-        if self.buffer.filename.is_none() {
+    /// returns whether the screen needs a full render
+    /// the file name prompt takes over the screen
+    fn save(&mut self) -> bool {
+        let prompted = self.buffer.filename.is_none();
+        if prompted {
             self.get_file_name();
         }
-        self.buffer.save();
-        // onyl status line needs to change
-        Terminal::render_status_line(
-            &Mode::Insert,
-            self.buffer.is_saved,
-            &self.size,
-            self.buffer.filename.as_deref(),
-            Some((
-                self.cursor_position.height.saturating_add(1),
-                self.buffer.len(),
-            )),
-        )?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-        Ok(())
+        if let Err(err) = self.buffer.save() {
+            self.status_message = Some(format!("Save failed: {err}"));
+        }
+        prompted
+    }
+
+    /// save ahead of ending the session
+    /// returns false when the save failed and the session should stay open
+    pub fn save_before_exit(&mut self) -> bool {
+        self.save();
+        if self.buffer.is_saved {
+            return true;
+        }
+        // the exit prompt took over the screen
+        let res = self.full_screen_render();
+        debug_assert!(res.is_ok());
+        let res = self.set_cursor_and_status();
+        debug_assert!(res.is_ok());
+        let res = Terminal::execute();
+        debug_assert!(res.is_ok());
+        false
     }
 
     fn paste_text(&mut self) -> Option<bool> {
@@ -282,16 +379,54 @@ impl View {
         };
         self.buffer
             .add_text_from_clipboard(&paste_text, &mut self.cursor_position);
+        self.check_offset();
         None
     }
 
+    /// typing ends on the first command that is not typing, completing the change for .
+    /// I or A on a block then puts the text typed on the top row into the rows below
+    fn finish_typing(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(typed) = self.buffer.history.take_typed() else {
+            return Ok(());
+        };
+        let Some(LastChange {
+            command: Repeatable::BlockInsert { at, rows, pad },
+            inserted,
+        }) = self.dot_repeat.finish_typing(typed)
+        else {
+            return Ok(());
+        };
+        let filled = self.buffer.insert_block_rows(
+            &inserted,
+            at.height.saturating_add(1),
+            at.width,
+            rows.saturating_sub(1),
+            pad,
+        );
+        if !filled {
+            self.status_message =
+                Some("Text with a line break is only put on the top row of a block".to_string());
+        }
+        self.buffer.history.seal(self.cursor_position);
+        if filled {
+            self.full_screen_render()?;
+        }
+        Ok(())
+    }
+
     fn enter_vim_mode(&mut self) -> bool {
         let mut vim_mode = VimMode::new(
             self.cursor_position,
             self.screen_offset,
             self.size,
             &mut self.buffer,
+            &mut self.registers,
+            &mut self.dot_repeat,
+            &mut self.scrolloff,
         );
+        if let Some(message) = self.status_message.take() {
+            vim_mode.report(&message);
+        }
         vim_mode.run(
             &mut self.cursor_position,
             &mut self.screen_offset,
@@ -306,32 +441,59 @@ impl View {
             self.screen_offset,
             &mut self.size,
             &mut self.buffer,
+            SelectionKind::Chars,
         );
-        highlight.run(
+        let exit = highlight.run(
             self.theme.highlight,
             self.theme.text,
             parse_highlight_normal_mode,
+            &mut KeySource::default(),
         );
+        if let HighlightExit::Copy(register) = exit {
+            if !register.text.is_empty() {
+                if let Err(err) = ClipboardUtils::copy_text_to_clipboard(register.text) {
+                    self.status_message = Some(format!("clipboard not available: {err}"));
+                }
+            }
+        }
     }
 
-    fn enter_search_mode(&mut self) {
+    /// a replacing search asks for the replacement once the query is entered
+    fn enter_search_mode(&mut self, replacing: bool) {
         let mut search = Search::new(
             self.cursor_position,
             self.screen_offset,
             self.theme.highlight,
             self.theme.text,
         );
-        search.run(
+        if replacing {
+            search = search.replacing();
+        }
+        let saved = search.run(
             &mut self.cursor_position,
             &mut self.screen_offset,
             &mut self.size,
-            &self.buffer,
+            &mut self.buffer,
+            self.registers.search_history(),
+            &mut KeySource::default(),
         );
+        if let Err(err) = saved {
+            self.status_message = Some(format!("Search history not saved: {err}"));
+        }
+    }
+
+    /// the searches saved by earlier sessions, a view starts out with none
+    pub fn load_search_history(&mut self) {
+        *self.registers.search_history() = SearchHistory::load();
     }
 
     pub fn handle_event(&mut self, command: EditorCommand) -> Result<bool, Box<dyn Error>> {
         let mut continue_status: bool = true;
         let mut render_type: ScreenUpdateType = ScreenUpdateType::DefaultAction;
+        self.status_message = None;
+        if !command.is_typing() {
+            self.finish_typing()?;
+        }
         match command {
             EditorCommand::Move(direction) => {
                 // if offset changes, render the entire screen
@@ -343,28 +505,31 @@ impl View {
                     ScreenUpdateType::FullScreen
                 };
             }
-            EditorCommand::Insert(char) => {
+            EditorCommand::Insert(_) | EditorCommand::Tab => {
                 let was_empty = self.buffer.is_empty();
-                self.insert_char(char);
+                let end_before = self.buffer.end_state(self.cursor_position.height);
+                if let EditorCommand::Insert(char) = command {
+                    self.insert_char(char);
+                } else {
+                    self.insert_tab();
+                }
                 render_type = if was_empty {
                     ScreenUpdateType::FullScreen
                 } else {
-                    self.evaluate_view_state_change()
+                    self.evaluate_view_state_change(end_before)
                 };
             }
-            EditorCommand::Delete => {
-                self.deletion();
-            }
-            EditorCommand::Tab => {
-                self.insert_tab();
-                render_type = self.evaluate_view_state_change();
-            }
+            EditorCommand::Delete => self.deletion(),
             EditorCommand::NewLine => {
                 self.new_line();
                 render_type = ScreenUpdateType::MultiLineRender;
             }
             EditorCommand::JumpWord(direction) => self.jump_word(direction),
-            EditorCommand::Save => self.save()?,
+            EditorCommand::Save => {
+                if self.save() {
+                    render_type = ScreenUpdateType::FullScreen;
+                }
+            }
             EditorCommand::Resize(size) => {
                 // render always
                 self.resize(size);
@@ -390,26 +555,40 @@ impl View {
                 let _ = self.check_offset(); // making sure the offset is correct on a delete
                 render_type = ScreenUpdateType::FullScreen;
             }
-            EditorCommand::Search => {
-                self.enter_search_mode();
+            EditorCommand::Search | EditorCommand::Replace => {
+                self.enter_search_mode(matches!(command, EditorCommand::Replace));
                 render_type = ScreenUpdateType::FullScreen;
             }
-            EditorCommand::JumpLine => {
-                render_type = self.jump_cursor()?;
-            }
+            EditorCommand::JumpLine => render_type = self.jump_cursor()?,
             EditorCommand::Help => {
                 Help::render_help(&mut self.size, self.theme.highlight, self.theme.text);
                 self.full_screen_render()?;
             }
-
             EditorCommand::Quit => continue_status = false,
             EditorCommand::Theme => {
                 self.theme.set_theme();
                 render_type = ScreenUpdateType::FullScreen;
             }
+            EditorCommand::Undo => {
+                if self.buffer.undo(&mut self.cursor_position) {
+                    self.check_offset();
+                    render_type = ScreenUpdateType::FullScreen;
+                }
+            }
+            EditorCommand::Redo => {
+                if self.buffer.redo(&mut self.cursor_position) {
+                    self.check_offset();
+                    render_type = ScreenUpdateType::FullScreen;
+                }
+            }
             EditorCommand::None => {}
         }
+        // a run of typed word chars is undone as a single edit
+        if !matches!(command, EditorCommand::Insert(c) if !c.is_whitespace()) {
+            self.buffer.history.seal(self.cursor_position);
+        }
         self.eval_screen_update(&render_type)?;
+        self.highlight_matching_bracket();
         self.set_cursor_and_status()?;
         Terminal::execute()?;
         Ok(continue_status)
@@ -419,14 +598,11 @@ impl View {
         match update_t {
             ScreenUpdateType::FullScreen => self.full_screen_render()?,
             ScreenUpdateType::SingleLineRender => {
-                Self::render_line(
+                self.render_buffer_line(
                     self.cursor_position
                         .height
                         .saturating_sub(self.screen_offset.height),
-                    self.buffer.text[self.cursor_position.height].get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
-                    ),
+                    self.cursor_position.height,
                 );
             }
             ScreenUpdateType::MultiLineRender => {
@@ -436,6 +612,16 @@ impl View {
                 // no additional action is required
             }
         }
+        // relative numbers follow the cursor even when the text stays put
+        if self.screen_offset.numbers.relative && !matches!(update_t, ScreenUpdateType::FullScreen)
+        {
+            gutter::render_labels(
+                &self.buffer,
+                &self.screen_offset,
+                &self.size,
+                self.cursor_position.height,
+            )?;
+        }
         Ok(())
     }
 
@@ -452,6 +638,11 @@ impl View {
 
     #[inline]
     fn check_offset(&mut self) -> usize {
+        // the gutter widens as the buffer grows past a power of ten, moving all the text
+        if self.screen_offset.fit_gutter(self.buffer.len()) {
+            self.check_offset();
+            return 2;
+        }
         let view_delta =
             self.cursor_position
                 .max_displacement_from_view(&self.screen_offset, &self.size, 2);
@@ -467,6 +658,24 @@ impl View {
                 self.buffer.len(),
             ),
         }
+        // a scroll to keep the margin moves every row on screen
+        if self.screen_offset.keep_margin(
+            &self.cursor_position,
+            &self.size,
+            self.scrolloff,
+            self.buffer.len(),
+        ) {
+            return std::cmp::max(view_delta, 2);
+        }
+        // wrapped lines take more than one row, so the line count alone may leave the cursor below
+        if wrap::keep_in_view(
+            &self.buffer,
+            &self.cursor_position,
+            &mut self.screen_offset,
+            &self.size,
+        ) {
+            return std::cmp::max(view_delta, 2);
+        }
         view_delta
     }
 
@@ -478,21 +687,22 @@ impl View {
         match self.cursor_position.width {
             0 => match (
                 self.cursor_position.at_top(),
-                self.buffer.text[self.cursor_position.height].is_empty(),
+                self.buffer.line(self.cursor_position.height).is_empty(),
             ) {
                 (true, true) => return,
                 (false, true) => {
-                    self.buffer.text.remove(self.cursor_position.height);
+                    self.buffer.pop_line(self.cursor_position.height);
                     self.cursor_position.up(1);
                     self.cursor_position
-                        .set_width(self.buffer.text[self.cursor_position.height].grapheme_len());
+                        .set_width(self.buffer.line(self.cursor_position.height).grapheme_len());
                 }
                 _ => {
                     // get length of 1 line above
                     // this will be new width after join line operation
-                    let prev_line_width = self.buffer.text
-                        [self.cursor_position.height.saturating_sub(1)]
-                    .grapheme_len();
+                    let prev_line_width = self
+                        .buffer
+                        .line(self.cursor_position.height.saturating_sub(1))
+                        .grapheme_len();
                     self.buffer.join_line(self.cursor_position.height);
                     self.cursor_position.up(1);
                     self.cursor_position.set_width(prev_line_width);
@@ -506,10 +716,7 @@ impl View {
         // evaluate how much of the screen we need to render
         let view_delta = self.check_offset();
         self.render(view_delta > 0);
-        let move_res = Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        );
+        let move_res = Terminal::move_cursor_to(self.screen_cursor());
         debug_assert!(move_res.is_ok());
     }
 
@@ -521,13 +728,7 @@ impl View {
             .saturating_sub(2);
 
         if self.buffer.len() >= l {
-            Terminal::render_line(
-                self.size.height.saturating_sub(2),
-                self.buffer.text[l].get_line_subset(
-                    self.screen_offset.width
-                        ..self.screen_offset.width.saturating_add(self.size.width),
-                ),
-            )?;
+            self.render_buffer_line(self.size.height.saturating_sub(2), l);
         } else {
             Terminal::render_line(self.size.height.saturating_sub(1), "~")?;
         }
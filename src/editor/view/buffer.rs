@@ -1,99 +1,212 @@
-use super::line::{GraphemeWidth, Line, TextFragment};
+use super::file_format::{FileFormat, LineEnding};
+use super::history::{Change, EditHistory};
+use super::line::{GraphemeWidth, Line};
+use super::syntax::{tokenize, Language, LineState, SyntaxCache, TokenKind};
 use crate::editor::view::Position;
-use std::fs::{read_to_string, OpenOptions};
-use std::io::{Error, LineWriter, Write};
+use regex::{Captures, Regex};
+use ropey::Rope;
+use std::cell::RefCell;
+use std::fs::{self, read_to_string, File, OpenOptions, Permissions};
+use std::io::{BufWriter, Error, ErrorKind, IntoInnerError, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
+
+// the pairs % jumps between
+const BRACKETS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+
+/// a search hit, columns are grapheme indices on the line of `start`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchMatch {
+    pub start: Position,
+    pub end: usize,                // the column after the match
+    pub groups: Vec<Range<usize>>, // the capture groups that took part in the match
+}
+
+impl SearchMatch {
+    /// the hit for a regex match on a line
+    pub fn from_captures(line: &Line, height: usize, captures: &Captures) -> Self {
+        // regex offsets are bytes, matches are kept in grapheme columns
+        let columns = |found: regex::Match| {
+            line.grapheme_index(found.start())..line.grapheme_index(found.end())
+        };
+        let found = captures.get(0).map(columns).unwrap_or_default();
+        Self {
+            start: Position {
+                height,
+                width: found.start,
+                max_width: found.start,
+            },
+            end: found.end,
+            groups: captures
+                .iter()
+                .skip(1)
+                .flatten()
+                .filter(|group| !group.is_empty())
+                .map(columns)
+                .collect(),
+        }
+    }
+}
 
+/// the buffer text is held in a rope
+/// lines are only materialized into a `Line` when they are read
 #[derive(Default, Clone)]
 pub struct Buffer {
-    pub text: Vec<Line>,
+    text: Rope,
     pub filename: Option<String>,
     pub is_saved: bool,
+    pub history: EditHistory,
+    pub format: FileFormat,
+    syntax: RefCell<SyntaxCache>, // filled in as lines are drawn
 }
 
 impl Buffer {
     pub fn is_empty(&self) -> bool {
-        self.text.is_empty()
+        self.text.len_chars() == 0
     }
 
     pub fn len(&self) -> usize {
-        self.text.len()
-    }
-
-    pub fn add_text_from_clipboard(&mut self, paste_text: &str, pos: &mut Position) {
-        // getting buff len
-        // when adding if current pos > buff_len
-        // need to add to buffer vec
-        let mut buff_len = if self.is_empty() {
+        if self.is_empty() {
             0
         } else {
-            self.len().saturating_sub(1)
-        };
-        for (i, line_str) in paste_text.lines().enumerate() {
-            if i != 0 {
-                pos.height = pos.height.saturating_add(1);
-                pos.width = 0;
-            }
+            self.text.len_lines()
+        }
+    }
 
-            if pos.height > buff_len {
-                self.text.push(Line::from(line_str));
-                buff_len = buff_len.saturating_add(1);
-                continue;
-            }
-            for c in line_str.chars() {
-                self.update_line_insert(pos, c);
-            }
+    pub fn get_line(&self, index: usize) -> Option<Line> {
+        if index >= self.len() {
+            return None;
         }
+        let mut line = String::from(self.text.line(index));
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Some(Line::from(line.as_str()))
+    }
+
+    /// the highlighted runs of a line in grapheme indices, empty for a file with no language
+    pub fn tokens(&self, index: usize) -> Vec<(Range<usize>, TokenKind)> {
+        let Some(language) = self.filename.as_deref().and_then(Language::from_filename) else {
+            return Vec::new();
+        };
+        let state = self
+            .syntax
+            .borrow_mut()
+            .state(language, index, |above| self.line(above).raw_string);
+        let line = self.line(index);
+        let (tokens, _) = tokenize(language, &line.raw_string, state);
+        tokens
+            .into_iter()
+            .map(|(range, kind)| {
+                (
+                    line.grapheme_index(range.start)..line.grapheme_index(range.end),
+                    kind,
+                )
+            })
+            .collect()
+    }
+
+    /// what a line leaves open for the lines below it, such as an unclosed comment
+    pub fn end_state(&self, index: usize) -> LineState {
+        let Some(language) = self.filename.as_deref().and_then(Language::from_filename) else {
+            return LineState::Normal;
+        };
+        self.syntax
+            .borrow_mut()
+            .state(language, index.saturating_add(1), |above| {
+                self.line(above).raw_string
+            })
+    }
+
+    /// the line at an index, lines out of the buffer are empty
+    pub fn line(&self, index: usize) -> Line {
+        self.get_line(index).unwrap_or_default()
     }
 
-    pub fn load_named_empty(filename: &str, screen_height: usize) -> Buffer {
-        let text: Vec<Line> = Vec::with_capacity(screen_height);
+    pub fn add_text_from_clipboard(&mut self, paste_text: &str, pos: &mut Position) {
+        // normalize the line endings coming from the clipboard
+        let text = paste_text.lines().collect::<Vec<&str>>().join("\n");
+        let end = self.insert_text(pos, &text);
+        pos.set_position(end);
+        pos.max_width = end.width;
+    }
+
+    pub fn load_named_empty(filename: &str) -> Buffer {
         Self {
-            text,
+            text: Rope::new(),
             filename: Some(filename.to_string()),
             is_saved: false,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         }
     }
 
     pub fn load(filename: &str) -> Result<Buffer, Error> {
         let file_contents = read_to_string(filename)?;
-        // size of file + 10% for starting capacity
-        let starting_capacity = (file_contents.len() as f32 * 1.1_f32) as usize;
-        let mut text = Vec::with_capacity(starting_capacity);
-        for line in file_contents.lines() {
-            text.push(Line::from(line));
-        }
+        // line endings, BOM and the final new line are added back on save
+        let (format, text) = FileFormat::detect(&file_contents);
 
         Ok(Self {
-            text,
+            text: Rope::from_str(&text),
             filename: Some(filename.to_string()),
             is_saved: true,
+            history: EditHistory::default(),
+            format,
+            syntax: RefCell::default(),
         })
     }
 
-    pub fn search(&self, search_str: &str) -> Vec<Position> {
-        //change to return a vector of positions of search results
-        let mut positions: Vec<Position> = Vec::new();
+    /// change the line endings the file is written with
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.format.line_ending != line_ending {
+            self.format.line_ending = line_ending;
+            self.is_saved = false;
+        }
+    }
 
-        for (i, line) in self.text.iter().enumerate() {
-            if line.raw_string.contains(search_str) {
-                let resulting_widths = self.find_search_widths(search_str, i);
-                for width in resulting_widths {
-                    positions.push(Position {
-                        width,
-                        height: i,
-                        max_width: 0_usize,
-                    });
-                }
-            }
+    /// every match of a pattern, matches do not cross lines and empty matches are skipped
+    pub fn search(&self, pattern: &Regex) -> Vec<SearchMatch> {
+        let mut matches: Vec<SearchMatch> = Vec::new();
+        for height in 0..self.len() {
+            let line = self.line(height);
+            matches.extend(
+                pattern
+                    .captures_iter(&line.raw_string)
+                    .filter(|captures| captures.get(0).is_some_and(|found| !found.is_empty()))
+                    .map(|captures| SearchMatch::from_captures(&line, height, &captures)),
+            );
         }
-        positions
+        matches
+    }
+
+    /// the word under or after the cursor on its line, for * and #
+    pub fn keyword_at(&self, pos: &Position) -> Option<String> {
+        let line = self.get_line(pos.height)?;
+        let graphemes: Vec<&str> = line.raw_string.graphemes(true).collect();
+        let is_keyword = |index: &usize| {
+            graphemes[*index]
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_')
+        };
+        let first = (pos.width..graphemes.len()).find(is_keyword)?;
+        let start = (0..first)
+            .rev()
+            .take_while(is_keyword)
+            .last()
+            .unwrap_or(first);
+        let end = (first..graphemes.len())
+            .find(|index| !is_keyword(index))
+            .unwrap_or(graphemes.len());
+        Some(graphemes[start..end].concat())
     }
 
     pub fn add_new_line(&mut self, pos: &mut Position) {
         let grapheme_len = if self.is_empty() {
             0
         } else {
-            self.text[pos.height].grapheme_len()
+            self.line(pos.height).grapheme_len()
         };
 
         // if at end of current line -> new blank line
@@ -127,13 +240,13 @@ impl Buffer {
             return;
         }
 
-        if let Some(new_width) = self.text[position.height].get_prev_word(position.width) {
+        if let Some(new_width) = self.line(position.height).get_prev_word(position.width) {
             position.width = new_width;
             return;
         }
         while position.height > 0 {
             position.height = position.height.saturating_sub(1);
-            if let Some(new_width) = self.text[position.height].get_prev_word_spillover() {
+            if let Some(new_width) = self.line(position.height).get_prev_word_spillover() {
                 position.width = new_width;
                 return;
             }
@@ -152,151 +265,233 @@ impl Buffer {
             return;
         }
 
-        if let Some(new_width) = self.text[position.height].get_next_word(position.width) {
+        if let Some(new_width) = self.line(position.height).get_next_word(position.width) {
             position.width = new_width;
             return;
         }
 
         // here look for the next char following a space
         // go to next line until we reach EOF
-        while position.height < self.text.len().saturating_sub(1) {
+        while position.height < self.len().saturating_sub(1) {
             position.height = position.height.saturating_add(1);
 
-            if let Some(new_width) = self.text[position.height].next_word_spillover() {
+            if let Some(new_width) = self.line(position.height).next_word_spillover() {
                 position.width = new_width;
                 return;
             }
         }
-        position.width = self.text[position.height].grapheme_len();
+        position.width = self.line(position.height).grapheme_len();
+    }
+
+    /// whether a line holds nothing but white space, these lines seperate paragraphs
+    pub fn is_blank_line(&self, line_index: usize) -> bool {
+        self.line(line_index).raw_string.trim().is_empty()
     }
 
-    fn find_search_widths(&self, search_str: &str, line_index: usize) -> Vec<usize> {
-        let mut string_split = self
-            .text
-            .get(line_index)
-            .expect("Out of bounds error")
-            .raw_string
-            .split(search_str);
-        let search_len = search_str.len();
-        let first = string_split.next().expect("No split results");
-        let mut running_len = first.len();
-        let mut widths: Vec<usize> = vec![running_len];
-        for slice in string_split {
-            let current_len = slice.len();
-            running_len = running_len
-                .saturating_add(search_len)
-                .saturating_add(current_len);
-            widths.push(running_len);
+    /// } the first blank line after the paragraph, the end of the buffer when there is none
+    /// blank lines at the position are passed over first
+    pub fn find_next_paragraph(&self, position: &mut Position) {
+        if self.is_empty() {
+            return;
+        }
+        let last = self.len().saturating_sub(1);
+        let mut height = position.height;
+        while height < last && self.is_blank_line(height) {
+            height = height.saturating_add(1);
         }
-        widths.pop();
-        widths
+        while height < last && !self.is_blank_line(height) {
+            height = height.saturating_add(1);
+        }
+        position.height = height;
+        position.width = if self.is_blank_line(height) {
+            0
+        } else {
+            self.line(height).grapheme_len()
+        };
+        position.max_width = position.width;
+    }
+
+    /// { the first blank line before the paragraph, the start of the buffer when there is none
+    pub fn find_prev_paragraph(&self, position: &mut Position) {
+        if self.is_empty() {
+            return;
+        }
+        let mut height = position.height;
+        while height > 0 && self.is_blank_line(height) {
+            height = height.saturating_sub(1);
+        }
+        while height > 0 && !self.is_blank_line(height) {
+            height = height.saturating_sub(1);
+        }
+        position.height = height;
+        position.width = 0;
+        position.max_width = 0;
+    }
+
+    /// ) the start of the next sentence, the blank line after a paragraph is a stop too
+    pub fn find_next_sentence(&self, position: &mut Position) {
+        if self.is_empty() {
+            return;
+        }
+        let last = self.len().saturating_sub(1);
+        let mut line = position.height;
+        loop {
+            let (_, run_end, stops) = self.sentence_stops(line);
+            if let Some(stop) = stops
+                .into_iter()
+                .find(|stop| (stop.height, stop.width) > (position.height, position.width))
+            {
+                *position = stop;
+                return;
+            }
+            if run_end >= last {
+                position.height = last;
+                position.width = self.line(last).grapheme_len();
+                position.max_width = position.width;
+                return;
+            }
+            line = run_end.saturating_add(1);
+        }
+    }
+
+    /// ( the start of the sentence, or of the one before when already at its start
+    pub fn find_prev_sentence(&self, position: &mut Position) {
+        if self.is_empty() {
+            return;
+        }
+        let mut line = position.height;
+        loop {
+            let (run_start, _, stops) = self.sentence_stops(line);
+            if let Some(stop) = stops
+                .into_iter()
+                .rev()
+                .find(|stop| (stop.height, stop.width) < (position.height, position.width))
+            {
+                *position = stop;
+                return;
+            }
+            if run_start == 0 {
+                *position = Position::default();
+                return;
+            }
+            line = run_start.saturating_sub(1);
+        }
+    }
+
+    // the first and last line of the paragraph, or run of blank lines, holding a line
+    // with where sentences start in it, a run of blank lines is a single stop
+    // a sentence ends at . ! or ? and any closing brackets or quotes, then white space
+    fn sentence_stops(&self, line_index: usize) -> (usize, usize, Vec<Position>) {
+        let blank = self.is_blank_line(line_index);
+        let last_line = self.len().saturating_sub(1);
+        let mut first = line_index;
+        while first > 0 && self.is_blank_line(first.saturating_sub(1)) == blank {
+            first = first.saturating_sub(1);
+        }
+        let mut last = line_index;
+        while last < last_line && self.is_blank_line(last.saturating_add(1)) == blank {
+            last = last.saturating_add(1);
+        }
+        let at = |height: usize, width: usize| Position {
+            height,
+            width,
+            max_width: width,
+        };
+        if blank {
+            return (first, last, vec![at(first, 0)]);
+        }
+        let mut stops = Vec::new();
+        // the paragraph itself starts a sentence
+        let mut ended = true;
+        let mut gap = true;
+        for height in first..=last {
+            let line = self.line(height);
+            for (width, grapheme) in line.raw_string.graphemes(true).enumerate() {
+                if grapheme.trim().is_empty() {
+                    gap = true;
+                    continue;
+                }
+                let starts = ended && gap;
+                if starts {
+                    stops.push(at(height, width));
+                }
+                ended = matches!(grapheme, "." | "!" | "?")
+                    || (ended && !starts && matches!(grapheme, ")" | "]" | "\"" | "'"));
+                gap = false;
+            }
+            gap = true; // the line break
+        }
+        (first, last, stops)
     }
 
     pub fn assume_file_name(&mut self, filename: String) {
         self.filename = Some(filename);
     }
 
-    pub fn save(&mut self) {
-        //write buffer to disk
+    /// write the buffer to disk
+    pub fn save(&mut self) -> Result<(), Error> {
         let Some(filename) = &self.filename else {
-            panic!("Trying to save without filename being set")
-        };
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(filename)
-            .expect("Error opening file");
-        let mut file = LineWriter::new(file);
-        for line in &self.text {
-            let text_line = line.to_string();
-            file.write_all(text_line.as_bytes())
-                .expect("Error on write");
-            file.write_all(b"\n").expect("Error entering new line");
-        }
+            return Err(Error::new(ErrorKind::InvalidInput, "no file name set"));
+        };
+        write_file(Path::new(filename), |writer| self.write_text(writer))?;
         self.is_saved = true;
+        Ok(())
     }
 
-    pub fn insert_tab(&mut self, pos: &Position, num_tabs: usize) {
-        if self.is_empty() {
-            let new_line = Line {
-                string: Vec::new(),
-                raw_string: String::new(),
-            };
-            self.text.push(new_line);
+    fn write_text(&self, writer: &mut BufWriter<File>) -> Result<(), Error> {
+        if let Some(bom) = self.format.bom() {
+            writer.write_all(bom.as_bytes())?;
         }
-
-        for _ in pos.width..pos.width.saturating_add(num_tabs.saturating_mul(4)) {
-            self.text
-                .get_mut(pos.height)
-                .expect("Out of bounds")
-                .string
-                .insert(
-                    pos.width,
-                    TextFragment::try_from(" ").expect("Error generating new fragment"),
-                );
+        let line_ending = self.format.line_ending.as_str();
+        for chunk in self.text.chunks() {
+            match self.format.line_ending {
+                LineEnding::Unix => writer.write_all(chunk.as_bytes())?,
+                LineEnding::Dos => writer.write_all(chunk.replace('\n', line_ending).as_bytes())?,
+            }
+        }
+        if self.format.final_newline && !self.is_empty() {
+            writer.write_all(line_ending.as_bytes())?;
         }
+        Ok(())
+    }
 
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .generate_raw_string();
+    pub fn insert_tab(&mut self, pos: &Position, num_tabs: usize) {
+        self.insert_text(pos, &" ".repeat(num_tabs.saturating_mul(4)));
     }
 
     pub fn update_line_insert(&mut self, pos: &mut Position, insert_char: char) {
-        //take current vec<TextFragment> at height
-        //insert new char
-        //generate a new vec<TextFragment from new string
-        //replace current with new vec
+        //insert the new char at the cursor
         //return the cursor position update
-        let new_fragment: TextFragment = TextFragment::try_from(insert_char.to_string().as_str())
-            .expect("Error getting new fragment");
-        let move_width = match new_fragment.render_width {
+        let grapheme = insert_char.to_string();
+        let move_width = match GraphemeWidth::from_str_width(&grapheme) {
             GraphemeWidth::Half => 1,
             GraphemeWidth::Full => 2,
         };
-        if self.is_empty() {
-            self.text.push(Line::from(insert_char.to_string().as_str()));
-        } else {
-            self.text
-                .get_mut(pos.height)
-                .expect("Error getting mut line")
-                .string
-                .insert(pos.width, new_fragment);
-        }
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .generate_raw_string();
-        self.is_saved = false;
+        self.insert_text(pos, &grapheme);
         pos.width = pos.width.saturating_add(move_width);
     }
 
     pub fn update_line_delete(&mut self, pos: &mut Position) {
         // pop out the char we want to removed
         // return the render_width of that char
+        if pos.width == 0 {
+            return;
+        }
         if self.is_tab(pos) {
-            for i in (pos.width.saturating_sub(4)..pos.width).rev() {
-                self.text
-                    .get_mut(pos.height)
-                    .expect("Out of bounds error")
-                    .string
-                    .remove(i);
-            }
+            let start = Position {
+                width: pos.width.saturating_sub(4),
+                ..*pos
+            };
+            self.remove_text(&start, pos);
             pos.left(4);
             return;
         }
-        let removed_char = self
-            .text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .string
-            .remove(pos.width.saturating_sub(1));
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .generate_raw_string();
-        self.is_saved = false;
-        let diff = match removed_char.render_width {
+        let start = Position {
+            width: pos.width.saturating_sub(1),
+            ..*pos
+        };
+        let removed_char = self.remove_text(&start, pos);
+        let diff = match GraphemeWidth::from_str_width(&removed_char) {
             GraphemeWidth::Half => 1,
             GraphemeWidth::Full => 2,
         };
@@ -307,28 +502,14 @@ impl Buffer {
         if pos.width < 4 {
             return false;
         }
-        let fragments_to_check = &self
-            .text
-            .get(pos.height)
-            .expect("Out of bounds")
-            .string
-            .get(pos.width.saturating_sub(4)..pos.width);
-        match fragments_to_check {
-            Some(frags) => {
-                for fragment in frags.iter().rev() {
-                    if fragment.grapheme != *" ".to_string() {
-                        return false;
-                    }
-                }
-            }
-            None => return false,
-        }
-
-        true
+        self.line(pos.height)
+            .grapheme_slice(pos.width.saturating_sub(4)..pos.width)
+            == "    "
     }
 
     pub fn num_tabs(&self, index: usize) -> usize {
-        let bytes = self.text[index].raw_string.as_bytes();
+        let line = self.line(index);
+        let bytes = line.raw_string.as_bytes();
         let len = bytes.len();
         let mut i = 5;
         while i < len && bytes[i] == 32 {
@@ -339,118 +520,568 @@ impl Buffer {
     }
 
     fn new_line(&mut self, line_index: usize) {
-        if self.is_empty() {
-            self.text.push(Line {
-                string: Vec::new(),
-                raw_string: String::new(),
-            });
-        }
-        self.text.insert(
-            line_index.saturating_add(1),
-            Line {
-                string: Vec::new(),
-                raw_string: String::new(),
-            },
-        );
-
+        let mut new_text = String::from("\n");
         if self.is_tab(&Position {
             height: line_index,
             width: 4,
             max_width: usize::default(),
         }) {
             let num_tabs = self.num_tabs(line_index);
-            self.insert_tab(
-                &Position {
+            new_text.push_str(&" ".repeat(num_tabs.saturating_mul(4)));
+        }
+        let end_of_line = Position {
+            height: line_index,
+            width: self.line(line_index).len(),
+            max_width: usize::default(),
+        };
+        self.insert_text(&end_of_line, &new_text);
+    }
+
+    pub fn split_line(&mut self, pos: &Position) {
+        self.insert_text(pos, "\n");
+    }
+
+    pub fn join_line(&mut self, line_index: usize) {
+        let prev_index = line_index.saturating_sub(1);
+        let end_of_prev = Position {
+            height: prev_index,
+            width: self.line(prev_index).len(),
+            max_width: usize::default(),
+        };
+        let start_of_current = Position {
+            height: line_index,
+            width: 0,
+            max_width: usize::default(),
+        };
+        self.remove_text(&end_of_prev, &start_of_current);
+    }
+
+    /// J, join the line below onto a line, the white space leading the lower line becomes a space
+    /// no space goes after white space, before a ), or around an empty line
+    /// returns the column where the lines meet, None on the last line
+    pub fn join_lines(&mut self, line_index: usize) -> Option<usize> {
+        let below = line_index.saturating_add(1);
+        if below >= self.len() {
+            return None;
+        }
+        let upper = self.line(line_index).raw_string;
+        let lower = self.line(below).raw_string;
+        let end_of_line = Position {
+            height: line_index,
+            width: self.line(line_index).len(),
+            max_width: usize::default(),
+        };
+        // the white space leading the lower line goes, then the lines are joined as they are
+        let start_below = Position {
+            height: below,
+            width: 0,
+            max_width: usize::default(),
+        };
+        let text_below = Position {
+            width: self.first_non_blank(below),
+            ..start_below
+        };
+        self.remove_text(&start_below, &text_below);
+        self.join_line(below);
+        let rest = lower.trim_start();
+        if !upper.is_empty()
+            && !upper.ends_with([' ', '\t'])
+            && !rest.is_empty()
+            && !rest.starts_with(')')
+        {
+            self.insert_text(&end_of_line, " ");
+        }
+        Some(end_of_line.width)
+    }
+
+    /// swap the text from start up to, but not including, end for new text
+    /// returns the position directly after the new text
+    pub fn replace_text(&mut self, start: &Position, end: &Position, text: &str) -> Position {
+        self.remove_text(start, end);
+        self.insert_text(start, text)
+    }
+
+    pub fn delete_segment(&mut self, left_pos: &Position, right_pos: &mut Position) {
+        // delete everything from left up to and including right
+        let end = Position {
+            width: right_pos.width.saturating_add(1),
+            ..*right_pos
+        };
+        self.remove_text(left_pos, &end);
+        right_pos.set_position(*left_pos);
+    }
+
+    pub fn pop_line(&mut self, line_index: usize) {
+        let Some(line) = self.get_line(line_index) else {
+            return;
+        };
+        let (start, end) = if line_index < self.len().saturating_sub(1) {
+            // take the line along with its new line char
+            (
+                Position {
+                    height: line_index,
+                    width: 0,
+                    max_width: usize::default(),
+                },
+                Position {
                     height: line_index.saturating_add(1),
                     width: 0,
                     max_width: usize::default(),
                 },
-                num_tabs,
-            );
-        }
+            )
+        } else {
+            // last line, take the new line char from the line above
+            // when this is the only line, this clears the buffer
+            let prev_index = line_index.saturating_sub(1);
+            (
+                Position {
+                    height: prev_index,
+                    width: if line_index == 0 {
+                        0
+                    } else {
+                        self.line(prev_index).len()
+                    },
+                    max_width: usize::default(),
+                },
+                Position {
+                    height: line_index,
+                    width: line.len(),
+                    max_width: usize::default(),
+                },
+            )
+        };
+        self.remove_text(&start, &end);
+    }
 
-        self.is_saved = false;
+    /// the text from start up to, but not including, end
+    pub fn get_text(&self, start: &Position, end: &Position) -> String {
+        let start = self.char_index(start);
+        let end = self.char_index(end);
+        if start >= end {
+            return String::new();
+        }
+        String::from(self.text.slice(start..end))
     }
 
-    pub fn split_line(&mut self, pos: &Position) {
-        let new_line = self
-            .text
-            .get(pos.height)
-            .expect("Out of bounds error")
-            .string
-            .get(pos.width..)
-            .expect("Out of bounds error");
-
-        self.text.insert(
-            pos.height.saturating_add(1),
-            Line {
-                string: new_line.to_vec(),
-                raw_string: String::new(),
+    /// remove the lines first through last along with their new line chars
+    /// returns the removed lines without a trailing new line
+    pub fn remove_lines(&mut self, first: usize, last: usize) -> String {
+        let last = std::cmp::min(last, self.len().saturating_sub(1));
+        if first > last {
+            return String::new();
+        }
+        let lines = self.get_text(
+            &Position {
+                height: first,
+                width: 0,
+                max_width: usize::default(),
+            },
+            &Position {
+                height: last,
+                width: self.line(last).len(),
+                max_width: usize::default(),
             },
         );
+        for _ in first..=last {
+            self.pop_line(first);
+        }
+        lines
+    }
 
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .string
-            .truncate(pos.width);
+    /// find the bracket that closes, or going backward opens, the nesting level at a position
+    /// the grapheme at the position itself is not counted
+    pub fn find_unmatched(
+        &self,
+        from: &Position,
+        open: &str,
+        close: &str,
+        forward: bool,
+    ) -> Option<Position> {
+        let (target, nested) = if forward {
+            (close, open)
+        } else {
+            (open, close)
+        };
+        let last = self.len().saturating_sub(1);
+        let mut depth: usize = 0;
+        let mut height = from.height;
+        loop {
+            let line = self.get_line(height)?;
+            let graphemes: Vec<&str> = line.raw_string.graphemes(true).collect();
+            let mut widths: Vec<usize> = (0..graphemes.len())
+                .filter(|width| {
+                    height != from.height
+                        || (forward && *width > from.width)
+                        || (!forward && *width < from.width)
+                })
+                .collect();
+            if !forward {
+                widths.reverse();
+            }
+            for width in widths {
+                if graphemes[width] == nested {
+                    depth = depth.saturating_add(1);
+                } else if graphemes[width] == target {
+                    if depth == 0 {
+                        return Some(Position {
+                            height,
+                            width,
+                            max_width: width,
+                        });
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+            }
+            if forward {
+                if height >= last {
+                    return None;
+                }
+                height = height.saturating_add(1);
+            } else {
+                if height == 0 {
+                    return None;
+                }
+                height = height.saturating_sub(1);
+            }
+        }
+    }
 
-        self.text
-            .get_mut(pos.height.saturating_add(1))
-            .expect("Out of bounds error")
-            .generate_raw_string();
+    /// the bracket that pairs with the one at a position, None when it is not on a bracket
+    pub fn matching_bracket(&self, pos: &Position) -> Option<Position> {
+        let line = self.get_line(pos.height)?;
+        let under = line.grapheme_slice(pos.width..pos.width.saturating_add(1));
+        BRACKETS.iter().find_map(|(open, close)| {
+            if under == *open {
+                self.find_unmatched(pos, open, close, true)
+            } else if under == *close {
+                self.find_unmatched(pos, open, close, false)
+            } else {
+                None
+            }
+        })
+    }
 
-        self.is_saved = false;
+    /// where % goes, the match of the first bracket at or after the position on its line
+    pub fn bracket_jump(&self, pos: &Position) -> Option<Position> {
+        let line = self.get_line(pos.height)?;
+        let width = (pos.width..line.len()).find(|width| {
+            let grapheme = line.grapheme_slice(*width..width.saturating_add(1));
+            BRACKETS
+                .iter()
+                .any(|(open, close)| grapheme == *open || grapheme == *close)
+        })?;
+        self.matching_bracket(&Position {
+            height: pos.height,
+            width,
+            max_width: width,
+        })
     }
 
-    pub fn join_line(&mut self, line_index: usize) {
-        let mut current_line = self
-            .text
-            .get(line_index)
-            .expect("Out of bounds error")
-            .string
-            .clone();
+    /// insert whole lines so the first of them becomes line `line_index`
+    pub fn insert_lines(&mut self, line_index: usize, text: &str) {
+        if self.is_empty() {
+            self.insert_text(&Position::default(), text);
+        } else if line_index < self.len() {
+            let start_of_line = Position {
+                height: line_index,
+                width: 0,
+                max_width: usize::default(),
+            };
+            self.insert_text(&start_of_line, &format!("{text}\n"));
+        } else {
+            let last = self.len().saturating_sub(1);
+            let end_of_last = Position {
+                height: last,
+                width: self.line(last).len(),
+                max_width: usize::default(),
+            };
+            self.insert_text(&end_of_last, &format!("\n{text}"));
+        }
+    }
 
-        self.text.remove(line_index);
+    /// width of the leading whitespace of a line
+    pub fn first_non_blank(&self, line_index: usize) -> usize {
+        let line = self.line(line_index);
+        line.raw_string
+            .len()
+            .saturating_sub(line.raw_string.trim_start_matches([' ', '\t']).len())
+    }
 
-        self.text
-            .get_mut(line_index.saturating_sub(1))
-            .expect("Out of bounds error")
-            .string
-            .append(&mut current_line);
+    /// the columns left up to, but not including, right on each of the lines first through last
+    /// rows are seperated by new line chars
+    pub fn get_block(&self, first: usize, last: usize, left: usize, right: usize) -> String {
+        (first..=std::cmp::min(last, self.len().saturating_sub(1)))
+            .map(|line| self.line(line).grapheme_slice(left..right).to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 
-        self.is_saved = false;
+    /// remove the columns left up to, but not including, right on each of the lines first through last
+    /// returns the removed rows seperated by new line chars
+    pub fn remove_block(&mut self, first: usize, last: usize, left: usize, right: usize) -> String {
+        let removed = self.get_block(first, last, left, right);
+        for line in first..=std::cmp::min(last, self.len().saturating_sub(1)) {
+            let start = Position {
+                height: line,
+                width: left,
+                max_width: usize::default(),
+            };
+            let end = Position {
+                height: line,
+                width: right,
+                max_width: usize::default(),
+            };
+            self.remove_text(&start, &end);
+        }
+        removed
+    }
+
+    /// insert each row at the same column on the lines from `line_index` down
+    /// with `pad` short lines are filled out with spaces and lines are added past the end,
+    /// otherwise lines that do not reach the column are skipped
+    pub fn insert_block(&mut self, line_index: usize, column: usize, rows: &[&str], pad: bool) {
+        for (i, row) in rows.iter().enumerate() {
+            let height = line_index.saturating_add(i);
+            if height >= self.len() {
+                if !pad {
+                    return;
+                }
+                self.insert_lines(height, "");
+            }
+            let line_len = self.line(height).len();
+            if line_len < column {
+                if !pad {
+                    continue;
+                }
+                let end_of_line = Position {
+                    height,
+                    width: line_len,
+                    max_width: usize::default(),
+                };
+                self.insert_text(&end_of_line, &" ".repeat(column.saturating_sub(line_len)));
+            }
+            let at = Position {
+                height,
+                width: column,
+                max_width: usize::default(),
+            };
+            self.insert_text(&at, row);
+        }
+    }
 
+    /// the same text at one column on `rows` lines from `line_index` down
+    /// returns false without changing anything when the text has a line break
+    pub fn insert_block_rows(
+        &mut self,
+        text: &str,
+        line_index: usize,
+        column: usize,
+        rows: usize,
+        pad: bool,
+    ) -> bool {
+        if text.contains('\n') {
+            return false;
+        }
+        if !text.is_empty() {
+            self.insert_block(line_index, column, &vec![text; rows], pad);
+        }
+        true
+    }
+
+    /// indent, or with `right` false outdent, the lines first through last by one tab width
+    pub fn shift_lines(&mut self, first: usize, last: usize, right: bool) {
+        for line in first..=std::cmp::min(last, self.len().saturating_sub(1)) {
+            let start = Position {
+                height: line,
+                width: 0,
+                max_width: usize::default(),
+            };
+            if right {
+                // blank lines are left alone
+                if !self.is_blank_line(line) {
+                    self.insert_tab(&start, 1);
+                }
+            } else {
+                let line_text = self.line(line).raw_string;
+                let width = if line_text.starts_with('\t') {
+                    1
+                } else {
+                    std::cmp::min(self.first_non_blank(line), 4)
+                };
+                let end = Position { width, ..start };
+                self.remove_text(&start, &end);
+            }
+        }
+    }
+
+    /// insert text that may span multiple lines at a position
+    /// returns the position directly after the inserted text
+    pub fn insert_text(&mut self, at: &Position, text: &str) -> Position {
+        let at = if self.is_empty() {
+            Position::default()
+        } else {
+            self.clamp(at)
+        };
+        let end = self.apply_insert(&at, text);
+        if !text.is_empty() {
+            self.history.record(
+                Change::Insert {
+                    at,
+                    text: text.to_string(),
+                },
+                at,
+            );
+            self.is_saved = false;
+        }
+        end
+    }
+
+    /// remove the text from start up to, but not including, end
+    /// returns the removed text, lines are seperated by new line chars
+    pub fn remove_text(&mut self, start: &Position, end: &Position) -> String {
+        let start = self.clamp(start);
+        let removed = self.apply_delete(&start, end);
+        if !removed.is_empty() {
+            self.history.record(
+                Change::Delete {
+                    at: start,
+                    text: removed.clone(),
+                },
+                start,
+            );
+            self.is_saved = false;
+        }
+        removed
+    }
+
+    /// clamp a position to a valid place in the buffer
+    fn clamp(&self, pos: &Position) -> Position {
+        let height = std::cmp::min(pos.height, self.text.len_lines().saturating_sub(1));
+        let width = std::cmp::min(pos.width, self.line(height).len());
+        Position {
+            height,
+            width,
+            max_width: width,
+        }
+    }
+
+    /// the rope char index of a position
+    fn char_index(&self, pos: &Position) -> usize {
+        let pos = self.clamp(pos);
+        let line = self.line(pos.height);
+        let line_chars = line.raw_string[..line.byte_index(pos.width)]
+            .chars()
+            .count();
         self.text
-            .get_mut(line_index.saturating_sub(1))
-            .expect("Out of bounds error")
-            .generate_raw_string();
+            .line_to_char(pos.height)
+            .saturating_add(line_chars)
     }
 
-    pub fn delete_segment(&mut self, left_pos: &Position, right_pos: &mut Position) {
-        //delete from right to left
-        right_pos.width = right_pos.width.saturating_add(1);
-        while right_pos.width > left_pos.width {
-            self.update_line_delete(right_pos);
+    fn apply_insert(&mut self, at: &Position, text: &str) -> Position {
+        self.syntax.get_mut().invalidate(at.height);
+        while self.text.len_lines() <= at.height {
+            self.text.insert_char(self.text.len_chars(), '\n');
         }
+        let at = self.clamp(at);
+        let index = self.char_index(&at);
+        self.text.insert(index, text);
+        Self::end_of_insert(&at, text)
     }
 
-    pub fn pop_line(&mut self, line_index: usize) {
-        self.text.remove(line_index);
+    fn apply_delete(&mut self, start: &Position, end: &Position) -> String {
+        self.syntax.get_mut().invalidate(start.height);
+        let start = self.char_index(start);
+        let end = self.char_index(end);
+        if start >= end {
+            return String::new();
+        }
+        let removed = String::from(self.text.slice(start..end));
+        self.text.remove(start..end);
+        removed
+    }
+
+    /// the position directly after a block of text inserted at a position
+    fn end_of_insert(at: &Position, text: &str) -> Position {
+        let line_count = text.split('\n').count().saturating_sub(1);
+        let last_segment = text.rsplit('\n').next().unwrap_or_default();
+        let segment_len = last_segment.graphemes(true).count();
+        if line_count == 0 {
+            Position {
+                height: at.height,
+                width: at.width.saturating_add(segment_len),
+                max_width: usize::default(),
+            }
+        } else {
+            Position {
+                height: at.height.saturating_add(line_count),
+                width: segment_len,
+                max_width: usize::default(),
+            }
+        }
+    }
+
+    /// revert the most recent group of changes
+    /// returns false when there is nothing to undo
+    pub fn undo(&mut self, pos: &mut Position) -> bool {
+        self.history.seal(*pos);
+        let Some(group) = self.history.pop_undo() else {
+            return false;
+        };
+        for change in group.changes.iter().rev() {
+            match change {
+                Change::Insert { at, text } => {
+                    let end = Self::end_of_insert(at, text);
+                    self.apply_delete(at, &end);
+                }
+                Change::Delete { at, text } => {
+                    self.apply_insert(at, text);
+                }
+            }
+        }
+        pos.set_position(group.cursor_before);
+        pos.max_width = pos.width;
+        self.history.push_redo(group);
+        self.is_saved = false;
+        true
+    }
+
+    /// reapply the most recently undone group of changes
+    /// returns false when there is nothing to redo
+    pub fn redo(&mut self, pos: &mut Position) -> bool {
+        self.history.seal(*pos);
+        let Some(group) = self.history.pop_redo() else {
+            return false;
+        };
+        for change in &group.changes {
+            match change {
+                Change::Insert { at, text } => {
+                    self.apply_insert(at, text);
+                }
+                Change::Delete { at, text } => {
+                    let end = Self::end_of_insert(at, text);
+                    self.apply_delete(at, &end);
+                }
+            }
+        }
+        pos.set_position(group.cursor_after);
+        pos.max_width = pos.width;
+        self.history.push_undo(group);
+        self.is_saved = false;
+        true
     }
 
     pub fn begining_of_current_word(&self, pos: &mut Position) {
         if self.is_empty() {
             return;
         }
-        if let Some(new) = self.text[pos.height].begining_of_current_word(pos.width) {
+        if let Some(new) = self.line(pos.height).begining_of_current_word(pos.width) {
             pos.width = new;
             return;
         }
 
         while pos.height >= 1 {
             pos.height = pos.height.saturating_sub(1);
-            if let Some(new) = self.text[pos.height].begining_of_current_word_spillover() {
+            if let Some(new) = self.line(pos.height).begining_of_current_word_spillover() {
                 pos.width = new;
                 return;
             }
@@ -464,7 +1095,7 @@ impl Buffer {
         if self.is_empty() {
             return;
         }
-        if let Some(new) = self.text[pos.height].begining_of_next_word(pos.width) {
+        if let Some(new) = self.line(pos.height).begining_of_next_word(pos.width) {
             pos.width = new;
             return;
         }
@@ -472,34 +1103,38 @@ impl Buffer {
         let max = self.len().saturating_sub(1);
         while pos.height < max {
             pos.height = pos.height.saturating_add(1);
-            if let Some(new) = self.text[pos.height].begining_of_next_word_spillover() {
+            if let Some(new) = self.line(pos.height).begining_of_next_word_spillover() {
                 pos.width = new;
                 return;
             }
         }
         // if we are here we are at the end
-        pos.width = self.text.last().unwrap().grapheme_len();
+        pos.width = self.line(self.len().saturating_sub(1)).grapheme_len();
     }
 
     pub fn get_segment(&self, start: &Position, end: &Position) -> String {
         let mut copy_string = String::new();
         if start.height == end.height {
-            let line_len = self.text[start.height].raw_string.len().saturating_sub(1);
-            let line_string = &self.text[start.height].raw_string;
-            let slice: String = if end.width == line_len {
-                line_string[start.width..].to_owned()
+            let line = self.line(start.height);
+            let line_len = line.len().saturating_sub(1);
+            let slice = if end.width == line_len {
+                line.grapheme_slice(start.width..line.len())
             } else {
-                line_string[start.width..end.width].to_owned()
+                line.grapheme_slice(start.width..end.width)
             };
-            copy_string.push_str(&slice);
+            copy_string.push_str(slice);
         } else {
-            copy_string.push_str(&self.text[start.height].raw_string[start.width..]);
+            let first = self.line(start.height);
+            copy_string.push_str(first.grapheme_slice(start.width..first.len()));
             copy_string.push('\n');
             for h in start.height.saturating_add(1)..end.height {
-                copy_string.push_str(&self.text[h].raw_string);
+                copy_string.push_str(&self.line(h).raw_string);
                 copy_string.push('\n');
             }
-            copy_string.push_str(&self.text[end.height].raw_string[..=end.width]);
+            copy_string.push_str(
+                self.line(end.height)
+                    .grapheme_slice(0..end.width.saturating_add(1)),
+            );
         }
         copy_string
     }
@@ -508,7 +1143,7 @@ impl Buffer {
         if self.is_empty() {
             return;
         }
-        if let Some(new) = self.text[pos.height].end_of_current_word(pos.width) {
+        if let Some(new) = self.line(pos.height).end_of_current_word(pos.width) {
             pos.width = new;
             return;
         }
@@ -516,14 +1151,70 @@ impl Buffer {
         let max_height = self.len().saturating_sub(1);
         while pos.height < max_height {
             pos.height = pos.height.saturating_add(1);
-            if let Some(thing) = self.text[pos.height].end_of_current_word_spillover() {
+            if let Some(thing) = self.line(pos.height).end_of_current_word_spillover() {
                 pos.width = thing;
                 return;
             }
         }
 
-        pos.width = self.text.last().unwrap().grapheme_len().saturating_sub(1);
+        pos.width = self
+            .line(self.len().saturating_sub(1))
+            .grapheme_len()
+            .saturating_sub(1);
+    }
+}
+
+/// write a file through a temp file next to it which is synced and renamed into place
+/// so a failed or interrupted write never leaves a truncated file behind
+pub fn write_file<F>(path: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+{
+    // write through symlinks rather than replacing them
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let permissions = fs::metadata(&target).ok().map(|meta| meta.permissions());
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let Some(name) = target.file_name() else {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid file name"));
+    };
+    let temp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let written =
+        write_temp(&temp_path, permissions, write).and_then(|()| fs::rename(&temp_path, &target));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    // persist the rename itself, best effort as not every platform allows this
+    if let Ok(dir) = File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn write_temp<F>(path: &Path, permissions: Option<Permissions>, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+{
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
     }
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(IntoInnerError::into_error)?;
+    file.sync_all()
 }
 
 #[cfg(test)]
@@ -532,13 +1223,16 @@ mod tests {
 
     #[test]
     fn end_of_current_word() {
-        let line1 = Line::from("I have a bunch of text");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "I have a bunch of text";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
 
         let mut pos = Position {
@@ -561,13 +1255,16 @@ mod tests {
 
     #[test]
     fn end_of_current_word_spillover() {
-        let line1 = Line::from("I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "I have a bunch of text ";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
 
         let mut pos = Position {
@@ -590,13 +1287,16 @@ mod tests {
 
     #[test]
     fn end_of_current_word_end() {
-        let line1 = Line::from("I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "I have a bunch of text ";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
 
         let mut pos = Position {
@@ -620,13 +1320,16 @@ mod tests {
 
     #[test]
     fn begining_of_current_word() {
-        let line1 = Line::from("I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "I have a bunch of text ";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
 
         let mut pos = Position {
@@ -650,13 +1353,16 @@ mod tests {
 
     #[test]
     fn begining_of_current_word_origin() {
-        let line1 = Line::from("  I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "  I have a bunch of text ";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
 
         let mut pos = Position {
@@ -680,14 +1386,445 @@ mod tests {
 
     #[test]
     fn num_tabs() {
-        let line1 = Line::from("              I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
+        let line1 = "              I have a bunch of text ";
+        let line2 = "This is a bunch more text";
+        let lines = [line1, line2];
         let buff = Buffer {
-            text: lines,
+            text: Rope::from_str(&lines.join("\n")),
             filename: None,
             is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
         };
         assert_eq!(buff.num_tabs(0), 3);
     }
+
+    #[test]
+    fn undo_redo_insert_and_split() {
+        let mut buff = Buffer {
+            text: Rope::from_str("hello world"),
+            filename: None,
+            is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        let mut pos = Position {
+            height: 0,
+            width: 5,
+            max_width: usize::default(),
+        };
+        buff.update_line_insert(&mut pos, '!');
+        buff.history.seal(pos);
+        buff.split_line(&pos);
+        buff.history.seal(pos);
+        assert_eq!(buff.len(), 2);
+        assert_eq!(buff.line(0).raw_string, "hello!");
+        assert_eq!(buff.line(1).raw_string, " world");
+
+        assert!(buff.undo(&mut pos));
+        assert_eq!(buff.len(), 1);
+        assert_eq!(buff.line(0).raw_string, "hello! world");
+        assert!(buff.undo(&mut pos));
+        assert_eq!(buff.line(0).raw_string, "hello world");
+        assert_eq!(pos.width, 5);
+        assert!(!buff.undo(&mut pos));
+
+        assert!(buff.redo(&mut pos));
+        assert!(buff.redo(&mut pos));
+        assert_eq!(buff.line(0).raw_string, "hello!");
+        assert_eq!(buff.line(1).raw_string, " world");
+        assert!(!buff.redo(&mut pos));
+    }
+
+    #[test]
+    fn undo_join_and_pop_line() {
+        let mut buff = Buffer {
+            text: Rope::from_str("one\ntwo\nthree"),
+            filename: None,
+            is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        let mut pos = Position::default();
+        buff.join_line(1);
+        buff.pop_line(1);
+        buff.history.seal(pos);
+        assert_eq!(buff.len(), 1);
+        assert_eq!(buff.line(0).raw_string, "onetwo");
+
+        assert!(buff.undo(&mut pos));
+        assert_eq!(buff.len(), 3);
+        assert_eq!(buff.line(0).raw_string, "one");
+        assert_eq!(buff.line(1).raw_string, "two");
+        assert_eq!(buff.line(2).raw_string, "three");
+    }
+
+    #[test]
+    fn undo_multi_line_delete() {
+        let mut buff = Buffer {
+            text: Rope::from_str("abc\ndef\nghi"),
+            filename: None,
+            is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        let start = Position {
+            height: 0,
+            width: 1,
+            max_width: usize::default(),
+        };
+        let end = Position {
+            height: 2,
+            width: 2,
+            max_width: usize::default(),
+        };
+        assert_eq!(buff.remove_text(&start, &end), "bc\ndef\ngh");
+        assert_eq!(buff.line(0).raw_string, "ai");
+
+        let mut pos = start;
+        assert!(buff.undo(&mut pos));
+        assert_eq!(buff.len(), 3);
+        assert_eq!(buff.line(2).raw_string, "ghi");
+    }
+
+    #[test]
+    fn insert_after_multibyte_graphemes() {
+        let mut buff = Buffer {
+            text: Rope::from_str("héllo wörld\nsecond"),
+            filename: None,
+            is_saved: true,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        let mut pos = Position {
+            height: 0,
+            width: 8,
+            max_width: usize::default(),
+        };
+        buff.update_line_insert(&mut pos, 'X');
+        assert_eq!(buff.line(0).raw_string, "héllo wöXrld");
+        assert_eq!(buff.line(1).raw_string, "second");
+        assert_eq!(buff.len(), 2);
+        assert!(buff.get_line(2).is_none());
+    }
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mini-vim-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn save_replaces_file_contents() {
+        let path = scratch_path("save_replaces");
+        fs::write(
+            &path,
+            "a much longer line that should not survive\nsecond\nthird\n",
+        )
+        .unwrap();
+        let mut buff = Buffer {
+            text: Rope::from_str("short"),
+            filename: Some(path.to_string_lossy().into_owned()),
+            is_saved: false,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        buff.save().unwrap();
+        assert!(buff.is_saved);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "short\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = scratch_path("save_permissions");
+        fs::write(&path, "old\n").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        let mut buff = Buffer {
+            text: Rope::from_str("new"),
+            filename: Some(path.to_string_lossy().into_owned()),
+            is_saved: false,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        buff.save().unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_save_reports_error() {
+        let path = scratch_path("missing_dir").join("file.txt");
+        let mut buff = Buffer {
+            text: Rope::from_str("text"),
+            filename: Some(path.to_string_lossy().into_owned()),
+            is_saved: false,
+            history: EditHistory::default(),
+            format: FileFormat::default(),
+            syntax: RefCell::default(),
+        };
+        assert!(buff.save().is_err());
+        assert!(!buff.is_saved);
+
+        buff.filename = None;
+        assert!(buff.save().is_err());
+    }
+
+    #[test]
+    fn load_and_save_round_trip_format() {
+        let path = scratch_path("round_trip");
+        let contents = "\u{feff}[section]\r\nkey = 1\r\nother = 2";
+        fs::write(&path, contents).unwrap();
+        let mut buff = Buffer::load(&path.to_string_lossy()).unwrap();
+        assert_eq!(buff.len(), 3);
+        assert_eq!(buff.line(1).raw_string, "key = 1");
+        buff.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+
+        buff.set_line_ending(LineEnding::Unix);
+        assert!(!buff.is_saved);
+        buff.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{feff}[section]\nkey = 1\nother = 2"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mixed_endings_save_with_one_ending() {
+        let path = scratch_path("mixed_endings");
+        fs::write(&path, "one\r\ntwo\nthree\r\n").unwrap();
+        let mut buff = Buffer::load(&path.to_string_lossy()).unwrap();
+        buff.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "one\r\ntwo\r\nthree\r\n"
+        );
+
+        fs::write(&path, "one\ntwo\r\nthree\n").unwrap();
+        let mut buff = Buffer::load(&path.to_string_lossy()).unwrap();
+        buff.set_line_ending(LineEnding::Dos);
+        buff.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "one\r\ntwo\r\nthree\r\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn block_edits() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "abcd\nef\nghij");
+        assert_eq!(buff.get_block(0, 2, 1, 3), "bc\nf\nhi");
+        assert_eq!(buff.remove_block(0, 2, 1, 3), "bc\nf\nhi");
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "ad\ne\ngj"
+        );
+
+        buff.insert_block(0, 1, &["X", "Y", "Z"], false);
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "aXd\neY\ngZj"
+        );
+        buff.insert_block(1, 4, &["1", "2", "3"], true);
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "aXd\neY  1\ngZj 2\n    3"
+        );
+    }
+
+    #[test]
+    fn end_state_follows_an_opened_comment() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "let a = 1;\nlet b = 2;");
+        assert_eq!(buff.end_state(0), LineState::Normal);
+        buff.filename = Some("main.rs".to_string());
+        assert_eq!(buff.end_state(0), LineState::Normal);
+        buff.insert_text(&Position::default(), "/*");
+        assert_ne!(buff.end_state(0), LineState::Normal);
+        assert_eq!(buff.end_state(1), buff.end_state(0));
+    }
+
+    #[test]
+    fn block_rows_refuse_a_line_break() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "ab\ncd\nef");
+        assert!(buff.insert_block_rows("-", 1, 1, 2, false));
+        assert!(!buff.insert_block_rows("x\ny", 0, 0, 3, false));
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "ab\nc-d\ne-f"
+        );
+    }
+
+    #[test]
+    fn shift_lines_by_a_tab() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "a\n\n  b");
+        buff.shift_lines(0, 2, true);
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "    a\n\n      b"
+        );
+        buff.shift_lines(0, 2, false);
+        buff.shift_lines(2, 2, false);
+        assert_eq!(
+            buff.get_text(&Position::default(), &buff_end(&buff)),
+            "a\n\nb"
+        );
+    }
+
+    fn buff_end(buff: &Buffer) -> Position {
+        let height = buff.len().saturating_sub(1);
+        Position {
+            height,
+            width: buff.line(height).len(),
+            max_width: usize::default(),
+        }
+    }
+
+    #[test]
+    fn remove_lines_at_the_end() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "a\nb\nc\nd");
+        assert_eq!(buff.remove_lines(1, 1), "b");
+        assert_eq!(buff.len(), 3);
+        assert_eq!(buff.remove_lines(1, 5), "c\nd");
+        assert_eq!(buff.len(), 1);
+        assert_eq!(buff.line(0).raw_string, "a");
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let mut buff = Buffer::default();
+        buff.insert_text(
+            &Position::default(),
+            "fn a(b: [u8]) {
+    (c)
+}",
+        );
+        let at = |height, width| Position {
+            height,
+            width,
+            max_width: width,
+        };
+        assert_eq!(buff.matching_bracket(&at(0, 4)), Some(at(0, 12)));
+        assert_eq!(buff.matching_bracket(&at(0, 12)), Some(at(0, 4)));
+        assert_eq!(buff.matching_bracket(&at(0, 14)), Some(at(2, 0)));
+        assert_eq!(buff.matching_bracket(&at(2, 0)), Some(at(0, 14)));
+        assert_eq!(buff.matching_bracket(&at(0, 0)), None);
+        // off a bracket % looks ahead on the line
+        assert_eq!(buff.bracket_jump(&at(0, 0)), Some(at(0, 12)));
+        assert_eq!(buff.bracket_jump(&at(0, 6)), Some(at(0, 11)));
+        assert_eq!(buff.bracket_jump(&at(1, 0)), Some(at(1, 6)));
+        assert_eq!(buff.bracket_jump(&at(1, 7)), None);
+    }
+
+    #[test]
+    fn paragraph_and_sentence_motions() {
+        let mut buff = Buffer::default();
+        buff.insert_text(
+            &Position::default(),
+            "One. Two (three.) Four\nfive?\n\n  Six! e.g.x\n",
+        );
+        let at = |height, width| Position {
+            height,
+            width,
+            max_width: width,
+        };
+        let mut pos = at(0, 1);
+        buff.find_next_paragraph(&mut pos);
+        assert_eq!(pos, at(2, 0));
+        buff.find_next_paragraph(&mut pos);
+        assert_eq!(pos, at(4, 0));
+        buff.find_prev_paragraph(&mut pos);
+        assert_eq!(pos, at(2, 0));
+        buff.find_prev_paragraph(&mut pos);
+        assert_eq!(pos, at(0, 0));
+
+        let mut pos = at(0, 0);
+        let mut stops = Vec::new();
+        for _ in 0..5 {
+            buff.find_next_sentence(&mut pos);
+            stops.push(pos);
+        }
+        assert_eq!(
+            stops,
+            vec![at(0, 5), at(0, 18), at(2, 0), at(3, 2), at(3, 7)]
+        );
+        buff.find_prev_sentence(&mut pos);
+        assert_eq!(pos, at(3, 2));
+        let mut pos = at(0, 20);
+        buff.find_prev_sentence(&mut pos);
+        assert_eq!(pos, at(0, 18));
+        buff.find_prev_sentence(&mut pos);
+        assert_eq!(pos, at(0, 5));
+    }
+
+    #[test]
+    fn join_lines_collapses_white_space() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "a\n    b\n)\n\nc  \nd");
+        assert_eq!(buff.join_lines(0), Some(1));
+        assert_eq!(buff.join_lines(0), Some(3));
+        assert_eq!(buff.join_lines(0), Some(4));
+        assert_eq!(buff.line(0).raw_string, "a b)");
+        assert_eq!(buff.join_lines(1), Some(3));
+        assert_eq!(buff.line(1).raw_string, "c  d");
+        assert_eq!(buff.join_lines(1), None);
+    }
+
+    #[test]
+    fn search_columns_after_multibyte_graphemes() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "é fn(a)\n😀😀 fn(bc) fn()");
+        let matches = buff.search(&Regex::new(r"fn\((\w*)\)").unwrap());
+        let found: Vec<_> = matches
+            .into_iter()
+            .map(|found| {
+                let groups = found
+                    .groups
+                    .iter()
+                    .map(|group| (group.start, group.end))
+                    .collect::<Vec<_>>();
+                (found.start.height, found.start.width, found.end, groups)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, 2, 7, vec![(5, 6)]),
+                (1, 3, 9, vec![(6, 8)]),
+                (1, 10, 14, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keyword_under_or_after_cursor() {
+        let mut buff = Buffer::default();
+        buff.insert_text(&Position::default(), "let café_1 = x.len();");
+        let at = |width| Position {
+            height: 0,
+            width,
+            max_width: usize::default(),
+        };
+        assert_eq!(buff.keyword_at(&at(6)).as_deref(), Some("café_1"));
+        assert_eq!(buff.keyword_at(&at(3)).as_deref(), Some("café_1"));
+        assert_eq!(buff.keyword_at(&at(14)).as_deref(), Some("len"));
+        assert!(buff.keyword_at(&at(18)).is_none());
+        let below = Position { height: 1, ..at(0) };
+        assert!(buff.keyword_at(&below).is_none());
+    }
 }
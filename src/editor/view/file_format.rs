@@ -0,0 +1,149 @@
+use std::fmt;
+
+const BOM: char = '\u{feff}';
+
+/// the line terminator the file is written with
+/// the buffer itself only ever holds `\n`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unix => "\n",
+            Self::Dos => "\r\n",
+        }
+    }
+}
+
+impl TryFrom<&str> for LineEnding {
+    type Error = String;
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "unix" => Ok(Self::Unix),
+            "dos" => Ok(Self::Dos),
+            _ => Err(format!("unknown fileformat {val}")),
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unix => write!(f, "unix"),
+            Self::Dos => write!(f, "dos"),
+        }
+    }
+}
+
+/// how the file looked on disk
+/// remembered so a save writes it back the same way
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FileFormat {
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+    pub bom: bool,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::Unix,
+            final_newline: true,
+            bom: false,
+        }
+    }
+}
+
+impl FileFormat {
+    /// detect the format of the file contents and normalize them for the buffer
+    /// returns the text with the BOM, CRLF endings and final new line removed
+    pub fn detect(contents: &str) -> (Self, String) {
+        let (bom, contents) = match contents.strip_prefix(BOM) {
+            Some(rest) => (true, rest),
+            None => (false, contents),
+        };
+        let crlf_count = contents.matches("\r\n").count();
+        let lf_count = contents.matches('\n').count();
+        // the majority wins on mixed files, every line is saved with it
+        let line_ending = if crlf_count > 0 && crlf_count >= lf_count.saturating_sub(crlf_count) {
+            LineEnding::Dos
+        } else {
+            LineEnding::Unix
+        };
+
+        let mut text = contents.replace("\r\n", "\n");
+        let final_newline = text.ends_with('\n');
+        if final_newline {
+            text.pop();
+        }
+        let format = Self {
+            line_ending,
+            // an empty file has nothing to terminate, keep the default for new text
+            final_newline: final_newline || text.is_empty(),
+            bom,
+        };
+        (format, text)
+    }
+
+    pub fn bom(self) -> Option<&'static str> {
+        if self.bom {
+            Some("\u{feff}")
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.line_ending)?;
+        if self.bom {
+            write!(f, ",bom")?;
+        }
+        if !self.final_newline {
+            write!(f, ",noeol")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_dos_with_bom() {
+        let (format, text) = FileFormat::detect("\u{feff}a = 1\r\nb = 2\r\n");
+        assert_eq!(format.line_ending, LineEnding::Dos);
+        assert!(format.bom);
+        assert!(format.final_newline);
+        assert_eq!(text, "a = 1\nb = 2");
+        assert_eq!(format.to_string(), "dos,bom");
+    }
+
+    #[test]
+    fn detect_unix_without_final_newline() {
+        let (format, text) = FileFormat::detect("one\ntwo");
+        assert_eq!(format.line_ending, LineEnding::Unix);
+        assert!(!format.bom);
+        assert!(!format.final_newline);
+        assert_eq!(text, "one\ntwo");
+        assert_eq!(format.to_string(), "unix,noeol");
+    }
+
+    #[test]
+    fn mixed_endings_follow_the_majority() {
+        let (format, text) = FileFormat::detect("one\ntwo\r\nthree\n");
+        assert_eq!(format.line_ending, LineEnding::Unix);
+        assert_eq!(text, "one\ntwo\nthree");
+
+        let (format, text) = FileFormat::detect("one\r\ntwo\nthree\r\n");
+        assert_eq!(format.line_ending, LineEnding::Dos);
+        assert_eq!(text, "one\ntwo\nthree");
+    }
+}
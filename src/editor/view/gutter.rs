@@ -0,0 +1,112 @@
+use super::buffer::Buffer;
+use super::wrap;
+use crate::editor::terminal::{ScreenOffset, ScreenPosition, Size, Terminal};
+use std::io::Error;
+
+// like vim's numberwidth, the narrowest gutter counting the space after the number
+const MIN_WIDTH: usize = 4;
+
+/// how lines are numbered, set with :set number and :set relativenumber
+/// with both on the cursor line shows its own number and the rest their distance to it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineNumbers {
+    pub absolute: bool,
+    pub relative: bool,
+}
+
+impl LineNumbers {
+    /// the columns taken at the left of the screen, none when numbers are off
+    pub fn width(self, buffer_len: usize) -> usize {
+        if !self.absolute && !self.relative {
+            return 0;
+        }
+        let digits = buffer_len.to_string().len();
+        std::cmp::max(digits.saturating_add(1), MIN_WIDTH)
+    }
+
+    /// the gutter text for a line, padded to the gutter width
+    pub fn label(self, index: usize, cursor: usize, width: usize) -> String {
+        if width == 0 {
+            return String::new();
+        }
+        let digits = width.saturating_sub(1);
+        match (self.absolute, self.relative) {
+            (_, true) if index != cursor => format!("{:>digits$} ", index.abs_diff(cursor)),
+            (false, true) => format!("{:>digits$} ", 0),
+            // the hybrid cursor line stands out by sitting on the left like in vim
+            (true, true) => format!("{:<digits$} ", index.saturating_add(1)),
+            _ => format!("{:>digits$} ", index.saturating_add(1)),
+        }
+    }
+}
+
+/// clear a screen row and draw the gutter of the buffer line shown on it
+pub fn start_row(
+    row: usize,
+    index: usize,
+    cursor: usize,
+    offset: &ScreenOffset,
+) -> Result<(), Error> {
+    Terminal::render_line(row, offset.numbers.label(index, cursor, offset.gutter))
+}
+
+/// clear a screen row that carries on a wrapped line, its gutter is left blank
+pub fn continue_row(row: usize, offset: &ScreenOffset) -> Result<(), Error> {
+    Terminal::render_line(row, " ".repeat(offset.gutter))
+}
+
+/// redraw only the gutter of every line on screen, relative numbers change as the cursor moves
+pub fn render_labels(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    cursor: usize,
+) -> Result<(), Error> {
+    if offset.gutter == 0 {
+        return Ok(());
+    }
+    for screen_row in wrap::screen_rows(buffer, offset, size, ScreenOffset::cursor_rows(size)) {
+        let Some(index) = screen_row.line.filter(|_| screen_row.first) else {
+            continue;
+        };
+        Terminal::move_cursor_to(ScreenPosition {
+            height: screen_row.row,
+            width: 0,
+        })?;
+        Terminal::print(offset.numbers.label(index, cursor, offset.gutter))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gutter_labels() {
+        let off = LineNumbers::default();
+        let absolute = LineNumbers {
+            absolute: true,
+            relative: false,
+        };
+        let relative = LineNumbers {
+            absolute: false,
+            relative: true,
+        };
+        let hybrid = LineNumbers {
+            absolute: true,
+            relative: true,
+        };
+        assert_eq!(off.width(5000), 0);
+        assert_eq!(off.label(3, 1, 0), "");
+        assert_eq!(absolute.width(0), 4);
+        assert_eq!(absolute.width(999), 4);
+        assert_eq!(absolute.width(1000), 5);
+
+        assert_eq!(absolute.label(3, 1, 4), "  4 ");
+        assert_eq!(relative.label(3, 1, 4), "  2 ");
+        assert_eq!(relative.label(1, 1, 4), "  0 ");
+        assert_eq!(hybrid.label(0, 1, 4), "  1 ");
+        assert_eq!(hybrid.label(1, 1, 4), "2   ");
+    }
+}
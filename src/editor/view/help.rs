@@ -2,7 +2,7 @@ use super::Size;
 use crate::editor::editorcommands::HelpCommand;
 use crate::editor::terminal::{ScreenPosition, Terminal};
 use crossterm::event::{read, Event, KeyEvent};
-use crossterm::style::{Color, PrintStyledContent, StyledContent, Stylize};
+use crossterm::style::{Color, StyledContent, Stylize};
 
 // trying to get the help mapping items map at comptime
 // since these are static
@@ -20,6 +20,9 @@ enum HelpKeys {
     SnapDown,
     Highlight,
     VimMode,
+    Undo,
+    Redo,
+    Replace,
 }
 
 impl From<&'static str> for HelpKeys {
@@ -33,6 +36,9 @@ impl From<&'static str> for HelpKeys {
             "SnapDown" => Self::SnapDown,
             "Highlight" => Self::Highlight,
             "VimMode" => Self::VimMode,
+            "Undo" => Self::Undo,
+            "Redo" => Self::Redo,
+            "Replace" => Self::Replace,
             _ => panic!("Unsupported item"),
         }
     }
@@ -73,11 +79,23 @@ impl HelpKeys {
                 offset: 9,
                 help_str: "Ctrl-n = vim mode   ",
             },
+            Self::Undo => &HelpItemMap {
+                offset: 10,
+                help_str: "Ctrl-z = undo       ",
+            },
+            Self::Redo => &HelpItemMap {
+                offset: 11,
+                help_str: "Ctrl-y = redo       ",
+            },
+            Self::Replace => &HelpItemMap {
+                offset: 12,
+                help_str: "Ctrl-r = replace    ",
+            },
         }
     }
 }
 
-const HELP_ITEMS: [&str; 8] = [
+const HELP_ITEMS: [&str; 11] = [
     "Save",
     "Quit",
     "JumpTo",
@@ -86,6 +104,9 @@ const HELP_ITEMS: [&str; 8] = [
     "SnapDown",
     "Highlight",
     "VimMode",
+    "Undo",
+    "Redo",
+    "Replace",
 ];
 
 pub struct Help;
@@ -128,13 +149,13 @@ impl Help {
             })
             .unwrap();
 
-            Terminal::queue_command(PrintStyledContent(highlight_seg)).unwrap();
+            Terminal::print_styled(highlight_seg).unwrap();
         }
         Terminal::execute().unwrap();
     }
 }
 
-const VIM_BINDINGS: [&str; 12] = [
+const VIM_BINDINGS: [&str; 14] = [
     "Jump To Begining Of Next Word",
     "Jump To End Of Current Word",
     "Jump to Begining Of Current Word",
@@ -147,6 +168,8 @@ const VIM_BINDINGS: [&str; 12] = [
     "Up",
     "Down",
     "Exit",
+    "Undo",
+    "Redo",
 ];
 
 enum VimKeyBindings {
@@ -162,6 +185,8 @@ enum VimKeyBindings {
     Up,
     Down,
     Exit,
+    Undo,
+    Redo,
 }
 
 impl From<&'static str> for VimKeyBindings {
@@ -178,6 +203,8 @@ impl From<&'static str> for VimKeyBindings {
             "Down" => Self::Down,
             "Page Left" => Self::PageLeft,
             "Page Right" => Self::PageRight,
+            "Undo" => Self::Undo,
+            "Redo" => Self::Redo,
             _ => Self::Exit,
         }
     }
@@ -239,6 +266,14 @@ impl VimKeyBindings {
                 offset: 12,
                 help_str: "0 = Page Left                 ",
             },
+            VimKeyBindings::Undo => &VimItemHelpMap {
+                offset: 13,
+                help_str: "u = Undo                      ",
+            },
+            VimKeyBindings::Redo => &VimItemHelpMap {
+                offset: 14,
+                help_str: "Ctrl-r = Redo                 ",
+            },
         }
     }
 }
@@ -273,7 +308,7 @@ impl VimHelpScreen {
             })
             .unwrap();
 
-            Terminal::queue_command(PrintStyledContent(highlight_seg)).unwrap();
+            Terminal::print_styled(highlight_seg).unwrap();
         }
         Terminal::execute().unwrap();
     }
@@ -1,11 +1,13 @@
-use super::clipboard_interface::ClipboardUtils;
-use crate::editor::editorcommands::HighlightCommand;
+use super::keys::KeySource;
+use super::motion::{TextObject, TextObjectKind, TextRegion};
+use super::registers::{Register, RegisterKind, Registers};
+use crate::editor::editorcommands::{HighlightCommand, SelectionKind};
 use crate::editor::{
     terminal::{Coordinate, Position, ScreenOffset, ScreenPosition, Size, Terminal},
-    view::{Buffer, Mode},
+    view::{gutter, wrap, Buffer, Mode},
 };
-use crossterm::event::{read, Event};
-use crossterm::style::{Color, Print, PrintStyledContent, StyledContent, Stylize};
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use crossterm::style::{Color, StyledContent, Stylize};
 use std::error::Error;
 use std::ops::{Range, RangeInclusive};
 
@@ -18,6 +20,22 @@ pub enum Orientation {
     EndFirst,
 }
 
+/// how the highlight was left, along with the highlighted text
+pub enum HighlightExit {
+    Copy(Register),
+    Delete(Register),
+    Indent {
+        rows: usize,
+        right: bool,
+    },
+    BlockInsert {
+        at: Position,
+        rows: usize,
+        pad: bool,
+    }, // type at `at` for every row
+    Revert,
+}
+
 /// seperate the logic for highlight a partial and full line
 pub enum LineType {
     Middle,
@@ -32,6 +50,8 @@ pub struct Highlight<'a> {
     offset: ScreenOffset,
     or: Orientation,
     line_range: RangeInclusive<usize>,
+    kind: SelectionKind,
+    register: Option<char>,  // register named with " during the highlight
     start: &'a mut Position, //one mutably borrowed, the view's position
     size: &'a mut Size,      //owned by view
     buffer: &'a mut Buffer,  //owned by view
@@ -43,27 +63,47 @@ impl Highlight<'_> {
         offset: ScreenOffset,
         size: &'a mut Size,
         buffer: &'a mut Buffer,
+        kind: SelectionKind,
     ) -> Highlight<'a> {
         Highlight {
             offset,
             end: *end,
             or: Orientation::default(),
             line_range: 0..=0,
+            kind,
+            register: None,
             start: end, // the immutable reference
             size,
             buffer,
         }
     }
 
-    pub fn run<P>(&mut self, highlight: Color, text: Color, parser: P)
+    /// keys come through the vim mode key source, so macros record and replay the highlight
+    pub fn run<P>(
+        &mut self,
+        highlight: Color,
+        text: Color,
+        parser: P,
+        keys: &mut KeySource,
+    ) -> HighlightExit
     where
         P: Fn(Event) -> Result<HighlightCommand, Box<dyn Error>>,
     {
         let res = self.initial_set_screen();
         debug_assert!(res.is_ok());
+        if self.kind != SelectionKind::Chars {
+            // a line or block highlight shows the cursor row straight away
+            self.adjust_range();
+            let res = if self.offset.wrap {
+                self.render_wrapped(highlight, text)
+            } else {
+                self.render_rows(highlight, text)
+            };
+            let res = res.and_then(|()| self.post_render());
+            debug_assert!(res.is_ok());
+        }
         loop {
-            let Ok(read_event) = read() else { continue }; //skipping errors here
-            match parser(read_event) {
+            match parser(keys.next_event()) {
                 Ok(event) => match event {
                     HighlightCommand::Move(dir) => dir.move_cursor(&mut self.end, &*self.buffer),
                     HighlightCommand::Copy => {
@@ -71,37 +111,63 @@ impl Highlight<'_> {
                     }
                     HighlightCommand::Resize(new_size) => *self.size = new_size,
                     HighlightCommand::RevertState => {
-                        return;
+                        return HighlightExit::Revert;
                     }
                     HighlightCommand::Delete => {
-                        if *self.start != self.end {
-                            self.batch_delete();
+                        if self.kind != SelectionKind::Chars {
+                            let deleted = self.selected();
+                            self.delete_rows();
+                            return HighlightExit::Delete(deleted);
                         }
-                        return;
+                        if *self.start == self.end {
+                            return HighlightExit::Revert;
+                        }
+                        self.resolve_orientation();
+                        let deleted = self.generate_copy_str();
+                        self.batch_delete();
+                        return HighlightExit::Delete(Register::new(deleted, RegisterKind::Chars));
+                    }
+                    HighlightCommand::Indent(right) => return self.indent(right),
+                    HighlightCommand::BlockInsert(append) => {
+                        if self.kind == SelectionKind::Block {
+                            return self.block_insert(append);
+                        }
+                        continue;
+                    }
+                    HighlightCommand::Selection(kind) => self.kind = kind,
+                    HighlightCommand::Register => {
+                        if let Event::Key(KeyEvent {
+                            code: KeyCode::Char(name),
+                            ..
+                        }) = keys.next_event()
+                        {
+                            // the selection can not go to a read only register like "/
+                            if Registers::writable(Some(name)).is_ok() {
+                                self.register = Some(name);
+                            }
+                        }
+                        continue;
+                    }
+                    HighlightCommand::TextObject(inner) => {
+                        self.select_text_object(inner, &keys.next_event());
                     }
                     HighlightCommand::NoAction => continue,
                 },
                 Err(_) => continue,
             }
-            let view_delta = self
-                .end
-                .max_displacement_from_view(&self.offset, self.size, 2);
-            match view_delta {
-                0_usize => {}
-                1_usize => self
-                    .offset
-                    .update_offset_single_move(&self.end, self.size, 2),
-                _ => self.offset.handle_offset_screen_snap(
-                    &self.end,
-                    self.size,
-                    2,
-                    self.buffer.len(),
-                ),
-            }
+            self.follow_end();
             self.resolve_orientation();
             self.adjust_range();
             let res = Terminal::hide_cursor();
             debug_assert!(res.is_ok());
+            if self.offset.wrap {
+                let res = self
+                    .render_wrapped(highlight, text)
+                    .and_then(|()| self.status_line())
+                    .and_then(|()| self.post_render());
+                debug_assert!(res.is_ok());
+                continue;
+            }
             let res = self.render();
             debug_assert!(res.is_ok());
             /*
@@ -112,7 +178,9 @@ impl Highlight<'_> {
             }
             */
 
-            let res = if self.start.height == self.end.height {
+            let res = if self.kind != SelectionKind::Chars {
+                self.render_rows(highlight, text)
+            } else if self.start.height == self.end.height {
                 self.render_single_line(highlight, text)
             } else {
                 self.multi_line_render(highlight, text)
@@ -125,24 +193,321 @@ impl Highlight<'_> {
             debug_assert!(res.is_ok());
         }
 
-        let copy_string = self.generate_copy_str();
+        HighlightExit::Copy(self.selected())
+    }
 
-        if !copy_string.is_empty() {
-            let res = ClipboardUtils::copy_text_to_clipboard(copy_string);
-            debug_assert!(res.is_ok());
+    /// scroll the view so the moving end stays on screen
+    fn follow_end(&mut self) {
+        if self.offset.wrap {
+            wrap::keep_in_view(self.buffer, &self.end, &mut self.offset, self.size);
+            return;
+        }
+        let view_delta = self
+            .end
+            .max_displacement_from_view(&self.offset, self.size, 2);
+        match view_delta {
+            0_usize => {}
+            1_usize => self
+                .offset
+                .update_offset_single_move(&self.end, self.size, 2),
+            _ => self
+                .offset
+                .handle_offset_screen_snap(&self.end, self.size, 2, self.buffer.len()),
+        }
+    }
+
+    pub fn register(&self) -> Option<char> {
+        self.register
+    }
+
+    /// the first and last line of the highlight
+    fn rows(&self) -> (usize, usize) {
+        (
+            std::cmp::min(self.start.height, self.end.height),
+            std::cmp::max(self.start.height, self.end.height),
+        )
+    }
+
+    /// the left column of a block, and the column after its right edge
+    fn columns(&self) -> (usize, usize) {
+        (
+            std::cmp::min(self.start.width, self.end.width),
+            std::cmp::max(self.start.width, self.end.width).saturating_add(1),
+        )
+    }
+
+    /// the highlighted text, in the shape it was selected in
+    fn selected(&self) -> Register {
+        let (first, last) = self.rows();
+        match self.kind {
+            SelectionKind::Chars => Register::new(self.generate_copy_str(), RegisterKind::Chars),
+            SelectionKind::Lines => Register::new(
+                (first..=last)
+                    .map(|line| self.buffer.line(line).raw_string)
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                RegisterKind::Lines,
+            ),
+            SelectionKind::Block => {
+                let (left, right) = self.columns();
+                Register::new(
+                    self.buffer.get_block(first, last, left, right),
+                    RegisterKind::Block,
+                )
+            }
+        }
+    }
+
+    /// indent or outdent every line the highlight touches
+    fn indent(&mut self, right: bool) -> HighlightExit {
+        let (first, last) = self.rows();
+        self.buffer.shift_lines(first, last, right);
+        self.start.set_position(Position {
+            height: first,
+            width: self.buffer.first_non_blank(first),
+            max_width: usize::default(),
+        });
+        HighlightExit::Indent {
+            rows: last.saturating_sub(first).saturating_add(1),
+            right,
+        }
+    }
+
+    /// delete a line or block highlight, the cursor goes to its top left
+    fn delete_rows(&mut self) {
+        let (first, last) = self.rows();
+        if self.kind == SelectionKind::Lines {
+            self.buffer.remove_lines(first, last);
+            let height = std::cmp::min(first, self.buffer.len().saturating_sub(1));
+            self.start.set_position(Position {
+                height,
+                width: self.buffer.first_non_blank(height),
+                max_width: usize::default(),
+            });
+        } else {
+            let (left, right) = self.columns();
+            self.buffer.remove_block(first, last, left, right);
+            self.start.set_position(Position {
+                height: first,
+                width: left,
+                max_width: usize::default(),
+            });
+        }
+    }
+
+    /// where typing goes for I and A on a block, before or after it on the top row
+    fn block_insert(&mut self, append: bool) -> HighlightExit {
+        let (first, last) = self.rows();
+        let (left, right) = self.columns();
+        let column = if append { right } else { left };
+        // appending past the end of a short top row fills it out first
+        let line_len = self.buffer.line(first).len();
+        if append && line_len < column {
+            let end_of_line = Position {
+                height: first,
+                width: line_len,
+                max_width: usize::default(),
+            };
+            self.buffer
+                .insert_text(&end_of_line, &" ".repeat(column.saturating_sub(line_len)));
+        }
+        HighlightExit::BlockInsert {
+            at: Position {
+                height: first,
+                width: column,
+                max_width: column,
+            },
+            rows: last.saturating_sub(first).saturating_add(1),
+            pad: append,
+        }
+    }
+
+    /// render the rows of a line or block highlight
+    fn render_rows(&self, highlight_color: Color, text_color: Color) -> Result<(), Box<dyn Error>> {
+        let (left, right) = self.columns();
+        let visible_rows = self.offset.height
+            ..self
+                .offset
+                .height
+                .saturating_add(self.size.height)
+                .saturating_sub(1);
+        for row in self.line_range.clone() {
+            if !visible_rows.contains(&row) {
+                continue;
+            }
+            let line = self.buffer.line(row).get_line_subset(
+                self.offset.width
+                    ..self
+                        .offset
+                        .width
+                        .saturating_add(self.offset.text_width(self.size)),
+            );
+            let len = line.len();
+            let selected = if self.kind == SelectionKind::Block {
+                std::cmp::min(left.saturating_sub(self.offset.width), len)
+                    ..std::cmp::min(right.saturating_sub(self.offset.width), len)
+            } else {
+                0..len
+            };
+            // an empty line still shows it is selected
+            let highlighted = match line.grapheme_slice(selected.clone()) {
+                "" if self.kind == SelectionKind::Lines => " ",
+                slice => slice,
+            };
+            gutter::start_row(
+                row.saturating_sub(self.offset.height),
+                row,
+                self.end.height,
+                &self.offset,
+            )?;
+            Terminal::print(line.grapheme_slice(0..selected.start))?;
+            Terminal::print_styled(highlighted.to_string().with(text_color).on(highlight_color))?;
+            Terminal::print(line.grapheme_slice(selected.end..len))?;
+        }
+        Ok(())
+    }
+
+    /// the graphemes of a line that are highlighted, None when the line is not
+    fn selected_columns(&self, index: usize) -> Option<Range<usize>> {
+        let (first, last) = self.rows();
+        if !(first..=last).contains(&index) {
+            return None;
+        }
+        let len = self.buffer.line(index).len();
+        let columns = match self.kind {
+            SelectionKind::Lines => 0..len,
+            SelectionKind::Block => {
+                let (left, right) = self.columns();
+                left..right
+            }
+            SelectionKind::Chars => {
+                let (top, bottom) = match self.or {
+                    Orientation::StartFirst => (*self.start, self.end),
+                    Orientation::EndFirst => (self.end, *self.start),
+                };
+                let start = if index == top.height { top.width } else { 0 };
+                let end = if index == bottom.height {
+                    bottom.width.saturating_add(1)
+                } else {
+                    len
+                };
+                start..end
+            }
+        };
+        Some(std::cmp::min(columns.start, len)..std::cmp::min(columns.end, len))
+    }
+
+    /// render every row of a wrapped view, a long line carries on over the rows below it
+    fn render_wrapped(
+        &self,
+        highlight_color: Color,
+        text_color: Color,
+    ) -> Result<(), Box<dyn Error>> {
+        let rows = self.size.height.saturating_sub(1);
+        for screen_row in wrap::screen_rows(self.buffer, &self.offset, self.size, rows) {
+            let Some(index) = screen_row.line else {
+                Terminal::render_line(screen_row.row, "~")?;
+                continue;
+            };
+            if screen_row.first {
+                gutter::start_row(screen_row.row, index, self.end.height, &self.offset)?;
+            } else {
+                gutter::continue_row(screen_row.row, &self.offset)?;
+            }
+            let line = self.buffer.line(index);
+            let columns = screen_row.columns;
+            let Some(selected) = self.selected_columns(index) else {
+                Terminal::print(line.grapheme_slice(columns))?;
+                continue;
+            };
+            // an empty line still shows it is selected
+            if line.is_empty() && self.kind != SelectionKind::Block {
+                Terminal::print_styled(" ".to_string().with(text_color).on(highlight_color))?;
+                continue;
+            }
+            let start = selected.start.clamp(columns.start, columns.end);
+            let end = selected.end.clamp(start, columns.end);
+            Terminal::print(line.grapheme_slice(columns.start..start))?;
+            Terminal::print_styled(
+                line.grapheme_slice(start..end)
+                    .to_string()
+                    .with(text_color)
+                    .on(highlight_color),
+            )?;
+            Terminal::print(line.grapheme_slice(end..columns.end))?;
+        }
+        Ok(())
+    }
+
+    /// highlight the object named by the key after i or a around the cursor
+    fn select_text_object(&mut self, inner: bool, event: &Event) {
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            ..
+        }) = *event
+        else {
+            return;
+        };
+        let Ok(kind) = TextObjectKind::try_from(c) else {
+            return;
+        };
+        let object = TextObject { kind, inner };
+        // the highlight end is inclusive
+        match object.region(&self.end, self.buffer) {
+            Some(TextRegion::Chars { start, end }) if start != end => {
+                self.start.set_position(start);
+                self.end = if end.width == 0 {
+                    let height = end.height.saturating_sub(1);
+                    Position {
+                        height,
+                        width: self.buffer.line(height).len().saturating_sub(1),
+                        max_width: usize::default(),
+                    }
+                } else {
+                    Position {
+                        width: end.width.saturating_sub(1),
+                        ..end
+                    }
+                };
+            }
+            Some(TextRegion::Lines { first, last }) => {
+                self.start.set_position(Position {
+                    height: first,
+                    width: 0,
+                    max_width: usize::default(),
+                });
+                self.end = Position {
+                    height: last,
+                    width: self.buffer.line(last).len().saturating_sub(1),
+                    max_width: usize::default(),
+                };
+            }
+            _ => {}
         }
     }
 
     fn initial_set_screen(&self) -> Result<(), Box<dyn Error>> {
         self.status_line()?; // to see status line before first event is read
-        Terminal::move_cursor_to(self.end.to_screen_position())?;
+        Terminal::move_cursor_to(self.cursor_cell())?;
         Terminal::execute()?;
         Ok(())
     }
 
+    /// the gutter of a line, relative numbers count from the moving end
+    fn label(&self, index: usize) -> String {
+        self.offset
+            .numbers
+            .label(index, self.end.height, self.offset.gutter)
+    }
+
+    /// where the moving end is drawn, on the row it wraps onto in a wrapped view
+    fn cursor_cell(&self) -> ScreenPosition {
+        wrap::screen_position(self.buffer, &self.end, &self.offset, self.size)
+    }
+
     #[inline]
     fn post_render(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::move_cursor_to(self.end.relative_view_position(&self.offset))?;
+        Terminal::move_cursor_to(self.cursor_cell())?;
         Terminal::show_cursor()?;
         Terminal::execute()?;
         Ok(())
@@ -155,6 +520,7 @@ impl Highlight<'_> {
             self.buffer.is_saved,
             self.size,
             self.buffer.filename.as_deref(),
+            self.buffer.format,
             Some((self.end.height.saturating_add(1), self.buffer.len())),
         )?;
         Ok(())
@@ -178,11 +544,15 @@ impl Highlight<'_> {
                 continue;
             }
 
-            if let Some(line) = self.buffer.text.get(current_row) {
-                Terminal::render_line(
-                    relative_row,
+            if let Some(line) = self.buffer.get_line(current_row) {
+                gutter::start_row(relative_row, current_row, self.end.height, &self.offset)?;
+                Terminal::print(
                     line.get_line_subset(
-                        self.offset.width..self.offset.width.saturating_add(self.size.width),
+                        self.offset.width
+                            ..self
+                                .offset
+                                .width
+                                .saturating_add(self.offset.text_width(self.size)),
                     ),
                 )?;
             } else {
@@ -271,7 +641,9 @@ impl Highlight<'_> {
         };
 
         // cond for is the highlight ends at the end of the line
-        let te = self.buffer.text[self.start.height]
+        let te = self
+            .buffer
+            .line(self.start.height)
             .raw_string
             .len()
             .saturating_sub(1)
@@ -288,8 +660,9 @@ impl Highlight<'_> {
         };
 
         HighlightUtility::render_highlight_line(
-            &self.buffer.text[self.start.height].raw_string,
-            self.start.height,
+            &self.buffer.line(self.start.height).raw_string,
+            self.start.height.saturating_sub(self.offset.height),
+            &self.label(self.start.height),
             h_r,
             &h_t,
             highlight_color,
@@ -309,7 +682,9 @@ impl Highlight<'_> {
         );
         let visible_width_range = RangeInclusive::new(
             self.offset.width,
-            self.offset.width.saturating_add(self.size.width),
+            self.offset
+                .width
+                .saturating_add(self.offset.text_width(self.size)),
         );
 
         for line_height in self.line_range.clone() {
@@ -318,7 +693,7 @@ impl Highlight<'_> {
                 continue;
             }
 
-            let line_text = &self.buffer.text[line_height].raw_string;
+            let line_text = &self.buffer.line(line_height).raw_string;
 
             // if line width not on screen
             if line_text.len().saturating_sub(1) < *visible_width_range.start() {
@@ -346,6 +721,7 @@ impl Highlight<'_> {
                     Orientation::StartFirst => HighlightUtility::render_highlight_line(
                         visible_line,
                         line_height.saturating_sub(self.offset.height),
+                        &self.label(line_height),
                         self.start.width..visible_line.len(),
                         &LineType::Trailing,
                         highlight_color,
@@ -354,6 +730,7 @@ impl Highlight<'_> {
                     Orientation::EndFirst => HighlightUtility::render_highlight_line(
                         visible_line,
                         line_height.saturating_sub(self.offset.height),
+                        &self.label(line_height),
                         0..self.start.width.saturating_add(1),
                         &LineType::Leading,
                         highlight_color,
@@ -370,6 +747,7 @@ impl Highlight<'_> {
                     Orientation::StartFirst => HighlightUtility::render_highlight_line(
                         visible_line,
                         line_height.saturating_sub(self.offset.height),
+                        &self.label(line_height),
                         0..self.end.width.saturating_sub(self.offset.width),
                         &LineType::Leading,
                         highlight_color,
@@ -378,6 +756,7 @@ impl Highlight<'_> {
                     Orientation::EndFirst => HighlightUtility::render_highlight_line(
                         visible_line,
                         line_height.saturating_sub(self.offset.height),
+                        &self.label(line_height),
                         self.end.width..visible_line.len(),
                         &LineType::Trailing,
                         highlight_color,
@@ -391,6 +770,7 @@ impl Highlight<'_> {
             HighlightUtility::render_highlight_line(
                 visible_line,
                 line_height.saturating_sub(self.offset.height),
+                &self.label(line_height),
                 0..visible_line.len(),
                 &LineType::All,
                 highlight_color,
@@ -441,7 +821,7 @@ impl Highlight<'_> {
                     self.buffer.delete_segment(
                         self.start,
                         &mut Position {
-                            width: self.buffer.text[self.start.height].len().saturating_sub(1),
+                            width: self.buffer.line(self.start.height).len().saturating_sub(1),
                             height: self.start.height,
                             max_width: usize::default(),
                         },
@@ -453,7 +833,7 @@ impl Highlight<'_> {
                     self.buffer.delete_segment(
                         self.start,
                         &mut Position {
-                            width: self.buffer.text[self.end.height].len().saturating_sub(1),
+                            width: self.buffer.line(self.end.height).len().saturating_sub(1),
                             height: self.end.height,
                             max_width: usize::default(),
                         },
@@ -480,13 +860,13 @@ impl HighlightUtility {
     pub fn render_highlight_line(
         line: &str,
         height: usize,
+        label: &str, // the gutter of the line
         h_range: Range<usize>,
         ctx: &LineType,
         h_color: Color,
         t_color: Color,
     ) -> Result<(), Box<dyn Error>> {
-        Terminal::move_cursor_to(ScreenPosition { height, width: 0 })?;
-        Terminal::clear_line()?;
+        Terminal::render_line(height, label)?;
 
         let segment_to_highlight: String = line[h_range.clone()].to_owned();
         let highlight_seg: StyledContent<String> =
@@ -496,20 +876,20 @@ impl HighlightUtility {
         // on the line based on line type
         match ctx {
             LineType::All => {
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
+                Terminal::print_styled(highlight_seg)?;
             }
             LineType::Leading => {
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-                Terminal::queue_command(Print(&line[(h_range.end)..]))?;
+                Terminal::print_styled(highlight_seg)?;
+                Terminal::print(&line[(h_range.end)..])?;
             }
             LineType::Trailing => {
-                Terminal::queue_command(Print(&line[..h_range.start]))?;
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
+                Terminal::print(&line[..h_range.start])?;
+                Terminal::print_styled(highlight_seg)?;
             }
             LineType::Middle => {
-                Terminal::queue_command(Print(&line[..h_range.start]))?;
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-                Terminal::queue_command(Print(&line[h_range.end..]))?;
+                Terminal::print(&line[..h_range.start])?;
+                Terminal::print_styled(highlight_seg)?;
+                Terminal::print(&line[h_range.end..])?;
             }
         }
 
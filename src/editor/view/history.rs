@@ -0,0 +1,183 @@
+use crate::editor::terminal::Position;
+
+// upper bound on the number of undo groups kept around
+const MAX_HISTORY: usize = 1000;
+
+/// a single primitive edit applied to the buffer
+/// every buffer mutation is expressed as one or more of these
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Insert { at: Position, text: String },
+    Delete { at: Position, text: String },
+}
+
+/// a set of changes that are undone and redone together
+/// along with where the cursor was before and after the edit
+#[derive(Clone, Debug)]
+pub struct EditGroup {
+    pub changes: Vec<Change>,
+    pub cursor_before: Position,
+    pub cursor_after: Position,
+}
+
+#[derive(Clone, Default)]
+pub struct EditHistory {
+    undo_stack: Vec<EditGroup>,
+    redo_stack: Vec<EditGroup>,
+    pending: Option<EditGroup>,
+    typed: Option<String>, // text typed since leaving vim mode, kept for dot repeat
+}
+
+impl EditHistory {
+    /// add a change to the group currently being built
+    /// opens a new group when there is none, any new edit invalidates the redo stack
+    pub fn record(&mut self, change: Change, cursor: Position) {
+        if let Some(typed) = self.typed.as_mut() {
+            match &change {
+                Change::Insert { text, .. } => typed.push_str(text),
+                // a backspace takes back what was typed
+                Change::Delete { text, .. } => {
+                    if typed.ends_with(text.as_str()) {
+                        typed.truncate(typed.len().saturating_sub(text.len()));
+                    }
+                }
+            }
+        }
+        if let Some(group) = self.pending.as_mut() {
+            group.changes.push(change);
+        } else {
+            self.redo_stack.clear();
+            self.pending = Some(EditGroup {
+                changes: vec![change],
+                cursor_before: cursor,
+                cursor_after: cursor,
+            });
+        }
+    }
+
+    /// close the group currently being built
+    /// called once a command that edits the buffer has finished
+    pub fn seal(&mut self, cursor: Position) {
+        let Some(mut group) = self.pending.take() else {
+            return;
+        };
+        group.cursor_after = cursor;
+        self.undo_stack.push(group);
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// start keeping the text inserted from here on
+    pub fn start_typing(&mut self) {
+        self.typed = Some(String::new());
+    }
+
+    /// stop keeping inserted text, returns what was typed
+    pub fn take_typed(&mut self) -> Option<String> {
+        self.typed.take()
+    }
+
+    pub fn pop_undo(&mut self) -> Option<EditGroup> {
+        self.undo_stack.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<EditGroup> {
+        self.redo_stack.pop()
+    }
+
+    pub fn push_undo(&mut self, group: EditGroup) {
+        self.undo_stack.push(group);
+    }
+
+    pub fn push_redo(&mut self, group: EditGroup) {
+        self.redo_stack.push(group);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(height: usize, width: usize) -> Position {
+        Position {
+            height,
+            width,
+            max_width: usize::default(),
+        }
+    }
+
+    #[test]
+    fn changes_group_until_sealed() {
+        let mut history = EditHistory::default();
+        history.record(
+            Change::Insert {
+                at: pos(0, 0),
+                text: "a".into(),
+            },
+            pos(0, 0),
+        );
+        history.record(
+            Change::Insert {
+                at: pos(0, 1),
+                text: "b".into(),
+            },
+            pos(0, 1),
+        );
+        history.seal(pos(0, 2));
+
+        let group = history.pop_undo().unwrap();
+        assert_eq!(group.changes.len(), 2);
+        assert_eq!(group.cursor_before, pos(0, 0));
+        assert_eq!(group.cursor_after, pos(0, 2));
+        assert!(history.pop_undo().is_none());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut history = EditHistory::default();
+        history.record(
+            Change::Insert {
+                at: pos(0, 0),
+                text: "a".into(),
+            },
+            pos(0, 0),
+        );
+        history.seal(pos(0, 1));
+        let group = history.pop_undo().unwrap();
+        history.push_redo(group);
+
+        history.record(
+            Change::Delete {
+                at: pos(0, 0),
+                text: "a".into(),
+            },
+            pos(0, 1),
+        );
+        assert!(history.pop_redo().is_none());
+    }
+
+    #[test]
+    fn typed_text_follows_backspace() {
+        let mut history = EditHistory::default();
+        history.start_typing();
+        for (width, text) in ["a", "b", "c"].into_iter().enumerate() {
+            history.record(
+                Change::Insert {
+                    at: pos(0, width),
+                    text: text.into(),
+                },
+                pos(0, width),
+            );
+        }
+        history.record(
+            Change::Delete {
+                at: pos(0, 2),
+                text: "c".into(),
+            },
+            pos(0, 3),
+        );
+        assert_eq!(history.take_typed(), Some("ab".to_string()));
+        assert!(history.take_typed().is_none());
+    }
+}
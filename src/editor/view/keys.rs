@@ -0,0 +1,54 @@
+use crossterm::event::{poll, read, Event};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// where vim mode and the modes it opens read their keys from
+/// a running macro is read before the terminal, keys from the terminal are kept while recording
+#[derive(Default)]
+pub struct KeySource {
+    pub replay: VecDeque<Event>, // macro keys waiting to be run
+    pub recording: Option<(char, Vec<Event>)>,
+    typed: Option<VecDeque<Event>>, // keys standing in for the terminal
+}
+
+impl KeySource {
+    /// keys handed over up front in place of the terminal
+    #[cfg(test)]
+    pub fn typed(keys: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            typed: Some(keys.into_iter().collect()),
+            ..Self::default()
+        }
+    }
+
+    pub fn next_event(&mut self) -> Event {
+        if let Some(event) = self.replay.pop_front() {
+            return event;
+        }
+        let event = self.read_event();
+        if let (Some((_, keys)), Event::Key(_)) = (&mut self.recording, &event) {
+            keys.push(event.clone());
+        }
+        event
+    }
+
+    /// a key typed while a macro runs stops it
+    pub fn interrupt_replay(&mut self) {
+        if !self.replay.is_empty() && self.typed.is_none() && poll(Duration::ZERO).unwrap_or(false)
+        {
+            self.replay.clear();
+        }
+    }
+
+    fn read_event(&mut self) -> Event {
+        if let Some(typed) = &mut self.typed {
+            return typed.pop_front().expect("ran out of typed keys");
+        }
+        // we are waiting on a single event
+        // so wait for an ok event
+        loop {
+            let Ok(read_event) = read() else { continue };
+            return read_event;
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::convert::TryFrom;
+use std::cell::OnceCell;
 use std::fmt;
 use std::ops::{Range, RangeInclusive};
 use unicode_segmentation::UnicodeSegmentation;
@@ -18,33 +18,42 @@ pub enum GraphemeWidth {
     Full,
 }
 
+impl GraphemeWidth {
+    pub fn from_str_width(val: &str) -> Self {
+        match val.width() {
+            0 | 1 => Self::Half,
+            _ => Self::Full,
+        }
+    }
+
+    /// the screen columns taken
+    pub fn columns(&self) -> usize {
+        match self {
+            Self::Half => 1,
+            Self::Full => 2,
+        }
+    }
+}
+
+/// the grapheme data for a single grapheme in a line
+/// stores the byte range of the grapheme in the line rather than a copy of it
 #[derive(Debug, Clone)]
 pub struct TextFragment {
-    pub grapheme: String,
+    pub start: usize,
+    pub end: usize,
     pub render_width: GraphemeWidth,
     replacement_text: Option<char>,
 }
 
-impl TryFrom<&str> for TextFragment {
-    type Error = String;
-    fn try_from(new_item: &str) -> Result<Self, Self::Error> {
-        let width = new_item.width();
-        let fragment_width = match width {
-            0 | 1 => GraphemeWidth::Half,
-            _ => GraphemeWidth::Full,
-        };
-
-        let replacement = match width {
+impl TextFragment {
+    fn new(grapheme: &str, start: usize) -> Self {
+        let replacement = match grapheme.width() {
             0 => {
-                let trimmed = new_item.trim();
+                let trimmed = grapheme.trim();
                 if trimmed == "\t" {
                     Some(' ')
                 } else {
-                    let control = trimmed
-                        .chars()
-                        .map(char::is_control)
-                        .reduce(|a, b| a | b)
-                        .expect("Error in reduction");
+                    let control = trimmed.chars().any(char::is_control);
                     let replace_val = if control {
                         '|'
                     } else if trimmed.is_empty() {
@@ -58,61 +67,130 @@ impl TryFrom<&str> for TextFragment {
             _ => None,
         };
 
-        Ok(Self {
-            grapheme: new_item.to_owned(),
-            render_width: fragment_width,
+        Self {
+            start,
+            end: start.saturating_add(grapheme.len()),
+            render_width: GraphemeWidth::from_str_width(grapheme),
             replacement_text: replacement,
-        })
+        }
     }
 }
 
+/// a single line of the buffer
+/// grapheme data is only computed the first time it is needed
 #[derive(Clone, Default)]
 pub struct Line {
-    pub string: Vec<TextFragment>,
     pub raw_string: String,
+    fragments: OnceCell<Vec<TextFragment>>,
 }
 
 impl fmt::Display for Line {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        let result: String = self
-            .string
-            .iter()
-            .map(|fragment| match fragment.replacement_text {
-                Some(char) => char.to_string().clone(),
-                None => fragment.grapheme.clone(),
-            })
-            .collect();
-
-        write!(formatter, "{result}")
+        for fragment in self.fragments() {
+            match fragment.replacement_text {
+                Some(char) => write!(formatter, "{char}")?,
+                None => write!(
+                    formatter,
+                    "{}",
+                    &self.raw_string[fragment.start..fragment.end]
+                )?,
+            }
+        }
+        Ok(())
     }
 }
 
 impl Line {
-    pub fn generate_raw_string(&mut self) {
-        self.raw_string = self.to_string();
+    pub fn fragments(&self) -> &[TextFragment] {
+        self.fragments.get_or_init(|| {
+            self.raw_string
+                .grapheme_indices(true)
+                .map(|(start, grapheme)| TextFragment::new(grapheme, start))
+                .collect()
+        })
     }
 
     pub fn len(&self) -> usize {
-        self.string.len()
+        self.fragments().len()
+    }
+
+    /// the byte offset of a grapheme index
+    /// indices past the end of the line resolve to the end of the line
+    pub fn byte_index(&self, grapheme_index: usize) -> usize {
+        self.fragments()
+            .get(grapheme_index)
+            .map_or(self.raw_string.len(), |fragment| fragment.start)
+    }
+
+    /// the grapheme index of a byte offset, an offset inside a grapheme resolves to the next one
+    pub fn grapheme_index(&self, byte_index: usize) -> usize {
+        self.fragments()
+            .partition_point(|fragment| fragment.start < byte_index)
+    }
+
+    /// the source text for a range of grapheme indices
+    pub fn grapheme_slice(&self, range: Range<usize>) -> &str {
+        let start = self.byte_index(range.start);
+        let end = std::cmp::max(start, self.byte_index(range.end));
+        &self.raw_string[start..end]
     }
 
     pub fn grapheme_len(&self) -> usize {
-        if self.string.is_empty() {
+        if self.is_empty() {
             return 0;
         }
 
         let len: usize = self
-            .string
+            .fragments()
             .iter()
-            .map(|fragment| match fragment.render_width {
-                GraphemeWidth::Full => 2usize,
-                GraphemeWidth::Half => 1usize,
-            })
+            .map(|fragment| fragment.render_width.columns())
             .sum::<usize>();
 
         len
     }
 
+    /// the screen columns taken by a range of graphemes
+    pub fn columns(&self, range: Range<usize>) -> usize {
+        self.fragments()
+            .get(range.start..range.end.min(self.len()))
+            .unwrap_or_default()
+            .iter()
+            .map(|fragment| fragment.render_width.columns())
+            .sum()
+    }
+
+    /// the grapheme each screen row starts at when the line is wrapped at `width` columns
+    /// a wide grapheme that does not fit at the end of a row goes to the next row
+    pub fn row_starts(&self, width: usize) -> Vec<usize> {
+        let mut starts = vec![0];
+        let mut used: usize = 0;
+        for (index, fragment) in self.fragments().iter().enumerate() {
+            let columns = fragment.render_width.columns();
+            if used > 0 && used.saturating_add(columns) > width {
+                starts.push(index);
+                used = 0;
+            }
+            used = used.saturating_add(columns);
+        }
+        starts
+    }
+
+    /// the grapheme of a range that covers a screen column counted from the start of the range
+    /// a column past the range is the last grapheme of it
+    pub fn index_at_column(&self, range: Range<usize>, column: usize) -> usize {
+        let mut used: usize = 0;
+        for index in range.clone() {
+            let Some(fragment) = self.fragments().get(index) else {
+                break;
+            };
+            used = used.saturating_add(fragment.render_width.columns());
+            if used > column {
+                return index;
+            }
+        }
+        range.end.min(self.len()).saturating_sub(1).max(range.start)
+    }
+
     pub fn get_next_word(&self, start: usize) -> Option<usize> {
         if self.is_empty() {
             return None;
@@ -214,6 +292,9 @@ impl Line {
         // then find the next alpha char
         // if neither are satisfied -> None
         let bytes = self.raw_string.as_bytes();
+        if pos >= bytes.len() {
+            return None;
+        }
         if is_alpha(bytes[pos]) {
             // currently at alpha char
             // find next non alpha char
@@ -283,6 +364,9 @@ impl Line {
         // then find next alpha
         // if end is not alpha
         // find the next alpha then find the next non alpha
+        if self.is_empty() {
+            return None;
+        }
         let len: usize = self.raw_string.len().saturating_sub(1);
         let bytes = self.raw_string.as_bytes();
         if is_alpha(bytes[len]) {
@@ -307,6 +391,8 @@ impl Line {
             return None;
         }
         let bytes = self.raw_string.as_bytes();
+        // the cursor may sit one past the last char
+        let pos = std::cmp::min(pos, bytes.len().saturating_sub(1));
         // making sure we are not at the begining of the line
         // if the current pos an alphabet char and is the char to the lest if a current alphabet
         // char
@@ -361,7 +447,7 @@ impl Line {
         // start at pos + 1 then find next alpha
         // then find next alpha again
         let len = self.raw_string.len().saturating_sub(1);
-        if pos == len {
+        if pos >= len {
             return None;
         }
         let bytes = self.raw_string.as_bytes();
@@ -400,6 +486,53 @@ impl Line {
         None
     }
 
+    /// the grapheme index of the `times`th match of `target` after, or before, `from`
+    pub fn find_grapheme(
+        &self,
+        from: usize,
+        target: &str,
+        forward: bool,
+        times: usize,
+    ) -> Option<usize> {
+        let fragments = self.fragments();
+        let is_target = |(_, fragment): &(usize, &TextFragment)| {
+            &self.raw_string[fragment.start..fragment.end] == target
+        };
+        let nth = times.saturating_sub(1);
+        if forward {
+            fragments
+                .iter()
+                .enumerate()
+                .skip(from.saturating_add(1))
+                .filter(is_target)
+                .nth(nth)
+                .map(|(i, _)| i)
+        } else {
+            fragments[..std::cmp::min(from, fragments.len())]
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(is_target)
+                .nth(nth)
+                .map(|(i, _)| i)
+        }
+    }
+
+    /// whether the grapheme at pos is the last of its word
+    pub fn is_word_end(&self, pos: usize) -> bool {
+        let is_word = |index: usize| {
+            self.grapheme_slice(index..index.saturating_add(1))
+                .bytes()
+                .next()
+                .map(is_alpha)
+        };
+        match (is_word(pos), is_word(pos.saturating_add(1))) {
+            (Some(_), None) => true,
+            (Some(current), Some(next)) => current != next,
+            (None, _) => false,
+        }
+    }
+
     pub fn end_of_current_word_spillover(&self) -> Option<usize> {
         if self.is_empty() {
             return None;
@@ -443,69 +576,21 @@ impl Line {
     }
 
     pub fn from(line_str: &str) -> Self {
-        let line = line_str
-            .graphemes(true)
-            .map(|grapheme| {
-                let line_width = grapheme.width();
-                let grapheme_width = match line_width {
-                    0 | 1 => GraphemeWidth::Half,
-                    _ => GraphemeWidth::Full,
-                };
-                let replacement = match line_width {
-                    0 => {
-                        let trimmed = grapheme.trim();
-                        if trimmed == "\t" {
-                            Some(' ')
-                        } else {
-                            let control = trimmed
-                                .chars()
-                                .map(char::is_control)
-                                .reduce(|a, b| a | b)
-                                .expect("Error in reduction");
-                            let replace_val = if control {
-                                '|'
-                            } else if trimmed.is_empty() {
-                                '*'
-                            } else {
-                                '.'
-                            };
-                            Some(replace_val)
-                        }
-                    }
-                    _ => None,
-                };
-                TextFragment {
-                    grapheme: grapheme.to_owned(),
-                    render_width: grapheme_width,
-                    replacement_text: replacement,
-                }
-            })
-            .collect();
-
         Self {
-            string: line,
             raw_string: line_str.to_owned(),
+            fragments: OnceCell::new(),
         }
     }
 
     pub fn get_line_subset(&self, range: Range<usize>) -> Line {
-        if range.start > self.grapheme_len() {
+        if range.start >= self.len() {
             return Line::default();
         }
-        let end = std::cmp::min(range.end, self.string.len());
-        let new_line = self
-            .string
-            .get(range.start..end)
-            .expect("Out of bounds error");
-
-        Line {
-            string: new_line.to_vec(),
-            raw_string: String::new(),
-        }
+        Line::from(self.grapheme_slice(range))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.string.len() == 0
+        self.raw_string.is_empty()
     }
 }
 
@@ -513,6 +598,36 @@ impl Line {
 mod tests {
     use super::*;
 
+    #[test]
+    fn find_grapheme() {
+        let line = Line::from("a,é,b,c");
+        assert_eq!(line.find_grapheme(0, ",", true, 1), Some(1));
+        assert_eq!(line.find_grapheme(0, ",", true, 3), Some(5));
+        assert_eq!(line.find_grapheme(0, ",", true, 4), None);
+        assert_eq!(line.find_grapheme(6, ",", false, 2), Some(3));
+        assert_eq!(line.find_grapheme(1, "é", true, 1), Some(2));
+    }
+
+    #[test]
+    fn grapheme_index_of_bytes() {
+        let line = Line::from("aé😀b");
+        assert_eq!(line.grapheme_index(0), 0);
+        assert_eq!(line.grapheme_index(1), 1);
+        assert_eq!(line.grapheme_index(3), 2);
+        assert_eq!(line.grapheme_index(7), 3);
+        assert_eq!(line.grapheme_index(8), 4);
+        assert_eq!(line.byte_index(line.grapheme_index(7)), 7);
+    }
+
+    #[test]
+    fn word_end_by_grapheme() {
+        let line = Line::from("ab ü");
+        assert!(!line.is_word_end(0));
+        assert!(line.is_word_end(1));
+        assert!(line.is_word_end(3));
+        assert!(!line.is_word_end(4));
+    }
+
     #[test]
     fn alpha_helper() {
         let line = Line::from("I have a bunch: of text. variable_name too");
@@ -568,4 +683,20 @@ mod tests {
             Some(len.saturating_sub(2))
         );
     }
+
+    #[test]
+    fn wrap_rows() {
+        let line = Line::from("ab界cd");
+        assert_eq!(line.columns(0..5), 6);
+        // the wide char does not fit after ab on a row of 3
+        assert_eq!(line.row_starts(3), vec![0, 2, 4]);
+        assert_eq!(line.row_starts(4), vec![0, 3]);
+        assert_eq!(line.row_starts(80), vec![0]);
+        assert_eq!(Line::from("").row_starts(3), vec![0]);
+
+        assert_eq!(line.index_at_column(0..3, 3), 2);
+        assert_eq!(line.index_at_column(0..3, 2), 2);
+        assert_eq!(line.index_at_column(0..3, 1), 1);
+        assert_eq!(line.index_at_column(3..5, 9), 4);
+    }
 }
@@ -0,0 +1,825 @@
+use super::buffer::Buffer;
+use crate::editor::terminal::Position;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// a motion an operator can be combined with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    NextWordStart,
+    WordEnd,
+    ChangeWord, // cw, like e but a cursor on the end of a word keeps to that word
+    WordStart,
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    CurrentLine,  // the operator typed twice, dd yy cc
+    MatchingPair, // %, both brackets go with the text between them
+    ParagraphForward,
+    ParagraphBack,
+    SentenceForward,
+    SentenceBack,
+}
+
+impl TryFrom<char> for Motion {
+    type Error = String;
+    fn try_from(val: char) -> Result<Self, Self::Error> {
+        match val {
+            'h' => Ok(Self::Left),
+            'l' => Ok(Self::Right),
+            'k' => Ok(Self::Up),
+            'j' => Ok(Self::Down),
+            'w' => Ok(Self::NextWordStart),
+            'e' => Ok(Self::WordEnd),
+            'b' => Ok(Self::WordStart),
+            '0' => Ok(Self::LineStart),
+            '$' => Ok(Self::LineEnd),
+            'G' => Ok(Self::LastLine),
+            '%' => Ok(Self::MatchingPair),
+            '}' => Ok(Self::ParagraphForward),
+            '{' => Ok(Self::ParagraphBack),
+            ')' => Ok(Self::SentenceForward),
+            '(' => Ok(Self::SentenceBack),
+            _ => Err(format!("{val} is not a motion")),
+        }
+    }
+}
+
+/// the part of the buffer an operator acts on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextRegion {
+    Chars { start: Position, end: Position }, // end is exclusive
+    Lines { first: usize, last: usize },
+}
+
+impl Motion {
+    /// the region covered by moving from the cursor
+    /// `count` is only set when one was typed, G and gg use it as a line number
+    pub fn region(self, cursor: &Position, count: Option<usize>, buffer: &Buffer) -> TextRegion {
+        let times = count.unwrap_or(1);
+        let last_line = buffer.len().saturating_sub(1);
+        match self {
+            Self::Left => TextRegion::Chars {
+                start: at(cursor.height, cursor.width.saturating_sub(times)),
+                end: *cursor,
+            },
+            Self::Right => TextRegion::Chars {
+                start: *cursor,
+                end: at(
+                    cursor.height,
+                    std::cmp::min(
+                        cursor.width.saturating_add(times),
+                        buffer.line(cursor.height).len(),
+                    ),
+                ),
+            },
+            Self::Up => TextRegion::Lines {
+                first: cursor.height.saturating_sub(times),
+                last: cursor.height,
+            },
+            Self::Down => TextRegion::Lines {
+                first: cursor.height,
+                last: std::cmp::min(cursor.height.saturating_add(times), last_line),
+            },
+            Self::CurrentLine => TextRegion::Lines {
+                first: cursor.height,
+                last: std::cmp::min(
+                    cursor.height.saturating_add(times.saturating_sub(1)),
+                    last_line,
+                ),
+            },
+            Self::NextWordStart => {
+                let target = Self::repeat(cursor, times, buffer, Buffer::begining_of_next_word);
+                // a word motion that wraps stops at the end of the line it left
+                let end = if target.height > cursor.height {
+                    let height = target.height.saturating_sub(1);
+                    at(height, buffer.line(height).len())
+                } else {
+                    target
+                };
+                Self::chars(*cursor, end)
+            }
+            Self::WordEnd => {
+                let target = Self::repeat(cursor, times, buffer, Buffer::end_of_current_word);
+                // inclusive, the last char of the word goes too
+                Self::chars(*cursor, at(target.height, target.width.saturating_add(1)))
+            }
+            Self::ChangeWord => {
+                let times = if buffer.line(cursor.height).is_word_end(cursor.width) {
+                    times.saturating_sub(1)
+                } else {
+                    times
+                };
+                let target = Self::repeat(cursor, times, buffer, Buffer::end_of_current_word);
+                Self::chars(*cursor, at(target.height, target.width.saturating_add(1)))
+            }
+            Self::WordStart => {
+                let target = Self::repeat(cursor, times, buffer, Buffer::begining_of_current_word);
+                Self::chars(target, *cursor)
+            }
+            Self::LineStart => Self::chars(at(cursor.height, 0), *cursor),
+            Self::LineEnd => {
+                let height = std::cmp::min(
+                    cursor.height.saturating_add(times.saturating_sub(1)),
+                    last_line,
+                );
+                Self::chars(*cursor, at(height, buffer.line(height).len()))
+            }
+            Self::MatchingPair => Self::pair(cursor, buffer),
+            Self::ParagraphForward | Self::SentenceForward => {
+                let motion = if self == Self::ParagraphForward {
+                    Buffer::find_next_paragraph
+                } else {
+                    Buffer::find_next_sentence
+                };
+                Self::exclusive(cursor, Self::repeat(cursor, times, buffer, motion), buffer)
+            }
+            Self::ParagraphBack | Self::SentenceBack => {
+                let motion = if self == Self::ParagraphBack {
+                    Buffer::find_prev_paragraph
+                } else {
+                    Buffer::find_prev_sentence
+                };
+                Self::chars(Self::repeat(cursor, times, buffer, motion), *cursor)
+            }
+            Self::FirstLine | Self::LastLine => {
+                let default = if self == Self::FirstLine {
+                    0
+                } else {
+                    last_line
+                };
+                let line = count.map_or(default, |line| {
+                    std::cmp::min(line.saturating_sub(1), last_line)
+                });
+                TextRegion::Lines {
+                    first: std::cmp::min(line, cursor.height),
+                    last: std::cmp::max(line, cursor.height),
+                }
+            }
+        }
+    }
+
+    fn repeat<F>(from: &Position, times: usize, buffer: &Buffer, motion: F) -> Position
+    where
+        F: Fn(&Buffer, &mut Position),
+    {
+        let mut target = *from;
+        for _ in 0..times {
+            let before = target;
+            motion(buffer, &mut target);
+            if before == target {
+                break;
+            }
+        }
+        target
+    }
+
+    // both brackets are taken, no bracket to jump to leaves an empty region
+    fn pair(cursor: &Position, buffer: &Buffer) -> TextRegion {
+        let target = buffer.bracket_jump(cursor).unwrap_or(*cursor);
+        match Self::chars(*cursor, target) {
+            TextRegion::Chars { start, end } if start != end => TextRegion::Chars {
+                start,
+                end: at(end.height, end.width.saturating_add(1)),
+            },
+            region => region,
+        }
+    }
+
+    // a motion that ends at the start of a later line stops at the end of the line before
+    // and takes whole lines when it starts at, or before, the first non blank of its line
+    fn exclusive(cursor: &Position, target: Position, buffer: &Buffer) -> TextRegion {
+        if target.width > 0 || target.height <= cursor.height {
+            return Self::chars(*cursor, target);
+        }
+        let last = target.height.saturating_sub(1);
+        if cursor.width <= buffer.first_non_blank(cursor.height) {
+            TextRegion::Lines {
+                first: cursor.height,
+                last,
+            }
+        } else {
+            TextRegion::Chars {
+                start: *cursor,
+                end: at(last, buffer.line(last).len()),
+            }
+        }
+    }
+
+    // order the ends of a char region
+    fn chars(a: Position, b: Position) -> TextRegion {
+        if (b.height, b.width) < (a.height, a.width) {
+            TextRegion::Chars { start: b, end: a }
+        } else {
+            TextRegion::Chars { start: a, end: b }
+        }
+    }
+}
+
+/// which of f F t T starts a char search, the char is read after the key
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FindKind {
+    pub forward: bool,
+    pub till: bool,
+}
+
+impl TryFrom<char> for FindKind {
+    type Error = String;
+    fn try_from(val: char) -> Result<Self, Self::Error> {
+        let (forward, till) = match val {
+            'f' => (true, false),
+            'F' => (false, false),
+            't' => (true, true),
+            'T' => (false, true),
+            _ => return Err(format!("{val} does not start a char search")),
+        };
+        Ok(Self { forward, till })
+    }
+}
+
+impl FindKind {
+    pub fn find(self, target: char) -> CharFind {
+        CharFind {
+            target,
+            forward: self.forward,
+            till: self.till,
+        }
+    }
+}
+
+/// f F t T, a search for a char on the cursor line
+/// a till search stops one short of the char
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CharFind {
+    pub target: char,
+    pub forward: bool,
+    pub till: bool,
+}
+
+impl CharFind {
+    /// the search started by one of f F t T
+    pub fn new(key: char, target: char) -> Option<Self> {
+        FindKind::try_from(key).ok().map(|kind| kind.find(target))
+    }
+
+    /// the same search the other way, for ,
+    pub fn reversed(self) -> Self {
+        Self {
+            forward: !self.forward,
+            ..self
+        }
+    }
+
+    /// the column the search lands on, None when the char is not found
+    /// `repeat` is set for ; and , so a till search moves past the char it stopped at
+    pub fn column(
+        self,
+        cursor: &Position,
+        times: usize,
+        buffer: &Buffer,
+        repeat: bool,
+    ) -> Option<usize> {
+        let from = match (self.till && repeat, self.forward) {
+            (true, true) => cursor.width.saturating_add(1),
+            (true, false) => cursor.width.saturating_sub(1),
+            (false, _) => cursor.width,
+        };
+        let found = buffer.line(cursor.height).find_grapheme(
+            from,
+            &self.target.to_string(),
+            self.forward,
+            times,
+        )?;
+        Some(match (self.till, self.forward) {
+            (true, true) => found.saturating_sub(1),
+            (true, false) => found.saturating_add(1),
+            (false, _) => found,
+        })
+    }
+
+    /// the region an operator covers, forward searches include the char landed on
+    pub fn region(self, cursor: &Position, times: usize, buffer: &Buffer) -> Option<TextRegion> {
+        let column = self.column(cursor, times, buffer, false)?;
+        Some(if self.forward {
+            TextRegion::Chars {
+                start: *cursor,
+                end: at(cursor.height, column.saturating_add(1)),
+            }
+        } else {
+            TextRegion::Chars {
+                start: at(cursor.height, column),
+                end: *cursor,
+            }
+        })
+    }
+}
+
+/// the thing a text object selects
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextObjectKind {
+    Word,
+    BigWord,
+    Quote(char),
+    Pair(char, char),
+    Paragraph,
+}
+
+impl TryFrom<char> for TextObjectKind {
+    type Error = String;
+    fn try_from(val: char) -> Result<Self, Self::Error> {
+        match val {
+            'w' => Ok(Self::Word),
+            'W' => Ok(Self::BigWord),
+            '"' | '\'' | '`' => Ok(Self::Quote(val)),
+            '(' | ')' | 'b' => Ok(Self::Pair('(', ')')),
+            '[' | ']' => Ok(Self::Pair('[', ']')),
+            '{' | '}' | 'B' => Ok(Self::Pair('{', '}')),
+            '<' | '>' => Ok(Self::Pair('<', '>')),
+            'p' => Ok(Self::Paragraph),
+            _ => Err(format!("{val} is not a text object")),
+        }
+    }
+}
+
+/// a text object, `inner` selects only the contents, otherwise the surrounding
+/// white space or delimiters are included
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextObject {
+    pub kind: TextObjectKind,
+    pub inner: bool,
+}
+
+impl TextObject {
+    /// the region of the object around the cursor, None when there is none
+    pub fn region(self, cursor: &Position, buffer: &Buffer) -> Option<TextRegion> {
+        if buffer.is_empty() {
+            return None;
+        }
+        match self.kind {
+            TextObjectKind::Word => self.word_region(cursor, buffer, false),
+            TextObjectKind::BigWord => self.word_region(cursor, buffer, true),
+            TextObjectKind::Quote(quote) => self.quote_region(cursor, buffer, quote),
+            TextObjectKind::Pair(open, close) => self.pair_region(cursor, buffer, open, close),
+            TextObjectKind::Paragraph => Some(self.paragraph_region(cursor, buffer)),
+        }
+    }
+
+    fn word_region(self, cursor: &Position, buffer: &Buffer, big: bool) -> Option<TextRegion> {
+        // 0 is white space, words and runs of punctuation are their own classes
+        let class = |grapheme: &str| -> u8 {
+            if grapheme.trim().is_empty() {
+                0
+            } else if big || grapheme.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                1
+            } else {
+                2
+            }
+        };
+        let line = buffer.line(cursor.height);
+        let classes: Vec<u8> = line.raw_string.graphemes(true).map(class).collect();
+        if classes.is_empty() {
+            return None;
+        }
+        let width = std::cmp::min(cursor.width, classes.len().saturating_sub(1));
+        let (mut start, mut end) = Self::run_around(&classes, width);
+        if !self.inner {
+            if classes[width] == 0 {
+                // white space takes the word after it
+                if end < classes.len() {
+                    end = Self::run_around(&classes, end).1;
+                }
+            } else if end < classes.len() && classes[end] == 0 {
+                end = Self::run_around(&classes, end).1;
+            } else if start > 0 && classes[start.saturating_sub(1)] == 0 {
+                // no trailing white space, take the leading instead
+                start = Self::run_around(&classes, start.saturating_sub(1)).0;
+            }
+        }
+        Some(TextRegion::Chars {
+            start: at(cursor.height, start),
+            end: at(cursor.height, end),
+        })
+    }
+
+    // the run of equal classes containing an index, end exclusive
+    fn run_around(classes: &[u8], index: usize) -> (usize, usize) {
+        let class = classes[index];
+        let mut start = index;
+        while start > 0 && classes[start.saturating_sub(1)] == class {
+            start = start.saturating_sub(1);
+        }
+        let mut end = index;
+        while end < classes.len() && classes[end] == class {
+            end = end.saturating_add(1);
+        }
+        (start, end)
+    }
+
+    fn quote_region(self, cursor: &Position, buffer: &Buffer, quote: char) -> Option<TextRegion> {
+        let line = buffer.line(cursor.height);
+        let graphemes: Vec<&str> = line.raw_string.graphemes(true).collect();
+        let quote = quote.to_string();
+        // quotes escaped with a backslash do not count
+        let quotes: Vec<usize> = (0..graphemes.len())
+            .filter(|i| {
+                graphemes[*i] == quote && (*i == 0 || graphemes[i.saturating_sub(1)] != "\\")
+            })
+            .collect();
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            // the pair around the cursor, otherwise the next one on the line
+            .find(|(_, close)| cursor.width <= *close)?;
+        let (start, end) = if self.inner {
+            (open.saturating_add(1), close)
+        } else {
+            let mut end = close.saturating_add(1);
+            while end < graphemes.len() && graphemes[end].trim().is_empty() {
+                end = end.saturating_add(1);
+            }
+            (open, end)
+        };
+        Some(TextRegion::Chars {
+            start: at(cursor.height, start),
+            end: at(cursor.height, end),
+        })
+    }
+
+    fn pair_region(
+        self,
+        cursor: &Position,
+        buffer: &Buffer,
+        open: char,
+        close: char,
+    ) -> Option<TextRegion> {
+        let (open, close) = (open.to_string(), close.to_string());
+        let line = buffer.line(cursor.height);
+        let under_cursor = line.grapheme_slice(cursor.width..cursor.width.saturating_add(1));
+        let open_pos = if under_cursor == open {
+            *cursor
+        } else {
+            buffer.find_unmatched(cursor, &open, &close, false)?
+        };
+        let close_pos = buffer.find_unmatched(&open_pos, &open, &close, true)?;
+        if !self.inner {
+            return Some(TextRegion::Chars {
+                start: open_pos,
+                end: at(close_pos.height, close_pos.width.saturating_add(1)),
+            });
+        }
+        // a block whose brackets sit on their own lines selects the lines between them
+        let open_ends_line = open_pos.width.saturating_add(1) == buffer.line(open_pos.height).len();
+        let close_starts_line = buffer.first_non_blank(close_pos.height) == close_pos.width;
+        if open_ends_line && close_starts_line && close_pos.height > open_pos.height {
+            if close_pos.height.saturating_sub(open_pos.height) < 2 {
+                let inside = at(close_pos.height, close_pos.width);
+                return Some(TextRegion::Chars {
+                    start: inside,
+                    end: inside,
+                });
+            }
+            return Some(TextRegion::Lines {
+                first: open_pos.height.saturating_add(1),
+                last: close_pos.height.saturating_sub(1),
+            });
+        }
+        Some(TextRegion::Chars {
+            start: at(open_pos.height, open_pos.width.saturating_add(1)),
+            end: at(close_pos.height, close_pos.width),
+        })
+    }
+
+    fn paragraph_region(self, cursor: &Position, buffer: &Buffer) -> TextRegion {
+        let last_line = buffer.len().saturating_sub(1);
+        let is_blank = |line: usize| buffer.is_blank_line(line);
+        let run = |line: usize| {
+            let blank = is_blank(line);
+            let mut first = line;
+            while first > 0 && is_blank(first.saturating_sub(1)) == blank {
+                first = first.saturating_sub(1);
+            }
+            let mut last = line;
+            while last < last_line && is_blank(last.saturating_add(1)) == blank {
+                last = last.saturating_add(1);
+            }
+            (first, last)
+        };
+        let (mut first, mut last) = run(cursor.height);
+        if !self.inner {
+            if last < last_line {
+                last = run(last.saturating_add(1)).1;
+            } else if first > 0 && !is_blank(cursor.height) {
+                // nothing after the paragraph, take the blank lines before it
+                first = run(first.saturating_sub(1)).0;
+            }
+        }
+        TextRegion::Lines { first, last }
+    }
+}
+
+fn at(height: usize, width: usize) -> Position {
+    Position {
+        height,
+        width,
+        max_width: usize::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(&Position::default(), text);
+        buffer
+    }
+
+    fn pos(height: usize, width: usize) -> Position {
+        Position {
+            height,
+            width,
+            max_width: usize::default(),
+        }
+    }
+
+    #[test]
+    fn word_regions() {
+        let buffer = buffer("one two three\nfour");
+        assert_eq!(
+            Motion::NextWordStart.region(&pos(0, 0), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(0, 4)
+            }
+        );
+        assert_eq!(
+            Motion::WordEnd.region(&pos(0, 0), Some(2), &buffer),
+            TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(0, 7)
+            }
+        );
+        assert_eq!(
+            Motion::WordStart.region(&pos(0, 6), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 4),
+                end: pos(0, 6)
+            }
+        );
+        // the last word on a line stops at the line end
+        assert_eq!(
+            Motion::NextWordStart.region(&pos(0, 8), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 8),
+                end: pos(0, 13)
+            }
+        );
+        // cw on the last char of a word only takes that char
+        assert_eq!(
+            Motion::ChangeWord.region(&pos(0, 2), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 2),
+                end: pos(0, 3)
+            }
+        );
+        assert_eq!(
+            Motion::ChangeWord.region(&pos(0, 1), Some(2), &buffer),
+            TextRegion::Chars {
+                start: pos(0, 1),
+                end: pos(0, 7)
+            }
+        );
+    }
+
+    #[test]
+    fn line_regions() {
+        let buffer = buffer("a\nb\nc\nd");
+        assert_eq!(
+            Motion::CurrentLine.region(&pos(1, 0), Some(2), &buffer),
+            TextRegion::Lines { first: 1, last: 2 }
+        );
+        assert_eq!(
+            Motion::LastLine.region(&pos(1, 0), None, &buffer),
+            TextRegion::Lines { first: 1, last: 3 }
+        );
+        assert_eq!(
+            Motion::FirstLine.region(&pos(2, 0), None, &buffer),
+            TextRegion::Lines { first: 0, last: 2 }
+        );
+        assert_eq!(
+            Motion::LineEnd.region(&pos(0, 0), Some(2), &buffer),
+            TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(1, 1)
+            }
+        );
+    }
+
+    fn object(kind: TextObjectKind, inner: bool) -> TextObject {
+        TextObject { kind, inner }
+    }
+
+    #[test]
+    fn word_objects() {
+        let buffer = buffer("let foo_bar = baz.qux;");
+        assert_eq!(
+            object(TextObjectKind::Word, true).region(&pos(0, 5), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 4),
+                end: pos(0, 11)
+            })
+        );
+        assert_eq!(
+            object(TextObjectKind::Word, false).region(&pos(0, 5), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 4),
+                end: pos(0, 12)
+            })
+        );
+        assert_eq!(
+            object(TextObjectKind::BigWord, true).region(&pos(0, 15), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 14),
+                end: pos(0, 22)
+            })
+        );
+        // the last word takes the white space before it
+        assert_eq!(
+            object(TextObjectKind::BigWord, false).region(&pos(0, 15), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 13),
+                end: pos(0, 22)
+            })
+        );
+    }
+
+    #[test]
+    fn quote_objects() {
+        let buffer = buffer(r#"say("a \"b\"", 'c')"#);
+        assert_eq!(
+            object(TextObjectKind::Quote('"'), true).region(&pos(0, 6), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 5),
+                end: pos(0, 12)
+            })
+        );
+        // from before the quotes the next pair on the line is used
+        assert_eq!(
+            object(TextObjectKind::Quote('\''), false).region(&pos(0, 0), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 15),
+                end: pos(0, 18)
+            })
+        );
+    }
+
+    #[test]
+    fn pair_objects() {
+        let buffer = buffer("f(a, (b), c)\nfn x() {\n    body\n}");
+        assert_eq!(
+            object(TextObjectKind::Pair('(', ')'), false).region(&pos(0, 3), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 1),
+                end: pos(0, 12)
+            })
+        );
+        assert_eq!(
+            object(TextObjectKind::Pair('(', ')'), true).region(&pos(0, 6), &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 6),
+                end: pos(0, 7)
+            })
+        );
+        // a block on its own lines selects the lines inside
+        assert_eq!(
+            object(TextObjectKind::Pair('{', '}'), true).region(&pos(2, 5), &buffer),
+            Some(TextRegion::Lines { first: 2, last: 2 })
+        );
+        assert_eq!(
+            object(TextObjectKind::Pair('[', ']'), true).region(&pos(0, 3), &buffer),
+            None
+        );
+    }
+
+    #[test]
+    fn paragraph_objects() {
+        let buffer = buffer("a\nb\n\n\nc\nd");
+        assert_eq!(
+            object(TextObjectKind::Paragraph, true).region(&pos(1, 0), &buffer),
+            Some(TextRegion::Lines { first: 0, last: 1 })
+        );
+        assert_eq!(
+            object(TextObjectKind::Paragraph, false).region(&pos(1, 0), &buffer),
+            Some(TextRegion::Lines { first: 0, last: 3 })
+        );
+        assert_eq!(
+            object(TextObjectKind::Paragraph, false).region(&pos(5, 0), &buffer),
+            Some(TextRegion::Lines { first: 2, last: 5 })
+        );
+    }
+
+    #[test]
+    fn char_find() {
+        let buffer = buffer("call(a, b) x");
+        let find = |key, target| CharFind::new(key, target).unwrap();
+        assert_eq!(
+            find('f', ',').column(&pos(0, 0), 1, &buffer, false),
+            Some(6)
+        );
+        assert_eq!(
+            find('t', ')').column(&pos(0, 0), 1, &buffer, false),
+            Some(8)
+        );
+        assert_eq!(
+            find('F', '(').column(&pos(0, 8), 1, &buffer, false),
+            Some(4)
+        );
+        assert_eq!(
+            find('T', '(').column(&pos(0, 8), 1, &buffer, false),
+            Some(5)
+        );
+        assert_eq!(find('f', 'z').column(&pos(0, 0), 1, &buffer, false), None);
+        assert!(FindKind::try_from('d').is_err());
+        // a repeated till moves on from the char it stopped in front of
+        assert_eq!(find('t', ' ').column(&pos(0, 6), 1, &buffer, true), Some(9));
+        assert_eq!(
+            find('t', ')').region(&pos(0, 5), 1, &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 5),
+                end: pos(0, 9)
+            })
+        );
+        assert_eq!(
+            find('F', 'c').region(&pos(0, 4), 1, &buffer),
+            Some(TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(0, 4)
+            })
+        );
+    }
+
+    #[test]
+    fn matching_pair() {
+        let buffer = buffer(
+            "x = f(a[0]);
+{
+}",
+        );
+        assert_eq!(
+            Motion::MatchingPair.region(&pos(0, 0), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(0, 11)
+            }
+        );
+        assert_eq!(
+            Motion::MatchingPair.region(&pos(2, 0), None, &buffer),
+            TextRegion::Chars {
+                start: pos(1, 0),
+                end: pos(2, 1)
+            }
+        );
+        assert_eq!(
+            Motion::MatchingPair.region(&pos(0, 11), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 11),
+                end: pos(0, 11)
+            }
+        );
+    }
+
+    #[test]
+    fn paragraph_and_sentence_regions() {
+        let buffer = buffer("a b. c\nd\n\ne");
+        assert_eq!(
+            Motion::ParagraphForward.region(&pos(0, 0), None, &buffer),
+            TextRegion::Lines { first: 0, last: 1 }
+        );
+        assert_eq!(
+            Motion::ParagraphForward.region(&pos(0, 2), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 2),
+                end: pos(1, 1)
+            }
+        );
+        assert_eq!(
+            Motion::SentenceForward.region(&pos(0, 0), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 0),
+                end: pos(0, 5)
+            }
+        );
+        assert_eq!(
+            Motion::SentenceBack.region(&pos(1, 0), None, &buffer),
+            TextRegion::Chars {
+                start: pos(0, 5),
+                end: pos(1, 0)
+            }
+        );
+    }
+}
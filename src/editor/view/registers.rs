@@ -0,0 +1,306 @@
+use super::clipboard_interface::ClipboardUtils;
+use super::search_history::SearchHistory;
+use crossterm::event::Event;
+use std::collections::HashMap;
+
+// number of numbered delete registers, "1 to "9
+const NUMBERED_REGISTERS: usize = 9;
+
+/// whether a register holds part of a line, whole lines or a block
+/// decides where a put places the text
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum RegisterKind {
+    #[default]
+    Chars,
+    Lines,
+    Block, // one row per line, put at the same column on each line
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Register {
+    pub text: String, // whole lines are held without a trailing new line
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn new(text: String, kind: RegisterKind) -> Self {
+        Self { text, kind }
+    }
+}
+
+/// the vim registers, kept in the editor so they outlive a vim mode session
+/// only "+ and "* go through the system clipboard
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Register,
+    yanked: Register,                  // "0
+    numbered: Vec<Register>,           // "1 to "9, newest first
+    small_delete: Register,            // "-
+    named: HashMap<char, Register>,    // "a to "z
+    macros: HashMap<char, Vec<Event>>, // recorded with q, run with @
+    last_macro: Option<char>,
+    search: SearchHistory, // "/ is the last search
+}
+
+impl Registers {
+    /// whether a char names a register that can be used after "
+    pub fn is_valid_name(name: char) -> bool {
+        name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | '+' | '*' | '_' | '/')
+    }
+
+    /// whether text can be stored in a register, "/ only changes by searching
+    pub fn writable(name: Option<char>) -> Result<(), String> {
+        match name {
+            Some('/') => Err(String::from("the / register is read only")),
+            Some(name) if !Self::is_valid_name(name) => Err(format!("no register named {name}")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn search_history(&mut self) -> &mut SearchHistory {
+        &mut self.search
+    }
+
+    /// store yanked text, without a name it goes to "0
+    pub fn yank(&mut self, name: Option<char>, register: Register) -> Result<(), String> {
+        match name {
+            None | Some('"') => {
+                self.yanked = register.clone();
+                self.unnamed = register;
+                Ok(())
+            }
+            Some(name) => self.write_named(name, register),
+        }
+    }
+
+    /// store deleted text, without a name whole lines shift through "1 to "9
+    /// and smaller deletes go to "-
+    pub fn delete(&mut self, name: Option<char>, register: Register) -> Result<(), String> {
+        match name {
+            None | Some('"') => {
+                if register.kind == RegisterKind::Lines || register.text.contains('\n') {
+                    self.numbered.insert(0, register.clone());
+                    self.numbered.truncate(NUMBERED_REGISTERS);
+                } else {
+                    self.small_delete = register.clone();
+                }
+                self.unnamed = register;
+                Ok(())
+            }
+            Some(name) => self.write_named(name, register),
+        }
+    }
+
+    fn write_named(&mut self, name: char, register: Register) -> Result<(), String> {
+        Self::writable(Some(name))?;
+        match name {
+            '+' | '*' => {
+                let mut text = register.text.clone();
+                if register.kind == RegisterKind::Lines {
+                    text.push('\n');
+                }
+                // the text stays in "" for p when there is no clipboard to put it in
+                self.unnamed = register;
+                ClipboardUtils::copy_text_to_clipboard(text)
+                    .map_err(|err| format!("clipboard not available: {err}"))?;
+            }
+            'A'..='Z' => {
+                // upper case appends to the lower case register
+                let entry = self.named.entry(name.to_ascii_lowercase()).or_default();
+                if entry.kind == RegisterKind::Lines || register.kind == RegisterKind::Lines {
+                    if !entry.text.is_empty() {
+                        entry.text.push('\n');
+                    }
+                    entry.kind = RegisterKind::Lines;
+                }
+                entry.text.push_str(&register.text);
+                self.unnamed = entry.clone();
+            }
+            'a'..='z' => {
+                self.named.insert(name, register.clone());
+                self.unnamed = register;
+            }
+            '0' => {
+                self.yanked = register.clone();
+                self.unnamed = register;
+            }
+            '-' => {
+                self.small_delete = register.clone();
+                self.unnamed = register;
+            }
+            '1'..='9' => {
+                let index = name
+                    .to_digit(10)
+                    .and_then(|digit| usize::try_from(digit).ok())
+                    .unwrap_or_default()
+                    .saturating_sub(1);
+                if self.numbered.len() <= index {
+                    self.numbered
+                        .resize_with(index.saturating_add(1), Register::default);
+                }
+                self.numbered[index] = register.clone();
+                self.unnamed = register;
+            }
+            _ => {} // "_ is the black hole register
+        }
+        Ok(())
+    }
+
+    /// store the keys recorded for a macro, an upper case name appends
+    pub fn record_macro(&mut self, name: char, events: Vec<Event>) {
+        let keys = self.macros.entry(name.to_ascii_lowercase()).or_default();
+        if !name.is_ascii_uppercase() {
+            keys.clear();
+        }
+        keys.extend(events);
+    }
+
+    /// the keys of a macro, @ is the last macro run
+    pub fn macro_keys(&mut self, name: char) -> Option<Vec<Event>> {
+        let name = if name == '@' {
+            self.last_macro?
+        } else {
+            name.to_ascii_lowercase()
+        };
+        let keys = self.macros.get(&name)?.clone();
+        self.last_macro = Some(name);
+        Some(keys)
+    }
+
+    /// the contents of a register, None when it is empty
+    pub fn get(&self, name: Option<char>) -> Option<Register> {
+        let register = match name {
+            None | Some('"') => self.unnamed.clone(),
+            Some('0') => self.yanked.clone(),
+            Some('-') => self.small_delete.clone(),
+            Some('/') => Register::new(self.search.last()?.query.clone(), RegisterKind::Chars),
+            Some(digit @ '1'..='9') => {
+                let index = digit.to_digit(10).and_then(|d| usize::try_from(d).ok())?;
+                self.numbered.get(index.saturating_sub(1))?.clone()
+            }
+            Some('+' | '*') => {
+                let text = ClipboardUtils::get_text_from_clipboard().ok()?;
+                // text copied with a trailing new line is put back as whole lines
+                match text.strip_suffix('\n') {
+                    Some(lines) => Register::new(lines.to_string(), RegisterKind::Lines),
+                    None => Register::new(text, RegisterKind::Chars),
+                }
+            }
+            Some(name) => self.named.get(&name.to_ascii_lowercase())?.clone(),
+        };
+        if register.text.is_empty() && register.kind == RegisterKind::Chars {
+            None
+        } else {
+            Some(register)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    #[test]
+    fn yank_and_named_registers() {
+        let mut registers = Registers::default();
+        registers
+            .yank(None, Register::new("one".into(), RegisterKind::Chars))
+            .unwrap();
+        registers
+            .yank(Some('a'), Register::new("two".into(), RegisterKind::Lines))
+            .unwrap();
+        registers
+            .yank(
+                Some('A'),
+                Register::new("three".into(), RegisterKind::Chars),
+            )
+            .unwrap();
+
+        assert_eq!(registers.get(Some('0')).unwrap().text, "one");
+        let a = registers.get(Some('a')).unwrap();
+        assert_eq!(a.text, "two\nthree");
+        assert_eq!(a.kind, RegisterKind::Lines);
+        assert_eq!(registers.get(None), Some(a));
+        assert!(registers.get(Some('b')).is_none());
+    }
+
+    #[test]
+    fn deletes_shift_numbered_registers() {
+        let mut registers = Registers::default();
+        registers
+            .delete(None, Register::new("first".into(), RegisterKind::Lines))
+            .unwrap();
+        registers
+            .delete(None, Register::new("second".into(), RegisterKind::Lines))
+            .unwrap();
+        registers
+            .delete(None, Register::new("word".into(), RegisterKind::Chars))
+            .unwrap();
+        registers
+            .delete(Some('_'), Register::new("gone".into(), RegisterKind::Lines))
+            .unwrap();
+
+        assert_eq!(registers.get(Some('1')).unwrap().text, "second");
+        assert_eq!(registers.get(Some('2')).unwrap().text, "first");
+        assert_eq!(registers.get(Some('-')).unwrap().text, "word");
+        assert_eq!(registers.get(None).unwrap().text, "word");
+    }
+
+    #[test]
+    fn numbered_and_read_only_registers() {
+        let mut registers = Registers::default();
+        registers
+            .yank(None, Register::new("old".into(), RegisterKind::Chars))
+            .unwrap();
+        registers
+            .yank(Some('0'), Register::new("line".into(), RegisterKind::Lines))
+            .unwrap();
+        assert_eq!(registers.get(Some('0')).unwrap().text, "line");
+        assert_eq!(registers.get(None).unwrap().text, "line");
+
+        registers
+            .delete(
+                Some('3'),
+                Register::new("third".into(), RegisterKind::Lines),
+            )
+            .unwrap();
+        registers
+            .delete(Some('-'), Register::new("dash".into(), RegisterKind::Chars))
+            .unwrap();
+        assert_eq!(registers.get(Some('3')).unwrap().text, "third");
+        assert!(registers.get(Some('1')).is_none());
+        assert_eq!(registers.get(Some('-')).unwrap().text, "dash");
+
+        let search = Register::new("query".into(), RegisterKind::Chars);
+        assert!(registers.yank(Some('/'), search).is_err());
+        assert_eq!(registers.get(None).unwrap().text, "dash");
+    }
+
+    #[test]
+    fn macros_append_and_repeat() {
+        let key = |c| Event::Key(KeyEvent::from(KeyCode::Char(c)));
+        let mut registers = Registers::default();
+        assert!(registers.macro_keys('@').is_none());
+        registers.record_macro('a', vec![key('x')]);
+        registers.record_macro('A', vec![key('j')]);
+        registers.record_macro('b', vec![key('k')]);
+
+        assert_eq!(registers.macro_keys('a'), Some(vec![key('x'), key('j')]));
+        assert_eq!(registers.macro_keys('@'), Some(vec![key('x'), key('j')]));
+        registers.record_macro('a', vec![key('w')]);
+        assert_eq!(registers.macro_keys('@'), Some(vec![key('w')]));
+        assert!(registers.macro_keys('c').is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn clipboard_failure_is_reported() {
+        // without a display there is no clipboard to write to
+        std::env::remove_var("DISPLAY");
+        let mut registers = Registers::default();
+        let yanked = Register::new("text".into(), RegisterKind::Chars);
+        assert!(registers.yank(Some('+'), yanked).is_err());
+        assert_eq!(registers.get(None).unwrap().text, "text");
+    }
+}
@@ -0,0 +1,126 @@
+use super::motion::{CharFind, Motion, TextObject};
+use crate::editor::editorcommands::{InsertEntry, Operator};
+use crate::editor::terminal::Position;
+
+/// what an operator was applied to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OperatorTarget {
+    Motion(Motion),
+    TextObject(TextObject),
+    Find(CharFind),
+}
+
+/// a vim command that changed the buffer and can be run again with .
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Repeatable {
+    Operator {
+        operator: Operator,
+        target: OperatorTarget,
+        count: Option<usize>,
+        register: Option<char>,
+    },
+    Put {
+        register: Option<char>,
+        before: bool,
+        times: usize,
+    },
+    Insert(InsertEntry), // leaving vim mode to type text
+    Shift {
+        rows: usize,
+        right: bool, // indent, otherwise outdent
+    },
+    BlockInsert {
+        at: Position, // where the top row was typed, a repeat uses the cursor
+        rows: usize,
+        pad: bool,
+    },
+    ReplaceChar {
+        with: char,
+        times: usize,
+    },
+    Overwrite,         // R, the typed text goes over the text at the cursor
+    Join(usize),       // the number of lines joined into one
+    ToggleCase(usize), // the number of chars switched
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LastChange {
+    pub command: Repeatable,
+    pub inserted: String, // text typed after the command left vim mode
+}
+
+/// the change . repeats, kept in the editor so it outlives a vim mode session
+#[derive(Default)]
+pub struct DotRepeat {
+    last: Option<LastChange>,
+    typing: Option<Repeatable>, // the command waiting on the text typed outside vim mode
+}
+
+impl DotRepeat {
+    /// remember a change that is complete once the command has run
+    pub fn set(&mut self, command: Repeatable) {
+        self.set_with_text(command, String::new());
+    }
+
+    /// remember a change made by typing without leaving vim mode
+    pub fn set_with_text(&mut self, command: Repeatable, inserted: String) {
+        self.typing = None;
+        self.last = Some(LastChange { command, inserted });
+    }
+
+    /// remember a command that leaves vim mode, the change is complete once typing ends
+    pub fn start_typing(&mut self, command: Repeatable) {
+        self.typing = Some(command);
+    }
+
+    /// attach the typed text to the command that left vim mode, returns the finished change
+    /// leaving vim mode without typing anything keeps the previous change
+    pub fn finish_typing(&mut self, inserted: String) -> Option<LastChange> {
+        let command = self.typing.take()?;
+        if inserted.is_empty()
+            && matches!(command, Repeatable::Insert(entry) if !entry.opens_line())
+        {
+            return None;
+        }
+        self.last = Some(LastChange { command, inserted });
+        self.last()
+    }
+
+    pub fn last(&self) -> Option<LastChange> {
+        self.last.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing_completes_the_change() {
+        let mut repeat = DotRepeat::default();
+        repeat.start_typing(Repeatable::Insert(InsertEntry::LineBelow));
+        repeat.finish_typing(String::new());
+        repeat.start_typing(Repeatable::Insert(InsertEntry::After));
+        repeat.finish_typing(String::new());
+        assert_eq!(
+            repeat.last().map(|change| change.command),
+            Some(Repeatable::Insert(InsertEntry::LineBelow))
+        );
+
+        let change = Repeatable::Operator {
+            operator: Operator::Change,
+            target: OperatorTarget::Motion(Motion::NextWordStart),
+            count: None,
+            register: None,
+        };
+        repeat.start_typing(change);
+        repeat.finish_typing("new".into());
+        assert_eq!(
+            repeat.last(),
+            Some(LastChange {
+                command: change,
+                inserted: "new".into(),
+            })
+        );
+    }
+}
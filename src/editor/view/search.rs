@@ -1,12 +1,21 @@
-use crate::editor::editorcommands::SearchCommand;
+use crate::editor::editorcommands::{ConfirmReplace, SearchCommand};
 use crate::editor::{
     terminal::{Coordinate, Mode, Position, ScreenOffset, ScreenPosition, Size, Terminal},
-    view::Buffer,
+    view::{
+        buffer::SearchMatch,
+        gutter,
+        keys::KeySource,
+        search_history::{CaseMode, SearchEntry, SearchHistory},
+        substitute::Substitution,
+        wrap::{self, ScreenRow},
+        Buffer,
+    },
 };
-use crossterm::event::read;
-use crossterm::style::{Attribute, Color, Print, PrintStyledContent, StyledContent, Stylize};
+use crossterm::style::{Attribute, Color, Stylize};
+use regex::Regex;
 use std::cmp::min;
 use std::collections::HashSet;
+use std::io::Error;
 
 pub struct Search {
     index: usize, // index of search positions we are currently on
@@ -14,9 +23,15 @@ pub struct Search {
     screen_offset: ScreenOffset,
     highlight: Color,
     text: Color,
-    stack: Vec<Vec<Position>>,
+    stack: Vec<Vec<SearchMatch>>,
     string: String,
     line_indicies: HashSet<usize>,
+    regex: bool, // the query is a regular expression rather than plain text
+    case: CaseMode,
+    whole_word: bool,                  // hits must start and end on a word boundary
+    error: Option<String>,             // why the query is not a valid pattern
+    recalled: Option<(usize, String)>, // the history entry shown and what was typed before it
+    replacing: bool,                   // opened with Ctrl-r, Enter asks for the replacement
 }
 
 impl Default for Search {
@@ -30,10 +45,24 @@ impl Default for Search {
             text: Color::White,
             stack: Vec::new(),
             line_indicies: HashSet::new(),
+            regex: false,
+            case: CaseMode::default(),
+            whole_word: false,
+            error: None,
+            recalled: None,
+            replacing: false,
         }
     }
 }
 
+// how a column of a line with search hits is drawn
+#[derive(Copy, Clone, PartialEq)]
+enum HitStyle {
+    Plain,
+    Match,
+    Group, // a capture group inside a match
+}
+
 enum IndexResolver {
     Left,
     Mid,
@@ -51,53 +80,82 @@ impl Search {
             text,
             stack: Vec::new(),
             line_indicies: HashSet::new(),
+            regex: false,
+            case: CaseMode::default(),
+            whole_word: false,
+            error: None,
+            recalled: None,
+            replacing: false,
         }
     }
 
+    /// a search that replaces its hits once the query is entered
+    pub fn replacing(mut self) -> Self {
+        self.replacing = true;
+        self
+    }
+
     // entry
-    // no changes to buffer -> immutable reference
+    // the buffer only changes when the hits are replaced
+    // keys come through the key source so a macro records and replays the search
+    // the search is kept in the history, a failure to save the history is handed back
     pub fn run(
         &mut self,
         prev_pos: &mut Position,
         prev_offset: &mut ScreenOffset,
         size: &mut Size,
-        buffer: &Buffer,
-    ) {
+        buffer: &mut Buffer,
+        history: &mut SearchHistory,
+        keys: &mut KeySource,
+    ) -> Result<(), Error> {
+        let mut saved = Ok(());
         loop {
             // on errors or events that dont matter in this context
             // skip and continue
             self.render(buffer, size);
-            let Ok(read_event) = read() else { continue }; //skipping errors here
-
-            match SearchCommand::try_from(read_event) {
+            let command = match SearchCommand::try_from(keys.next_event()) {
+                // in a search opened with Ctrl-r, Enter goes on to the replacement
+                Ok(SearchCommand::AssumeState) if self.replacing => Ok(SearchCommand::Replace),
+                command => command,
+            };
+            match command {
                 Ok(event) => match event {
                     SearchCommand::Insert(c) => {
                         // add char to search query
+                        self.recalled = None;
                         self.string.push(c);
-                        self.stack.push(buffer.search(&self.string));
+                        let query = self.string.clone();
+                        let matches = self.find(&query, buffer);
+                        self.stack.push(matches);
                         self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
                         self.set_line_indicies();
                     }
-                    SearchCommand::Next => {
-                        //snap to next result
-                        if !self.stack.is_empty() {
-                            let curr_results = self.stack.last().unwrap();
-                            self.index = if curr_results.len().saturating_sub(1) > self.index {
-                                self.index.saturating_add(1)
-                            } else {
-                                0
-                            };
+                    SearchCommand::Next => self.step(true),
+                    SearchCommand::Previous => self.step(false),
+                    SearchCommand::ToggleRegex
+                    | SearchCommand::ToggleCase
+                    | SearchCommand::ToggleWholeWord => {
+                        match event {
+                            SearchCommand::ToggleRegex => self.regex = !self.regex,
+                            SearchCommand::ToggleCase => self.case = self.case.next(),
+                            _ => self.whole_word = !self.whole_word,
                         }
+                        self.search_again(buffer);
+                        self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
+                        self.set_line_indicies();
                     }
-                    SearchCommand::Previous => {
-                        //snap to previous result
-                        if !self.stack.is_empty() {
-                            let curr_results = self.stack.last().unwrap();
-                            self.index = if self.index > 0 {
-                                self.index.saturating_sub(1)
-                            } else {
-                                curr_results.len().saturating_sub(1)
-                            };
+                    SearchCommand::Recall(older) => {
+                        self.recall(older, history, buffer);
+                        self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
+                        self.set_line_indicies();
+                    }
+                    SearchCommand::Replace => {
+                        if self.stack.last().is_some_and(|hits| !hits.is_empty()) {
+                            saved = history.add(self.entry(&self.string), false);
+                            self.replace(size, buffer, keys);
+                            *prev_pos = self.cursor_position;
+                            *prev_offset = self.screen_offset;
+                            break;
                         }
                     }
                     SearchCommand::RevertState => {
@@ -107,6 +165,7 @@ impl Search {
                     }
                     SearchCommand::AssumeState => {
                         //assume current state on screen after search
+                        saved = history.add(self.entry(&self.string), false);
                         *prev_pos = self.cursor_position;
                         *prev_offset = self.screen_offset;
                         break;
@@ -114,8 +173,14 @@ impl Search {
                     SearchCommand::BackSpace => {
                         // remove char from search query
                         if !self.string.is_empty() {
+                            self.recalled = None;
                             self.string.pop();
                             self.stack.pop();
+                            self.error = if self.string.is_empty() {
+                                None
+                            } else {
+                                self.pattern(&self.string).err()
+                            };
                             self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
                             self.set_line_indicies();
                         }
@@ -141,48 +206,169 @@ impl Search {
             //grab the latest search results from the stack
             //get the search index position
             //self.cursor_position = self.stack[self.stack.len() - 1][self.index].clone();
-            self.cursor_position = self.stack.last().unwrap()[self.index];
+            self.cursor_position = self.stack.last().unwrap()[self.index].start;
+
+            self.follow_cursor(size, buffer);
+        }
+        self.render(buffer, size);
+        saved
+    }
+
+    /// move the view so the cursor is on screen
+    fn follow_cursor(&mut self, size: &Size, buffer: &Buffer) {
+        // if the search position is out of current screen bounds
+        // if out width is within 0 - size
+        // snap offset left
+        // a replace can add lines enough to widen the gutter
+        self.screen_offset.fit_gutter(buffer.len());
+        if self.cursor_position.width < self.screen_offset.text_width(size) {
+            self.screen_offset.snap_left();
+        }
+        match self
+            .cursor_position
+            .max_displacement_from_view(&self.screen_offset, size, 3)
+        {
+            0_usize => {}
+            1_usize => self
+                .screen_offset
+                .update_offset_single_move(&self.cursor_position, size, 3),
+            _ => self.screen_offset.handle_offset_screen_snap(
+                &self.cursor_position,
+                size,
+                3,
+                buffer.len(),
+            ),
+        }
+        wrap::keep_in_view(buffer, &self.cursor_position, &mut self.screen_offset, size);
+        /*
+                    if !self
+                        .cursor_position
+                        .height_in_view(&self.screen_offset, size, 2)
+                        | !self
+                            .cursor_position
+                            .width_in_view(&self.screen_offset, size)
+                    {
+                        self.screen_offset.handle_offset_screen_snap(
+                            &self.cursor_position,
+                            size,
+                            3,
+                            buffer.len(),
+                        );
+                    }
+        */
+    }
 
-            // if the search position is out of current screen bounds
-            // if out width is within 0 - size
-            // snap offset left
-            if self.cursor_position.width < size.width {
-                self.screen_offset.snap_left();
+    /// Ctrl-r, ask for a replacement then step through the hits asking to replace each one
+    /// in a regex search $1 in the replacement is the first capture group
+    fn replace(&mut self, size: &mut Size, buffer: &mut Buffer, keys: &mut KeySource) {
+        let query = self.string.clone();
+        let Some(replacement) = self.read_replacement(size, buffer, keys) else {
+            return;
+        };
+        let Ok(pattern) = self.pattern(&query) else {
+            return;
+        };
+        let template = if self.regex {
+            replacement.clone()
+        } else {
+            replacement.replace('$', "$$")
+        };
+        let last_line = buffer.len().saturating_sub(1);
+        let mut substitution = Substitution::new(pattern, template, (0, last_line), true);
+        let mut confirm = true;
+        while let Some(hit) = substitution.next_hit(buffer) {
+            let answer = if confirm {
+                self.confirm_replace(&hit, &replacement, size, buffer, keys)
+            } else {
+                ConfirmReplace::Yes
+            };
+            match answer {
+                ConfirmReplace::Yes => substitution.replace(buffer, &hit),
+                ConfirmReplace::No => substitution.skip(&hit),
+                ConfirmReplace::All => {
+                    confirm = false;
+                    substitution.replace(buffer, &hit);
+                }
+                ConfirmReplace::Last => {
+                    substitution.replace(buffer, &hit);
+                    break;
+                }
+                _ => break,
             }
-            match self
-                .cursor_position
-                .max_displacement_from_view(&self.screen_offset, &size, 3)
-            {
-                0_usize => {}
-                1_usize => {
-                    self.screen_offset
-                        .update_offset_single_move(&self.cursor_position, &size, 3)
+        }
+        if let Some(last) = substitution.last {
+            self.cursor_position = last;
+            self.follow_cursor(size, buffer);
+        }
+    }
+
+    /// the text to replace the hits with, None when the prompt is left with Esc
+    fn read_replacement(
+        &mut self,
+        size: &mut Size,
+        buffer: &Buffer,
+        keys: &mut KeySource,
+    ) -> Option<String> {
+        let mut replacement = String::new();
+        loop {
+            self.render(buffer, size);
+            self.render_prompt(
+                size,
+                buffer,
+                &format!("Replace {} with: {replacement}", self.string),
+            );
+            match SearchCommand::try_from(keys.next_event()) {
+                Ok(SearchCommand::Insert(c)) => replacement.push(c),
+                Ok(SearchCommand::BackSpace) => {
+                    replacement.pop();
                 }
-                _ => self.screen_offset.handle_offset_screen_snap(
-                    &self.cursor_position,
-                    &size,
-                    3,
-                    buffer.len(),
-                ),
+                Ok(SearchCommand::AssumeState) => return Some(replacement),
+                Ok(SearchCommand::RevertState) => return None,
+                Ok(SearchCommand::Resize(new_size)) => *size = new_size,
+                _ => {}
+            }
+        }
+    }
+
+    /// show a hit and ask whether to replace it, the other hits stay highlighted
+    fn confirm_replace(
+        &mut self,
+        hit: &SearchMatch,
+        replacement: &str,
+        size: &mut Size,
+        buffer: &Buffer,
+        keys: &mut KeySource,
+    ) -> ConfirmReplace {
+        // earlier replacements moved the hits, they are searched again
+        let query = self.string.clone();
+        let hits = self.find(&query, buffer);
+        if let Some(top) = self.stack.last_mut() {
+            *top = hits;
+        }
+        self.set_line_indicies();
+        self.cursor_position = hit.start;
+        loop {
+            self.follow_cursor(size, buffer);
+            self.render(buffer, size);
+            self.render_prompt(
+                size,
+                buffer,
+                &format!("replace with {replacement} (y/n/a/q/l)?"),
+            );
+            match ConfirmReplace::try_from(keys.next_event()) {
+                Ok(ConfirmReplace::Resize(new_size)) => *size = new_size,
+                Ok(ConfirmReplace::NoAction) | Err(_) => {}
+                Ok(answer) => return answer,
             }
-            /*
-                        if !self
-                            .cursor_position
-                            .height_in_view(&self.screen_offset, size, 2)
-                            | !self
-                                .cursor_position
-                                .width_in_view(&self.screen_offset, size)
-                        {
-                            self.screen_offset.handle_offset_screen_snap(
-                                &self.cursor_position,
-                                size,
-                                3,
-                                buffer.len(),
-                            );
-                        }
-            */
         }
-        self.render(buffer, size);
+    }
+
+    /// draw over the search string and put the cursor back
+    fn render_prompt(&self, size: &Size, buffer: &Buffer, prompt: &str) {
+        let render = Terminal::render_line(size.height.saturating_sub(2), prompt);
+        let cursor = Terminal::move_cursor_to(self.screen_cursor(buffer, size));
+        let flush = Terminal::execute();
+        debug_assert!(render.is_ok() & cursor.is_ok() & flush.is_ok());
     }
 
     fn render(&self, buffer: &Buffer, size: &Size) {
@@ -195,54 +381,144 @@ impl Search {
         Terminal::move_cursor_to(self.screen_offset.to_position()).expect("Terminal error");
         Terminal::clear_screen().expect("Terminal error");
 
-        #[allow(clippy::integer_division)]
-        for current_row in self.screen_offset.height
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(size.height)
-                .saturating_sub(2)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
-            if self.line_indicies.contains(&current_row) {
-                self.render_search_line(current_row, buffer, size, self.highlight, self.text);
+        let rows = size.height.saturating_sub(2);
+        for screen_row in wrap::screen_rows(buffer, &self.screen_offset, size, rows) {
+            let Some(index) = screen_row.line else {
+                Terminal::render_line(screen_row.row, "~").expect("Terminal error");
                 continue;
-            }
-
-            // buffer should not be empty here
-            if let Some(line) = buffer.text.get(current_row) {
-                Terminal::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(size.width),
-                    ),
+            };
+            if screen_row.first {
+                gutter::start_row(
+                    screen_row.row,
+                    index,
+                    self.cursor_position.height,
+                    &self.screen_offset,
                 )
-                .expect("Terminal Error");
             } else {
-                Terminal::render_line(relative_row, "~").expect("Terminal error");
+                gutter::continue_row(screen_row.row, &self.screen_offset)
+            }
+            .expect("Terminal Error");
+            if self.line_indicies.contains(&index) {
+                self.render_search_line(&screen_row, buffer, self.highlight, self.text);
+            } else {
+                Terminal::print(buffer.line(index).get_line_subset(screen_row.columns))
+                    .expect("Terminal Error");
             }
         }
 
         self.render_search_string(size);
-        Terminal::render_status_line(
-            &Mode::Search,
-            buffer.is_saved,
-            size,
-            buffer.filename.as_deref(),
-            Some((self.cursor_position.height.saturating_add(1), buffer.len())),
-        )
-        .expect("Terminal Error");
-
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )
-        .expect("Terminal Error");
+        if let Some(err) = &self.error {
+            Terminal::render_status_message(size, err).expect("Terminal Error");
+        } else {
+            Terminal::render_status_line(
+                &Mode::Search,
+                buffer.is_saved,
+                size,
+                buffer.filename.as_deref(),
+                buffer.format,
+                Some((self.cursor_position.height.saturating_add(1), buffer.len())),
+            )
+            .expect("Terminal Error");
+        }
+
+        Terminal::move_cursor_to(self.screen_cursor(buffer, size)).expect("Terminal Error");
         Terminal::show_cursor().expect("Terminal Error");
         Terminal::execute().expect("Terminal Error");
     }
 
+    /// where the cursor is drawn
+    fn screen_cursor(&self, buffer: &Buffer, size: &Size) -> ScreenPosition {
+        wrap::screen_position(buffer, &self.cursor_position, &self.screen_offset, size)
+    }
+
+    /// the query along with the current search modes
+    fn entry(&self, query: &str) -> SearchEntry {
+        SearchEntry {
+            query: query.to_string(),
+            regex: self.regex,
+            case: self.case,
+            whole_word: self.whole_word,
+        }
+    }
+
+    fn pattern(&self, query: &str) -> Result<Regex, String> {
+        self.entry(query).pattern()
+    }
+
+    /// up and down arrow, bring back an older or newer search that starts with what was typed
+    /// going past the newest search brings back what was typed
+    fn recall(&mut self, older: bool, history: &SearchHistory, buffer: &Buffer) {
+        let (index, typed) = self
+            .recalled
+            .clone()
+            .unwrap_or_else(|| (history.len(), self.string.clone()));
+        let found = if older {
+            history.older(index, &typed)
+        } else {
+            history.newer(index, &typed)
+        };
+        match found {
+            Some((index, entry)) => {
+                self.string.clone_from(&entry.query);
+                self.regex = entry.regex;
+                self.case = entry.case;
+                self.whole_word = entry.whole_word;
+                self.recalled = Some((index, typed));
+            }
+            None if older => return,
+            None => {
+                self.string = typed;
+                self.recalled = None;
+            }
+        }
+        self.search_again(buffer);
+    }
+
+    /// search every query on the stack again after the search mode changed
+    fn search_again(&mut self, buffer: &Buffer) {
+        let queries: Vec<String> = self
+            .string
+            .char_indices()
+            .map(|(i, c)| self.string[..i.saturating_add(c.len_utf8())].to_string())
+            .collect();
+        self.stack = queries
+            .iter()
+            .map(|query| self.find(query, buffer))
+            .collect();
+    }
+
+    /// the matches for a query, an invalid pattern matches nothing
+    fn find(&mut self, query: &str, buffer: &Buffer) -> Vec<SearchMatch> {
+        match self.pattern(query) {
+            Ok(pattern) => {
+                self.error = None;
+                buffer.search(&pattern)
+            }
+            Err(err) => {
+                self.error = Some(err);
+                Vec::new()
+            }
+        }
+    }
+
+    /// snap to the next or previous result, wrapping around the ends
+    fn step(&mut self, forward: bool) {
+        let Some(curr_results) = self.stack.last() else {
+            return;
+        };
+        self.index = if forward {
+            if curr_results.len().saturating_sub(1) > self.index {
+                self.index.saturating_add(1)
+            } else {
+                0
+            }
+        } else if self.index > 0 {
+            self.index.saturating_sub(1)
+        } else {
+            curr_results.len().saturating_sub(1)
+        };
+    }
+
     #[inline]
     fn revert_screen_state(&mut self, pos: &Position, offset: &ScreenOffset) {
         self.cursor_position = *pos;
@@ -254,7 +530,7 @@ impl Search {
         // in most cases this probably does not matter
         let current_positions: Vec<Position> =
             match self.stack.get(self.stack.len().saturating_sub(1)) {
-                Some(positions) => positions.clone(),
+                Some(matches) => matches.iter().map(|found| found.start).collect(),
                 None => return None,
             };
         let mut l: usize = 0;
@@ -318,9 +594,24 @@ impl Search {
 
     #[inline]
     fn render_search_string(&self, size: &Size) {
+        let modes: Vec<&str> = [
+            (self.regex, "regex"),
+            (self.case == CaseMode::Ignore, "ignore case"),
+            (self.case == CaseMode::Smart, "smart case"),
+            (self.whole_word, "word"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        let mode = if modes.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", modes.join(", "))
+        };
+        let prompt = if self.replacing { "Replace" } else { "Search" };
         let result = Terminal::render_line(
             size.height.saturating_sub(2),
-            format!("Search: {}", self.string),
+            format!("{prompt}{mode}: {}", self.string),
         );
 
         debug_assert!(result.is_ok(), "Failed to render line");
@@ -334,56 +625,71 @@ impl Search {
         self.line_indicies.clear();
 
         // iter through search hits for current query
-        for position in &self.stack[self.stack.len().saturating_sub(1)] {
-            self.line_indicies.insert(position.height);
+        for found in &self.stack[self.stack.len().saturating_sub(1)] {
+            self.line_indicies.insert(found.start.height);
         }
     }
 
     #[inline]
     fn render_search_line(
         &self,
-        line: usize,
+        screen_row: &ScreenRow,
         buffer: &Buffer,
-        size: &Size,
         search_highlight: Color,
         search_text: Color,
     ) {
-        let styled_search: StyledContent<String> = self
-            .string
-            .clone()
-            .with(search_text)
-            .on(search_highlight)
-            .attribute(Attribute::Bold);
-
-        Terminal::move_cursor_to(ScreenPosition {
-            height: line.saturating_sub(self.screen_offset.height),
-            width: 0,
-        })
-        .expect("Terminal Error");
-        Terminal::clear_line().expect("Terminal Error");
-
-        let full_line = &buffer.text[line].raw_string;
-        let start = self.screen_offset.width;
-        let end = min(
-            self.screen_offset.width.saturating_add(size.width),
-            full_line.len(),
-        );
-        let current_line = match full_line.get(start..end) {
-            Some(text) => text,
-            None => return,
+        let Some(line) = screen_row.line else {
+            return;
         };
-        let mut split = current_line.split(&self.string);
-
-        if let Some(first) = split.next() {
-            if !current_line.starts_with(&self.string) {
-                Terminal::queue_command(Print(first)).expect("Terminal Error");
+        let Some(matches) = self.stack.last() else {
+            return;
+        };
+        // the hits are in line order
+        let first_hit = matches.partition_point(|found| found.start.height < line);
+        let hits: Vec<&SearchMatch> = matches[first_hit..]
+            .iter()
+            .take_while(|found| found.start.height == line)
+            .collect();
+        let style = |column: usize| {
+            let Some(hit) = hits
+                .iter()
+                .find(|hit| (hit.start.width..hit.end).contains(&column))
+            else {
+                return HitStyle::Plain;
+            };
+            if hit.groups.iter().any(|group| group.contains(&column)) {
+                HitStyle::Group
+            } else {
+                HitStyle::Match
             }
         };
 
-        for text in split {
-            Terminal::queue_command(PrintStyledContent(styled_search.clone()))
-                .expect("Terminal Error");
-            Terminal::queue_command(Print(text)).expect("Terminal Error");
+        let full_line = buffer.line(line);
+        let end = min(screen_row.columns.end, full_line.len());
+        // print runs of columns drawn the same way
+        let mut start = screen_row.columns.start;
+        while start < end {
+            let run_style = style(start);
+            let mut run_end = start.saturating_add(1);
+            while run_end < end && style(run_end) == run_style {
+                run_end = run_end.saturating_add(1);
+            }
+            let text = full_line.get_line_subset(start..run_end).to_string();
+            let res = match run_style {
+                HitStyle::Plain => Terminal::print(text),
+                HitStyle::Match => Terminal::print_styled(
+                    text.with(search_text)
+                        .on(search_highlight)
+                        .attribute(Attribute::Bold),
+                ),
+                HitStyle::Group => Terminal::print_styled(
+                    text.with(search_highlight)
+                        .on(search_text)
+                        .attribute(Attribute::Bold),
+                ),
+            };
+            res.expect("Terminal Error");
+            start = run_end;
         }
     }
 }
@@ -398,10 +704,14 @@ mod tests {
         let heights: Vec<usize> = vec![4, 9, 12, 30, 39, 45, 56, 63];
         let mut positions = Vec::new();
         for i in heights.iter() {
-            positions.push(Position {
-                height: *i,
-                width: 0,
-                max_width: usize::default(),
+            positions.push(SearchMatch {
+                start: Position {
+                    height: *i,
+                    width: 0,
+                    max_width: usize::default(),
+                },
+                end: 1,
+                groups: Vec::new(),
             })
         }
         search.stack = vec![positions];
@@ -414,4 +724,23 @@ mod tests {
         pos = search.find_relative_start(40);
         assert_eq!(pos.unwrap(), 4);
     }
+
+    #[test]
+    fn case_and_whole_word_patterns() {
+        let mut search = Search::default();
+        let hits = |search: &Search, query: &str| {
+            let pattern = search.pattern(query).unwrap();
+            pattern.find_iter("Foo foo food").count()
+        };
+        assert_eq!(hits(&search, "foo"), 2);
+        search.case = CaseMode::Ignore;
+        assert_eq!(hits(&search, "FOO"), 3);
+        search.case = CaseMode::Smart;
+        assert_eq!(hits(&search, "foo"), 3);
+        assert_eq!(hits(&search, "Foo"), 1);
+        search.whole_word = true;
+        assert_eq!(hits(&search, "foo"), 2);
+        search.regex = true;
+        assert_eq!(hits(&search, "fo+d?"), 3);
+    }
 }
@@ -0,0 +1,255 @@
+use super::buffer::write_file;
+use regex::{Regex, RegexBuilder};
+use std::env::var_os;
+use std::fs;
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+
+// upper bound on the number of searches kept
+const MAX_SEARCHES: usize = 50;
+// kept in the home directory so it is shared by every session
+const HISTORY_FILE: &str = ".mini_vim_search_history";
+
+/// how letter case is matched, Alt-c cycles through the modes
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Ignore,
+    Smart, // ignore case unless the query has an upper case letter
+}
+
+impl CaseMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Sensitive => Self::Ignore,
+            Self::Ignore => Self::Smart,
+            Self::Smart => Self::Sensitive,
+        }
+    }
+
+    fn ignores_case(self, query: &str) -> bool {
+        match self {
+            Self::Sensitive => false,
+            Self::Ignore => true,
+            Self::Smart => !query.chars().any(char::is_uppercase),
+        }
+    }
+}
+
+/// a search query along with the modes it was searched with
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchEntry {
+    pub query: String,
+    pub regex: bool, // the query is a regular expression rather than plain text
+    pub case: CaseMode,
+    pub whole_word: bool, // hits must start and end on a word boundary
+}
+
+impl SearchEntry {
+    /// the pattern for the query, plain text matches itself
+    /// an invalid pattern gives back the reason as a single line
+    pub fn pattern(&self) -> Result<Regex, String> {
+        let mut pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.case.ignores_case(&self.query))
+            .build()
+            .map_err(|err| {
+                let reason = err.to_string();
+                let reason = reason.lines().last().unwrap_or_default().trim();
+                format!("invalid pattern: {}", reason.trim_start_matches("error: "))
+            })
+    }
+
+    /// an entry as a line of the history file, the modes go before the first :
+    fn to_line(&self) -> String {
+        let modes: String = [
+            (self.regex, 'r'),
+            (self.case == CaseMode::Ignore, 'i'),
+            (self.case == CaseMode::Smart, 's'),
+            (self.whole_word, 'w'),
+        ]
+        .into_iter()
+        .filter_map(|(on, mode)| on.then_some(mode))
+        .collect();
+        format!("{modes}:{}", self.query)
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let (modes, query) = line.split_once(':')?;
+        let case = if modes.contains('i') {
+            CaseMode::Ignore
+        } else if modes.contains('s') {
+            CaseMode::Smart
+        } else {
+            CaseMode::Sensitive
+        };
+        Some(Self {
+            query: query.to_string(),
+            regex: modes.contains('r'),
+            case,
+            whole_word: modes.contains('w'),
+        })
+    }
+}
+
+/// past searches, oldest first, the last one is what n and N search for
+/// kept in the registers as "/ so it outlives a search or vim mode session
+#[derive(Default)]
+pub struct SearchHistory {
+    entries: Vec<SearchEntry>,
+    path: Option<PathBuf>, // where the history is saved, None keeps it in memory
+    pub backward: bool,    // the last search went up the buffer, N goes down
+}
+
+impl SearchHistory {
+    /// the history saved by earlier sessions, empty when there is none
+    pub fn load() -> Self {
+        let path = var_os("HOME")
+            .or_else(|| var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let entries = path.as_deref().map(read_entries).unwrap_or_default();
+        Self {
+            entries,
+            path,
+            backward: false,
+        }
+    }
+
+    /// add a search as the newest entry, an earlier search for the same query is dropped
+    /// the search is kept for this session even when saving it fails
+    pub fn add(&mut self, entry: SearchEntry, backward: bool) -> Result<(), Error> {
+        if entry.query.is_empty() {
+            return Ok(());
+        }
+        self.backward = backward;
+        push_entry(&mut self.entries, entry.clone());
+        self.save(entry)
+    }
+
+    /// add the entry to the saved history
+    /// other sessions may have saved searches since this one loaded, they are kept
+    fn save(&self, entry: SearchEntry) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut entries = read_entries(path);
+        push_entry(&mut entries, entry);
+        write_file(path, |writer| {
+            for entry in &entries {
+                writeln!(writer, "{}", entry.to_line())?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn last(&self) -> Option<&SearchEntry> {
+        self.entries.last()
+    }
+
+    /// the closest entry before `index` that starts with the prefix, for up arrow recall
+    /// the index is the number of entries when recall starts
+    pub fn older(&self, index: usize, prefix: &str) -> Option<(usize, &SearchEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .take(index)
+            .rev()
+            .find(|(_, entry)| entry.query.starts_with(prefix))
+    }
+
+    /// the closest entry after `index` that starts with the prefix, for down arrow recall
+    pub fn newer(&self, index: usize, prefix: &str) -> Option<(usize, &SearchEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(index.saturating_add(1))
+            .find(|(_, entry)| entry.query.starts_with(prefix))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// the entries of a history file, none when it can not be read
+fn read_entries(path: &Path) -> Vec<SearchEntry> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().filter_map(SearchEntry::from_line).collect())
+        .unwrap_or_default()
+}
+
+fn push_entry(entries: &mut Vec<SearchEntry>, entry: SearchEntry) {
+    entries.retain(|old| old.query != entry.query);
+    entries.push(entry);
+    if entries.len() > MAX_SEARCHES {
+        entries.remove(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str) -> SearchEntry {
+        SearchEntry {
+            query: query.into(),
+            ..SearchEntry::default()
+        }
+    }
+
+    #[test]
+    fn history_lines_keep_modes() {
+        let searched = SearchEntry {
+            query: "a:b".into(),
+            regex: true,
+            case: CaseMode::Smart,
+            whole_word: true,
+        };
+        assert_eq!(searched.to_line(), "rsw:a:b");
+        assert_eq!(SearchEntry::from_line(&searched.to_line()), Some(searched));
+        assert_eq!(SearchEntry::from_line(":x"), Some(entry("x")));
+        assert!(SearchEntry::from_line("no modes").is_none());
+    }
+
+    #[test]
+    fn recall_by_prefix() {
+        let mut history = SearchHistory::default();
+        for query in ["foo", "bar", "food", "foo", ""] {
+            history.add(entry(query), false).unwrap();
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.last(), Some(&entry("foo")));
+
+        let (index, found) = history.older(history.len(), "fo").unwrap();
+        assert_eq!((index, found.query.as_str()), (2, "foo"));
+        let (index, found) = history.older(index, "fo").unwrap();
+        assert_eq!((index, found.query.as_str()), (1, "food"));
+        assert!(history.older(index, "fo").is_none());
+        assert_eq!(history.newer(index, "").map(|(i, _)| i), Some(2));
+        assert!(history.newer(2, "").is_none());
+    }
+
+    #[test]
+    fn save_keeps_other_sessions() {
+        let path = std::env::temp_dir().join(format!("search_history_{}", std::process::id()));
+        fs::write(&path, ":other\n:both\n").unwrap();
+        let mut history = SearchHistory {
+            entries: vec![entry("mine")],
+            path: Some(path.clone()),
+            backward: false,
+        };
+        history.add(entry("both"), false).unwrap();
+        let saved = read_entries(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved, [entry("other"), entry("both")]);
+        assert_eq!(history.entries, [entry("mine"), entry("both")]);
+    }
+}
@@ -0,0 +1,413 @@
+use super::buffer::{Buffer, SearchMatch};
+use crate::editor::terminal::Position;
+use regex::Regex;
+
+/// a line in the range of a : command
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Address {
+    Line(usize),  // counted from one
+    Current,      // .
+    Last,         // $
+    Below(usize), // .+n
+    Above(usize), // .-n
+}
+
+impl Address {
+    /// the line index an address points to, kept inside the buffer
+    fn line(self, cursor: usize, len: usize) -> usize {
+        let line = match self {
+            Self::Line(line) => line.saturating_sub(1),
+            Self::Current => cursor,
+            Self::Last => len.saturating_sub(1),
+            Self::Below(lines) => cursor.saturating_add(lines),
+            Self::Above(lines) => cursor.saturating_sub(lines),
+        };
+        line.min(len.saturating_sub(1))
+    }
+
+    /// read an address from the start of a command, returns the rest of the command
+    fn parse(command: &str) -> Option<(Self, &str)> {
+        fn digits(text: &str) -> usize {
+            text.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len())
+        }
+        fn offset(text: &str) -> Option<(usize, &str)> {
+            // a sign without a number moves one line
+            let end = digits(text);
+            let lines = if end == 0 {
+                1
+            } else {
+                text[..end].parse().ok()?
+            };
+            Some((lines, &text[end..]))
+        }
+        let rest = command.strip_prefix('.').unwrap_or(command);
+        if let Some(text) = rest.strip_prefix('+') {
+            let (lines, rest) = offset(text)?;
+            return Some((Self::Below(lines), rest));
+        }
+        if let Some(text) = rest.strip_prefix('-') {
+            let (lines, rest) = offset(text)?;
+            return Some((Self::Above(lines), rest));
+        }
+        if rest.len() < command.len() {
+            return Some((Self::Current, rest));
+        }
+        if let Some(rest) = command.strip_prefix('$') {
+            return Some((Self::Last, rest));
+        }
+        let end = digits(command);
+        let line = command[..end].parse().ok()?;
+        Some((Self::Line(line), &command[end..]))
+    }
+}
+
+/// the lines a : command works on, % is every line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineRange {
+    pub first: Address,
+    pub last: Address,
+}
+
+impl Default for LineRange {
+    fn default() -> Self {
+        Self {
+            first: Address::Current,
+            last: Address::Current,
+        }
+    }
+}
+
+impl LineRange {
+    /// the first and last line index, a backwards range is turned around
+    pub fn lines(self, cursor: usize, len: usize) -> (usize, usize) {
+        let first = self.first.line(cursor, len);
+        let last = self.last.line(cursor, len);
+        (first.min(last), first.max(last))
+    }
+
+    /// read a range from the start of a command, returns the rest of the command
+    fn parse(command: &str) -> (Self, &str) {
+        if let Some(rest) = command.strip_prefix('%') {
+            let range = Self {
+                first: Address::Line(1),
+                last: Address::Last,
+            };
+            return (range, rest);
+        }
+        let Some((first, rest)) = Address::parse(command) else {
+            return (Self::default(), command);
+        };
+        let Some((last, rest)) = rest.strip_prefix(',').and_then(Address::parse) else {
+            return (Self { first, last: first }, rest);
+        };
+        (Self { first, last }, rest)
+    }
+}
+
+/// :[range]s/pattern/replacement/flags
+/// any char that is not a letter, a digit, \ or " can stand in for the /
+#[derive(Clone, Debug, PartialEq)]
+pub struct Substitute {
+    pub range: LineRange,
+    pub pattern: String,
+    pub replacement: String, // vim style, & and \1 refer to the match
+    pub global: bool,        // g, every match on a line rather than the first
+    pub confirm: bool,       // c, ask before each replace
+    pub ignore_case: bool,   // i, I turns it back off
+}
+
+impl TryFrom<&str> for Substitute {
+    type Error = String;
+    fn try_from(command: &str) -> Result<Self, Self::Error> {
+        let (range, rest) = LineRange::parse(command);
+        let rest = rest
+            .strip_prefix("substitute")
+            .or_else(|| rest.strip_prefix('s'))
+            .ok_or_else(|| format!("not a substitute command: {command}"))?;
+        let mut chars = rest.chars();
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && !matches!(c, '\\' | '"' | ' '))
+            .ok_or("expected a delimiter after s")?;
+        let (pattern, rest) = split_at_delimiter(chars.as_str(), delimiter);
+        if pattern.is_empty() {
+            return Err("no pattern to substitute".into());
+        }
+        let (replacement, flags) = split_at_delimiter(rest.unwrap_or_default(), delimiter);
+        let mut substitute = Self {
+            range,
+            pattern,
+            replacement,
+            global: false,
+            confirm: false,
+            ignore_case: false,
+        };
+        for flag in flags.unwrap_or_default().trim_end().chars() {
+            match flag {
+                'g' => substitute.global = true,
+                'c' => substitute.confirm = true,
+                'i' => substitute.ignore_case = true,
+                'I' => substitute.ignore_case = false,
+                _ => return Err(format!("unknown flag {flag}")),
+            }
+        }
+        Ok(substitute)
+    }
+}
+
+/// the text up to an unescaped delimiter and whatever follows it
+/// an escaped delimiter stands for itself, other escapes are left for later
+fn split_at_delimiter(text: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == delimiter {
+            return (part, Some(&text[i.saturating_add(c.len_utf8())..]));
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some((_, next)) if next == delimiter => part.push(next),
+                Some((_, next)) => {
+                    part.push(c);
+                    part.push(next);
+                }
+                None => part.push(c),
+            }
+            continue;
+        }
+        part.push(c);
+    }
+    (part, None)
+}
+
+/// turn a vim replacement into a regex replacement
+/// & and \0 are the whole match, \1 to \9 the groups, \r and \n break the line
+pub fn vim_replacement(replacement: &str) -> String {
+    let mut template = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => template.push_str("${0}"),
+            '$' => template.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    template.push_str("${");
+                    template.push(digit);
+                    template.push('}');
+                }
+                Some('r' | 'n') => template.push('\n'),
+                Some('t') => template.push('\t'),
+                Some('$') => template.push_str("$$"),
+                Some(other) => template.push(other),
+                None => template.push('\\'),
+            },
+            _ => template.push(c),
+        }
+    }
+    template
+}
+
+/// steps through the matches of a pattern on a range of lines and replaces them
+/// text put in by a replace is not searched again
+pub struct Substitution {
+    pattern: Regex,
+    replacement: String, // regex style, $1 is the first group
+    global: bool,
+    from: Position,          // where the next match is looked for
+    last_line: usize,        // grows as replacements add lines
+    after: Option<Position>, // the end of the last match, an empty match is not taken there
+    pub count: usize,
+    pub lines: usize,
+    pub last: Option<Position>, // the start of the last replacement
+}
+
+impl Substitution {
+    pub fn new(pattern: Regex, replacement: String, lines: (usize, usize), global: bool) -> Self {
+        Self {
+            pattern,
+            replacement,
+            global,
+            from: Position {
+                height: lines.0,
+                width: 0,
+                max_width: 0,
+            },
+            last_line: lines.1,
+            after: None,
+            count: 0,
+            lines: 0,
+            last: None,
+        }
+    }
+
+    /// the next match in the range, None once the range is done
+    pub fn next_hit(&mut self, buffer: &Buffer) -> Option<SearchMatch> {
+        while self.from.height <= self.last_line && self.from.height < buffer.len() {
+            let line = buffer.line(self.from.height);
+            if self.from.width <= line.len() {
+                let found = self
+                    .pattern
+                    .captures_at(&line.raw_string, line.byte_index(self.from.width))
+                    .map(|captures| SearchMatch::from_captures(&line, self.from.height, &captures));
+                match found {
+                    Some(hit) if hit.start.width == hit.end && Some(hit.start) == self.after => {
+                        // no empty match right where the last match ended
+                        self.from.width = hit.end.saturating_add(1);
+                        continue;
+                    }
+                    Some(hit) => return Some(hit),
+                    None => {}
+                }
+            }
+            self.next_line(self.from.height);
+        }
+        None
+    }
+
+    /// replace a match given by `next_hit`
+    pub fn replace(&mut self, buffer: &mut Buffer, hit: &SearchMatch) {
+        let line = buffer.line(hit.start.height);
+        let mut text = String::new();
+        if let Some(captures) = self
+            .pattern
+            .captures_at(&line.raw_string, line.byte_index(hit.start.width))
+        {
+            captures.expand(&self.replacement, &mut text);
+        }
+        let end = Position {
+            width: hit.end,
+            ..hit.start
+        };
+        let after = buffer.replace_text(&hit.start, &end, &text);
+        if self.last.is_none_or(|last| last.height != hit.start.height) {
+            self.lines = self.lines.saturating_add(1);
+        }
+        self.count = self.count.saturating_add(1);
+        self.last = Some(hit.start);
+        self.last_line = self
+            .last_line
+            .saturating_add(after.height.saturating_sub(hit.start.height));
+        self.advance(after, hit.start.width == hit.end);
+    }
+
+    /// leave a match as it is and move past it
+    pub fn skip(&mut self, hit: &SearchMatch) {
+        let end = Position {
+            width: hit.end,
+            ..hit.start
+        };
+        self.advance(end, hit.start.width == hit.end);
+    }
+
+    fn advance(&mut self, end: Position, empty: bool) {
+        if !self.global {
+            self.next_line(end.height);
+            return;
+        }
+        self.after = Some(end);
+        self.from = end;
+        if empty {
+            self.from.width = end.width.saturating_add(1);
+        }
+    }
+
+    fn next_line(&mut self, height: usize) {
+        self.from = Position {
+            height: height.saturating_add(1),
+            width: 0,
+            max_width: 0,
+        };
+        self.after = None;
+    }
+
+    /// how the result is reported, 3 substitutions on 2 lines
+    pub fn report(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        format!(
+            "{} substitution{} on {} line{}",
+            self.count,
+            plural(self.count),
+            self.lines,
+            plural(self.lines)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(&Position::default(), text);
+        buffer
+    }
+
+    fn substitute_all(buffer: &mut Buffer, command: &str) -> Substitution {
+        let command = Substitute::try_from(command).unwrap();
+        let lines = command.range.lines(0, buffer.len());
+        let mut substitution = Substitution::new(
+            Regex::new(&command.pattern).unwrap(),
+            vim_replacement(&command.replacement),
+            lines,
+            command.global,
+        );
+        while let Some(hit) = substitution.next_hit(buffer) {
+            substitution.replace(buffer, &hit);
+        }
+        substitution
+    }
+
+    #[test]
+    fn parse_substitute_commands() {
+        let command = Substitute::try_from("%s/a\\/b/c/gi").unwrap();
+        assert_eq!(command.range.lines(3, 10), (0, 9));
+        assert_eq!(command.pattern, "a/b");
+        assert_eq!(command.replacement, "c");
+        assert!(command.global && command.ignore_case && !command.confirm);
+
+        let command = Substitute::try_from("2,.+1s#x#y").unwrap();
+        assert_eq!(command.range.lines(5, 10), (1, 6));
+        assert!(!command.global);
+        let command = Substitute::try_from("$s/x/").unwrap();
+        assert_eq!(command.range.lines(0, 4), (3, 3));
+        assert_eq!(command.replacement, "");
+
+        assert!(Substitute::try_from("s").is_err());
+        assert!(Substitute::try_from("s//x/").is_err());
+        assert!(Substitute::try_from("s/x/y/z").is_err());
+        assert!(Substitute::try_from("w").is_err());
+    }
+
+    #[test]
+    fn vim_replacements() {
+        assert_eq!(vim_replacement(r"<&> \2\1 $5 \&"), "<${0}> ${2}${1} $$5 &");
+        assert_eq!(vim_replacement(r"a\rb"), "a\nb");
+    }
+
+    #[test]
+    fn substitute_lines() {
+        let mut buf = buffer("one two one\none\nthree");
+        let substitution = substitute_all(&mut buf, "%s/one/1/g");
+        assert_eq!(substitution.report(), "3 substitutions on 2 lines");
+        assert_eq!(buf.line(0).raw_string, "1 two 1");
+        assert_eq!(buf.line(1).raw_string, "1");
+
+        // empty matches are taken between the chars but not after a match
+        let mut buf = buffer("baaac");
+        substitute_all(&mut buf, "s/a*/-/g");
+        assert_eq!(buf.line(0).raw_string, "-b-c-");
+
+        // new lines from a replacement are not searched, the range grows with them
+        let mut buf = buffer("a,b\nc,d\ne,f");
+        let substitution = substitute_all(&mut buf, r"1,2s/\(\w\),/\1\r/");
+        assert_eq!(substitution.count, 0); // vim groups are not regex groups
+        let substitution = substitute_all(&mut buf, r"1,2s/(\w),/\1\r/");
+        assert_eq!(substitution.report(), "2 substitutions on 2 lines");
+        assert_eq!(buf.len(), 5);
+        assert_eq!(buf.line(3).raw_string, "d");
+        assert_eq!(buf.line(4).raw_string, "e,f");
+        assert_eq!(substitution.last.map(|pos| pos.height), Some(2));
+    }
+}
@@ -0,0 +1,411 @@
+use super::buffer::Buffer;
+use super::theme::SyntaxColors;
+use crate::editor::terminal::Terminal;
+use crossterm::style::Stylize;
+use std::io::Error;
+use std::ops::Range;
+use std::path::Path;
+mod languages;
+use languages::LANGUAGES;
+
+/// what a run of text is, the theme has a color for each kind
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Constant,
+    Number,
+    String,
+    Comment,
+    Heading, // a markdown heading, a toml table or a similar line
+    Variable,
+}
+
+/// a run that is opened and closed by markers, strings and block comments
+pub struct Delimited {
+    pub open: &'static str,
+    pub close: &'static str,
+    pub kind: TokenKind,
+    pub escape: Option<char>, // the char after this one never closes the run
+    pub multi_line: bool,     // the run can carry on over the end of a line
+    pub max_len: Option<usize>, // bytes after an escape, for chars like 'a' that are not lifetimes
+}
+
+/// a language definition, a language is added by adding one to `LANGUAGES`
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub file_names: &'static [&'static str], // files without an extension, like Makefile
+    pub line_comments: &'static [&'static str],
+    pub delimited: &'static [Delimited], // tried in order, so """ goes before "
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub constants: &'static [&'static str],
+    pub capitalized_types: bool, // a word starting with an upper case letter is a type
+    pub line_starts: &'static [(&'static str, TokenKind)], // colors the whole line
+    pub variable_prefix: Option<char>, // $name in a shell script
+}
+
+impl Language {
+    /// the language of a file, picked by its extension or name
+    pub fn from_filename(filename: &str) -> Option<&'static Self> {
+        let path = Path::new(filename);
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let name = path.file_name().and_then(|name| name.to_str());
+        LANGUAGES.iter().find(|language| {
+            extension.is_some_and(|ext| language.extensions.contains(&ext))
+                || name.is_some_and(|name| language.file_names.contains(&name))
+        })
+    }
+
+    fn word_kind(&self, word: &str) -> Option<TokenKind> {
+        if self.keywords.contains(&word) {
+            Some(TokenKind::Keyword)
+        } else if self.constants.contains(&word) {
+            Some(TokenKind::Constant)
+        } else if self.types.contains(&word)
+            || (self.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+        {
+            Some(TokenKind::Type)
+        } else {
+            None
+        }
+    }
+}
+
+/// what the end of a line leaves open for the next line
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    Inside(usize), // the index of the open multi line run in `Language::delimited`
+}
+
+/// a run of a line, the range is in bytes
+pub type Token = (Range<usize>, TokenKind);
+
+/// the end of a delimited run that starts its body at `from`, after the close marker
+fn find_close(line: &str, from: usize, run: &Delimited) -> Option<usize> {
+    let body = &line[from..];
+    // a run with a length limit holds a single char unless it starts with an escape
+    let limit = run.max_len.map(|max| {
+        if run.escape.is_some_and(|escape| body.starts_with(escape)) {
+            max
+        } else {
+            body.chars().next().map_or(0, char::len_utf8)
+        }
+    });
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if limit.is_some_and(|limit| i > limit) {
+            return None;
+        }
+        if Some(c) == run.escape {
+            chars.next();
+            continue;
+        }
+        if line[from.saturating_add(i)..].starts_with(run.close) {
+            return Some(from.saturating_add(i).saturating_add(run.close.len()));
+        }
+    }
+    None
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// the end of the run of chars from `start` that pass a test
+fn end_of(line: &str, start: usize, test: impl Fn(char) -> bool) -> usize {
+    line[start..]
+        .find(|c: char| !test(c))
+        .map_or(line.len(), |end| start.saturating_add(end))
+}
+
+/// split a line into tokens given the state the line above left, plain text is left out
+/// returns the tokens and the state at the end of the line
+pub fn tokenize(language: &Language, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut at = 0;
+    if let LineState::Inside(index) = state {
+        let Some(run) = language.delimited.get(index) else {
+            return (tokens, LineState::Normal);
+        };
+        let Some(end) = find_close(line, 0, run) else {
+            tokens.push((0..line.len(), run.kind));
+            return (tokens, state);
+        };
+        tokens.push((0..end, run.kind));
+        at = end;
+    } else if let Some((_, kind)) = language
+        .line_starts
+        .iter()
+        .find(|(start, _)| line.trim_start().starts_with(start))
+    {
+        tokens.push((0..line.len(), *kind));
+        return (tokens, state);
+    }
+
+    while let Some(c) = line[at..].chars().next() {
+        let rest = &line[at..];
+        if language
+            .line_comments
+            .iter()
+            .any(|comment| rest.starts_with(comment))
+        {
+            tokens.push((at..line.len(), TokenKind::Comment));
+            break;
+        }
+        if let Some((index, run)) = language
+            .delimited
+            .iter()
+            .enumerate()
+            .find(|(_, run)| rest.starts_with(run.open))
+        {
+            let body = at.saturating_add(run.open.len());
+            match find_close(line, body, run) {
+                Some(end) => {
+                    tokens.push((at..end, run.kind));
+                    at = end;
+                    continue;
+                }
+                None if run.multi_line => {
+                    tokens.push((at..line.len(), run.kind));
+                    return (tokens, LineState::Inside(index));
+                }
+                // an unclosed run that has a length limit is not a run at all
+                None if run.max_len.is_none() => {
+                    tokens.push((at..line.len(), run.kind));
+                    break;
+                }
+                None => {}
+            }
+        }
+        let next = at.saturating_add(c.len_utf8());
+        if Some(c) == language.variable_prefix {
+            let end = if line[next..].starts_with('{') {
+                line[next..].find('}').map_or(line.len(), |close| {
+                    next.saturating_add(close).saturating_add(1)
+                })
+            } else if line[next..].starts_with(is_word_char) {
+                end_of(line, next, is_word_char)
+            } else {
+                // special variables like $? and $1
+                line[next..]
+                    .chars()
+                    .next()
+                    .map_or(next, |special| next.saturating_add(special.len_utf8()))
+            };
+            tokens.push((at..end, TokenKind::Variable));
+            at = end;
+        } else if c.is_ascii_digit() {
+            // hex, floats and suffixes like 1_000u32 or 0x1f or 1.5e3
+            let end = end_of(line, at, |c| is_word_char(c) || c == '.');
+            let end = line[at..end]
+                .find("..")
+                .map_or(end, |range| at.saturating_add(range));
+            tokens.push((at..end, TokenKind::Number));
+            at = end;
+        } else if is_word_char(c) {
+            let end = end_of(line, at, is_word_char);
+            if let Some(kind) = language.word_kind(&line[at..end]) {
+                tokens.push((at..end, kind));
+            }
+            at = end;
+        } else {
+            at = next;
+        }
+    }
+    (tokens, LineState::Normal)
+}
+
+/// the state at the start of each line, worked out as far down as lines have been drawn
+/// an edit drops the states from the edited line down
+#[derive(Clone, Default)]
+pub struct SyntaxCache {
+    language: Option<&'static str>,
+    states: Vec<LineState>,
+}
+
+impl SyntaxCache {
+    /// forget the states below a line that changed
+    pub fn invalidate(&mut self, line_index: usize) {
+        self.states.truncate(line_index.saturating_add(1));
+    }
+
+    /// the state at the start of a line, `line` gives the text of the lines above it
+    pub fn state<F>(&mut self, language: &Language, line_index: usize, line: F) -> LineState
+    where
+        F: Fn(usize) -> String,
+    {
+        if self.language != Some(language.name) {
+            self.language = Some(language.name);
+            self.states.clear();
+        }
+        if self.states.is_empty() {
+            self.states.push(LineState::Normal);
+        }
+        while self.states.len() <= line_index {
+            let above = self.states.len().saturating_sub(1);
+            let start = self.states[above];
+            let (_, end) = tokenize(language, &line(above), start);
+            self.states.push(end);
+        }
+        self.states[line_index]
+    }
+}
+
+/// print the columns of a buffer line at the terminal cursor in the colors of its tokens
+pub fn print_line(
+    buffer: &Buffer,
+    index: usize,
+    columns: Range<usize>,
+    colors: &SyntaxColors,
+) -> Result<(), Error> {
+    let line = buffer.line(index);
+    let mut plain_from = columns.start;
+    for (range, kind) in buffer.tokens(index) {
+        let start = range.start.clamp(columns.start, columns.end);
+        let end = range.end.clamp(columns.start, columns.end);
+        if start >= end {
+            continue;
+        }
+        Terminal::print(line.get_line_subset(plain_from..start))?;
+        let text = line.get_line_subset(start..end).to_string();
+        Terminal::print_styled(text.with(colors.token_color(kind)))?;
+        plain_from = end;
+    }
+    Terminal::print(line.get_line_subset(plain_from..columns.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(
+        language: &str,
+        line: &str,
+        state: LineState,
+    ) -> (Vec<(String, TokenKind)>, LineState) {
+        let language = Language::from_filename(language).unwrap();
+        let (tokens, end) = tokenize(language, line, state);
+        let text = tokens
+            .into_iter()
+            .map(|(range, kind)| (line[range].to_string(), kind))
+            .collect();
+        (text, end)
+    }
+
+    fn token(text: &str, kind: TokenKind) -> (String, TokenKind) {
+        (text.to_string(), kind)
+    }
+
+    #[test]
+    fn languages_from_file_names() {
+        let name = |file: &str| Language::from_filename(file).map(|language| language.name);
+        assert_eq!(name("src/main.rs"), Some("rust"));
+        assert_eq!(name("Cargo.toml"), Some("toml"));
+        assert_eq!(name("README.md"), Some("markdown"));
+        assert_eq!(name("a.json"), Some("json"));
+        assert_eq!(name("setup.py"), Some("python"));
+        assert_eq!(name("install.sh"), Some("shell"));
+        assert_eq!(name(".bashrc"), Some("shell"));
+        assert_eq!(name("notes.txt"), None);
+    }
+
+    #[test]
+    fn rust_tokens() {
+        let (found, end) = tokens(
+            "a.rs",
+            "fn f<'a>(x: &'a str) -> u8 { 'x' } // done",
+            LineState::Normal,
+        );
+        assert_eq!(
+            found,
+            vec![
+                token("fn", TokenKind::Keyword),
+                token("str", TokenKind::Type),
+                token("u8", TokenKind::Type),
+                token("'x'", TokenKind::String),
+                token("// done", TokenKind::Comment),
+            ]
+        );
+        assert_eq!(end, LineState::Normal);
+        let (found, _) = tokens("a.rs", r"let c = '\n'; 0..10", LineState::Normal);
+        assert_eq!(
+            found,
+            vec![
+                token("let", TokenKind::Keyword),
+                token(r"'\n'", TokenKind::String),
+                token("0", TokenKind::Number),
+                token("10", TokenKind::Number),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_runs_carry_over() {
+        let (found, end) = tokens("a.rs", "x /* open", LineState::Normal);
+        assert_eq!(found, vec![token("/* open", TokenKind::Comment)]);
+        let (found, end) = tokens("a.rs", "still */ Some", end);
+        assert_eq!(
+            found,
+            vec![
+                token("still */", TokenKind::Comment),
+                token("Some", TokenKind::Type)
+            ]
+        );
+        assert_eq!(end, LineState::Normal);
+
+        let (_, end) = tokens("a.py", "s = \"\"\"doc", LineState::Normal);
+        let (found, end) = tokens("a.py", "more \" text", end);
+        assert_eq!(found, vec![token("more \" text", TokenKind::String)]);
+        let (found, end) = tokens("a.py", "\"\"\" if None", end);
+        assert_eq!(
+            found,
+            vec![
+                token("\"\"\"", TokenKind::String),
+                token("if", TokenKind::Keyword),
+                token("None", TokenKind::Constant),
+            ]
+        );
+        assert_eq!(end, LineState::Normal);
+
+        let (_, end) = tokens("a.md", "```rust", LineState::Normal);
+        let (found, end) = tokens("a.md", "# not a heading", end);
+        assert_eq!(found, vec![token("# not a heading", TokenKind::String)]);
+        let (_, end) = tokens("a.md", "```", end);
+        let (found, _) = tokens("a.md", "# heading", end);
+        assert_eq!(found, vec![token("# heading", TokenKind::Heading)]);
+    }
+
+    #[test]
+    fn shell_variables() {
+        let (found, _) = tokens("a.sh", "echo \"$HOME\" ${x}y $? # note", LineState::Normal);
+        assert_eq!(
+            found,
+            vec![
+                token("\"$HOME\"", TokenKind::String),
+                token("${x}", TokenKind::Variable),
+                token("$?", TokenKind::Variable),
+                token("# note", TokenKind::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn cache_follows_edits() {
+        let rust = Language::from_filename("a.rs").unwrap();
+        let mut lines = vec!["a /*", "b", "*/ c", "d"];
+        let mut cache = SyntaxCache::default();
+        let state = |cache: &mut SyntaxCache, lines: &[&str], index| {
+            cache.state(rust, index, |i| lines[i].to_string())
+        };
+        assert_eq!(state(&mut cache, &lines, 1), LineState::Inside(0));
+        assert_eq!(state(&mut cache, &lines, 3), LineState::Normal);
+        lines[0] = "a";
+        cache.invalidate(0);
+        assert_eq!(state(&mut cache, &lines, 2), LineState::Normal);
+        assert_eq!(state(&mut cache, &lines, 1), LineState::Normal);
+    }
+}
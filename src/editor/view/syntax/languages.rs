@@ -0,0 +1,172 @@
+use super::{Delimited, Language, TokenKind};
+
+impl Delimited {
+    /// a string that ends at the same quote it opened with, \ escapes the quote
+    const fn string(quote: &'static str, multi_line: bool) -> Self {
+        Self {
+            open: quote,
+            close: quote,
+            kind: TokenKind::String,
+            escape: Some('\\'),
+            multi_line,
+            max_len: None,
+        }
+    }
+
+    /// a string with no escapes, like a shell 'string' or a markdown `code span`
+    const fn raw(open: &'static str, close: &'static str, multi_line: bool) -> Self {
+        Self {
+            open,
+            close,
+            kind: TokenKind::String,
+            escape: None,
+            multi_line,
+            max_len: None,
+        }
+    }
+
+    const fn block_comment(open: &'static str, close: &'static str) -> Self {
+        Self {
+            open,
+            close,
+            kind: TokenKind::Comment,
+            escape: None,
+            multi_line: true,
+            max_len: None,
+        }
+    }
+}
+
+const RUST: Language = Language {
+    name: "rust",
+    extensions: &["rs"],
+    file_names: &[],
+    line_comments: &["//"],
+    delimited: &[
+        Delimited::block_comment("/*", "*/"),
+        Delimited::string("\"", true),
+        Delimited {
+            open: "'",
+            close: "'",
+            kind: TokenKind::String,
+            escape: Some('\\'),
+            multi_line: false,
+            max_len: Some(10), // '\u{10ffff}'
+        },
+    ],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "static", "struct", "super", "trait", "type", "unsafe",
+        "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    constants: &["true", "false"],
+    capitalized_types: true,
+    line_starts: &[("#[", TokenKind::Heading), ("#![", TokenKind::Heading)],
+    variable_prefix: None,
+};
+
+const TOML: Language = Language {
+    name: "toml",
+    extensions: &["toml"],
+    file_names: &["Cargo.lock"],
+    line_comments: &["#"],
+    delimited: &[
+        Delimited::string("\"\"\"", true),
+        Delimited::raw("'''", "'''", true),
+        Delimited::string("\"", false),
+        Delimited::raw("'", "'", false),
+    ],
+    keywords: &[],
+    types: &[],
+    constants: &["true", "false", "inf", "nan"],
+    capitalized_types: false,
+    line_starts: &[("[", TokenKind::Heading)],
+    variable_prefix: None,
+};
+
+const MARKDOWN: Language = Language {
+    name: "markdown",
+    extensions: &["md", "markdown"],
+    file_names: &[],
+    line_comments: &[],
+    delimited: &[
+        Delimited::raw("```", "```", true),
+        Delimited::raw("`", "`", false),
+        Delimited::block_comment("<!--", "-->"),
+    ],
+    keywords: &[],
+    types: &[],
+    constants: &[],
+    capitalized_types: false,
+    line_starts: &[("#", TokenKind::Heading), (">", TokenKind::Comment)],
+    variable_prefix: None,
+};
+
+const JSON: Language = Language {
+    name: "json",
+    extensions: &["json"],
+    file_names: &[],
+    line_comments: &[],
+    delimited: &[Delimited::string("\"", false)],
+    keywords: &[],
+    types: &[],
+    constants: &["true", "false", "null"],
+    capitalized_types: false,
+    line_starts: &[],
+    variable_prefix: None,
+};
+
+const PYTHON: Language = Language {
+    name: "python",
+    extensions: &["py", "pyi"],
+    file_names: &[],
+    line_comments: &["#"],
+    delimited: &[
+        Delimited::string("\"\"\"", true),
+        Delimited::string("'''", true),
+        Delimited::string("\"", false),
+        Delimited::string("'", false),
+    ],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield", "self",
+    ],
+    types: &[
+        "int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes", "object",
+    ],
+    constants: &["True", "False", "None"],
+    capitalized_types: true,
+    line_starts: &[("@", TokenKind::Heading)],
+    variable_prefix: None,
+};
+
+const SHELL: Language = Language {
+    name: "shell",
+    extensions: &["sh", "bash", "zsh"],
+    file_names: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+    line_comments: &["#"],
+    delimited: &[
+        Delimited::string("\"", true),
+        Delimited::raw("'", "'", true),
+    ],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "return", "local", "export", "readonly", "break", "continue", "exit",
+        "source", "set", "unset", "shift",
+    ],
+    types: &[],
+    constants: &["true", "false"],
+    capitalized_types: false,
+    line_starts: &[],
+    variable_prefix: Some('$'),
+};
+
+/// every language that can be highlighted, the first one that claims a file is used
+pub const LANGUAGES: [Language; 6] = [RUST, TOML, MARKDOWN, JSON, PYTHON, SHELL];
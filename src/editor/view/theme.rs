@@ -1,3 +1,4 @@
+use super::syntax::TokenKind;
 use crate::editor::terminal::{ScreenPosition, Terminal};
 use crossterm::cursor::SetCursorStyle;
 use crossterm::event::{read, Event, KeyCode, KeyEvent};
@@ -9,6 +10,50 @@ pub struct Theme {
     pub highlight: Color,
     pub text: Color,
     cursor_style: SetCursorStyle,
+    pub syntax: SyntaxColors,
+}
+
+/// the color of each kind of token when a file is highlighted
+#[derive(Copy, Clone)]
+pub struct SyntaxColors {
+    pub keyword: Color,
+    pub type_name: Color,
+    pub constant: Color,
+    pub number: Color,
+    pub string: Color,
+    pub comment: Color,
+    pub heading: Color,
+    pub variable: Color,
+}
+
+impl Default for SyntaxColors {
+    fn default() -> Self {
+        Self {
+            keyword: Color::Magenta,
+            type_name: Color::Cyan,
+            constant: Color::Yellow,
+            number: Color::Yellow,
+            string: Color::Green,
+            comment: Color::DarkGrey,
+            heading: Color::Blue,
+            variable: Color::Red,
+        }
+    }
+}
+
+impl SyntaxColors {
+    pub fn token_color(&self, kind: TokenKind) -> Color {
+        match kind {
+            TokenKind::Keyword => self.keyword,
+            TokenKind::Type => self.type_name,
+            TokenKind::Constant => self.constant,
+            TokenKind::Number => self.number,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Heading => self.heading,
+            TokenKind::Variable => self.variable,
+        }
+    }
 }
 
 impl Default for Theme {
@@ -19,6 +64,7 @@ impl Default for Theme {
             highlight: Color::Blue,
             text: Color::White,
             cursor_style: SetCursorStyle::DefaultUserShape,
+            syntax: SyntaxColors::default(),
         }
     }
 }
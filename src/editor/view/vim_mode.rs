@@ -1,25 +1,42 @@
-use super::clipboard_interface::ClipboardUtils;
-use super::{Search, Theme};
+use super::highlight::HighlightExit;
+use super::registers::{Register, RegisterKind, Registers};
+use super::repeat::{DotRepeat, OperatorTarget, Repeatable};
+use super::{Search, Theme, View};
 use crate::editor::Terminal;
 use crate::editor::{
     editorcommands::{
-        parse_highlight_vim_mode, ColonQueueActions, Direction, QueueInitCommand, VimColonQueue,
+        digit_value, parse_highlight_vim_mode, ColonQueueActions, ConfirmReplace, Direction,
+        InsertEntry, Operator, QueueInitCommand, SelectionKind, SetOption, ViewRow, VimColonQueue,
         VimModeCommands,
     },
     view::{
-        help::VimHelpScreen, highlight::Highlight, Buffer, Coordinate, Mode, Position,
-        ScreenOffset, Size,
+        buffer::SearchMatch,
+        gutter,
+        help::VimHelpScreen,
+        highlight::Highlight,
+        keys::KeySource,
+        motion::{CharFind, FindKind, Motion, TextObject, TextObjectKind, TextRegion},
+        search_history::SearchEntry,
+        substitute::{vim_replacement, Substitute, Substitution},
+        syntax,
+        theme::SyntaxColors,
+        wrap, Buffer, Coordinate, Mode, Position, ScreenOffset, ScreenPosition, Size,
     },
 };
-use crossterm::event::{read, Event, KeyCode, KeyEvent};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Stylize;
+use regex::RegexBuilder;
+use std::collections::VecDeque;
 use std::error::Error;
 
 enum ContinueState {
     ExitSession,
     ContinueVim,
     ContinueVimPersistError,
+    ContinueVimKeepStatus, // done, the status line it drew stays up
     InvalidCommand,
     JumpCursor(usize),
+    ExitVimMode,
 }
 
 pub struct VimMode<'a> {
@@ -27,20 +44,39 @@ pub struct VimMode<'a> {
     screen_offset: ScreenOffset,
     size: Size,
     buffer: &'a mut Buffer,
+    registers: &'a mut Registers, // owned by view so they outlive the session
+    dot_repeat: &'a mut DotRepeat,
+    scrolloff: &'a mut usize,
+    keys: KeySource,
+    last_find: Option<CharFind>, // repeated by ; and ,
+    matched_bracket: Option<Position>,
+    colors: SyntaxColors,           // taken from the theme when a session starts
+    status_message: Option<String>, // shown instead of the status line until the next command
 }
 
-impl VimMode<'_> {
+impl<'a> VimMode<'a> {
     pub fn new(
         cursor_position: Position,
         screen_offset: ScreenOffset,
         size: Size,
-        buffer: &'_ mut Buffer, // mutable reference to buffer
-    ) -> VimMode<'_> {
+        buffer: &'a mut Buffer, // mutable reference to buffer
+        registers: &'a mut Registers,
+        dot_repeat: &'a mut DotRepeat,
+        scrolloff: &'a mut usize,
+    ) -> VimMode<'a> {
         VimMode {
             cursor_position,
             screen_offset,
             size,
             buffer,
+            registers,
+            dot_repeat,
+            scrolloff,
+            keys: KeySource::default(),
+            last_find: None,
+            matched_bracket: None,
+            colors: SyntaxColors::default(),
+            status_message: None,
         }
     }
     pub fn run(
@@ -50,131 +86,408 @@ impl VimMode<'_> {
         size: &mut Size,
         theme: &Theme,
     ) -> bool {
+        self.colors = theme.syntax;
+        self.highlight_matching_bracket(theme);
         let res = self.start();
         debug_assert!(res.is_ok());
+        let mut count: Option<usize> = None;
+        let mut register: Option<char> = None;
         loop {
             let mut needs_render = false;
-            let Ok(read_event) = read() else { continue }; //skipping an error on read cursor action
+            self.keys.interrupt_replay();
+            let read_event = self.next_event();
+            self.status_message = None;
 
-            match VimModeCommands::try_from(read_event) {
-                Ok(event) => match event {
-                    VimModeCommands::Move(dir) => match dir {
-                        Direction::Right
-                        | Direction::Left
-                        | Direction::Up
-                        | Direction::Down
-                        | Direction::End
-                        | Direction::Home => {
-                            if self.move_cursor(dir) > 0 {
-                                needs_render = true;
-                            }
+            let Ok(event) = VimModeCommands::try_from(read_event) else {
+                continue; //ignoring error
+            };
+            if self.read_prefix(&event, &mut count, &mut register) {
+                continue;
+            }
+            let pending = count.take();
+            let register = register.take();
+            let before = self.cursor_position;
+
+            match event {
+                _ if event.is_movement() => needs_render = self.move_by(&event, pending),
+                VimModeCommands::Insert(_)
+                | VimModeCommands::Substitute(_)
+                | VimModeCommands::Highlight(_)
+                | VimModeCommands::ComplexCommand(_)
+                | VimModeCommands::Exit => {
+                    // if we get true back, staying in vim mode
+                    // else user is exiting the session
+                    match self.command_state(&event, pending, register, theme) {
+                        ContinueState::ContinueVimPersistError => {
+                            self.keys.replay.clear();
+                            continue;
                         }
-                        _ => continue,
-                    },
-                    VimModeCommands::JumpUp => {
-                        if self.jump_up() > 0 {
-                            needs_render = true;
+                        ContinueState::ContinueVimKeepStatus => continue,
+                        ContinueState::ContinueVim => needs_render = true,
+                        ContinueState::InvalidCommand => {
+                            self.keys.replay.clear();
+                            // if the command is invalid, render the help
+                            VimHelpScreen::render_help(&mut self.size, theme.highlight, theme.text);
                         }
-                    }
-                    VimModeCommands::JumpDown => {
-                        if self.jump_down() > 0 {
-                            needs_render = true;
+                        ContinueState::JumpCursor(line) => {
+                            needs_render = self.jump_cursor_to(line) > 0;
                         }
-                    }
-                    VimModeCommands::NewLine => {
-                        // new line
-                        self.buffer.add_new_line(&mut self.cursor_position);
-                        needs_render = true;
-                    }
-                    VimModeCommands::StartOfNextWord => {
-                        self.buffer.begining_of_next_word(&mut self.cursor_position);
-                    }
-                    VimModeCommands::EndOfCurrentWord => {
-                        self.buffer.end_of_current_word(&mut self.cursor_position);
-                    }
-                    VimModeCommands::BeginingOfCurrentWord => self
-                        .buffer
-                        .begining_of_current_word(&mut self.cursor_position),
-                    VimModeCommands::ComplexCommand(queue_command) => {
-                        // if we get true back, staying in vim mode
-                        // else user is exiting the session
-                        match self.determine_queue_command(&queue_command) {
-                            ContinueState::ContinueVimPersistError => continue,
-                            ContinueState::ContinueVim => {
-                                needs_render = true;
-                            } // no action
-                            ContinueState::InvalidCommand => {
-                                // if the command is invalid, render the help
-                                VimHelpScreen::render_help(
-                                    &mut self.size,
-                                    theme.highlight,
-                                    theme.text,
-                                );
-                            }
-                            ContinueState::JumpCursor(line) => {
-                                if self.jump_cursor_to(line) > 0 {
-                                    needs_render = true;
-                                }
-                            }
-                            ContinueState::ExitSession => return false,
+                        ContinueState::ExitSession => return false,
+                        ContinueState::ExitVimMode => {
+                            // here user is staying in terminal session
+                            // but leaving vim mode to type
+                            self.hand_back_state(cursor_position, screen_offset, size);
+                            return true;
                         }
                     }
-                    VimModeCommands::Search => {
-                        let mut search = Search::new(
-                            self.cursor_position,
-                            self.screen_offset,
-                            theme.highlight,
-                            theme.text,
-                        );
-                        search.run(
-                            &mut self.cursor_position,
-                            &mut self.screen_offset,
-                            &mut self.size,
-                            &self.buffer,
-                        );
-                        needs_render = true;
-                    }
-                    VimModeCommands::Highlight => {
-                        let mut highlight = Highlight::new(
-                            &mut self.cursor_position,
-                            self.screen_offset,
-                            &mut self.size,
-                            self.buffer,
-                        );
-                        highlight.run(theme.highlight, theme.text, parse_highlight_vim_mode);
-                        if self.resolve_displacement() > 0 {
-                            needs_render = true;
-                        } // making sure the offset is correct on a delete
-                    }
-                    VimModeCommands::Resize(new_size) => {
-                        self.resize(new_size);
-                        needs_render = true;
-                    }
-                    VimModeCommands::Exit => {
-                        // here user is staying in terminal session
-                        // but exiting vim mode
-                        self.hand_back_state(cursor_position, screen_offset, size);
-                        return true;
-                    }
-                    VimModeCommands::Paste => {
-                        self.add_from_clipboard();
+                }
+                VimModeCommands::DeleteChar(_)
+                | VimModeCommands::ReplaceChar
+                | VimModeCommands::Overwrite
+                | VimModeCommands::Join
+                | VimModeCommands::ToggleCase
+                | VimModeCommands::Shift(_) => {
+                    if self.edit_at_cursor(&event, pending, register) {
                         needs_render = true;
+                    } else {
+                        self.keys.replay.clear();
                     }
-                    VimModeCommands::NoAction => {
-                        VimHelpScreen::render_help(&mut self.size, theme.highlight, theme.text);
-                        needs_render = true;
-                    } // skipping other
-                },
-                Err(_) => continue, //ignoring error
+                }
+                VimModeCommands::Search => {
+                    self.open_search(theme);
+                    needs_render = true;
+                }
+                VimModeCommands::Resize(new_size) => {
+                    self.resize(new_size);
+                    needs_render = true;
+                }
+                VimModeCommands::Paste
+                | VimModeCommands::PasteBefore
+                | VimModeCommands::Repeat
+                | VimModeCommands::Undo
+                | VimModeCommands::Redo => {
+                    needs_render = self.put_repeat_or_undo(&event, pending, register);
+                }
+                VimModeCommands::Record | VimModeCommands::Replay => {
+                    self.record_or_replay(&event, pending.unwrap_or(1));
+                }
+                VimModeCommands::NoAction => {
+                    VimHelpScreen::render_help(&mut self.size, theme.highlight, theme.text);
+                    needs_render = true;
+                } // skipping other
+                // the movements are matched first, a register is read with the prefix
+                _ => {}
+            }
+            // a motion that can not move ends a running macro, like the end of the file
+            if event.is_motion() && self.cursor_position == before {
+                self.keys.replay.clear();
             }
-            if needs_render {
-                let res = self.render_proc();
-                debug_assert!(res.is_ok());
+            self.finish_command(needs_render, theme);
+        }
+    }
+
+    /// digits build up a count for the next command and "x names its register
+    /// returns true when the key was one of them, the command is still to come
+    fn read_prefix(
+        &mut self,
+        command: &VimModeCommands,
+        count: &mut Option<usize>,
+        register: &mut Option<char>,
+    ) -> bool {
+        match *command {
+            // a 0 with no count pending is still a move to the start of the line
+            VimModeCommands::Digit(digit) if digit > 0 || count.is_some() => {
+                *count = Some(
+                    count
+                        .unwrap_or_default()
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                true
             }
+            // the count is kept
+            VimModeCommands::Register => {
+                *register = self.read_char().filter(|c| Registers::is_valid_name(*c));
+                true
+            }
+            _ => false,
+        }
+    }
 
-            let res = self.cursor_and_status();
-            debug_assert!(res.is_ok());
+    /// the commands that can leave vim mode or end the session, the state says what run does next
+    fn command_state(
+        &mut self,
+        command: &VimModeCommands,
+        count: Option<usize>,
+        register: Option<char>,
+        theme: &Theme,
+    ) -> ContinueState {
+        match command {
+            VimModeCommands::Insert(entry) => {
+                // place the cursor for typing, then leave vim mode
+                self.enter_insert(*entry);
+                self.dot_repeat.start_typing(Repeatable::Insert(*entry));
+                ContinueState::ExitVimMode
+            }
+            VimModeCommands::Exit => {
+                self.dot_repeat
+                    .start_typing(Repeatable::Insert(InsertEntry::Before));
+                ContinueState::ExitVimMode
+            }
+            VimModeCommands::Substitute(motion) => {
+                let target = OperatorTarget::Motion(*motion);
+                self.run_operator(Operator::Change, target, count, register)
+            }
+            VimModeCommands::Highlight(kind) => self.open_highlight(*kind, register, theme),
+            VimModeCommands::ComplexCommand(queue_command) => {
+                self.determine_queue_command(queue_command, count, register, theme)
+            }
+            _ => ContinueState::ContinueVim,
+        }
+    }
+
+    /// every vim command is its own undo step, then the screen catches up with it
+    fn finish_command(&mut self, mut needs_render: bool, theme: &Theme) {
+        self.buffer.history.seal(self.cursor_position);
+        // the gutter widens as the buffer grows past a power of ten
+        if self.screen_offset.fit_gutter(self.buffer.len()) {
+            self.resolve_displacement();
+            needs_render = true;
+        }
+        let res = if needs_render {
+            self.render_proc()
+        } else {
+            self.render_relative_numbers()
+        };
+        debug_assert!(res.is_ok());
+        self.highlight_matching_bracket(theme);
+
+        let res = self.cursor_and_status();
+        debug_assert!(res.is_ok());
+    }
+
+    /// the motions and scrolls, returns true when the cursor or the view moved
+    fn move_by(&mut self, command: &VimModeCommands, count: Option<usize>) -> bool {
+        let times = count.unwrap_or(1);
+        match *command {
+            VimModeCommands::Move(dir) => match dir {
+                Direction::Right
+                | Direction::Left
+                | Direction::Up
+                | Direction::Down
+                | Direction::End
+                | Direction::Home => self.move_cursor(dir, times) > 0,
+                _ => false,
+            },
+            VimModeCommands::Digit(_) => self.move_cursor(Direction::Home, 1) > 0,
+            VimModeCommands::JumpUp => self.jump_up(times) > 0,
+            VimModeCommands::JumpDown => self.jump_down(times) > 0,
+            VimModeCommands::FindChar(key) => {
+                let Some(find) = self
+                    .read_char()
+                    .and_then(|target| CharFind::new(key, target))
+                else {
+                    return false;
+                };
+                self.last_find = Some(find);
+                self.find_in_line(find, times, false) > 0
+            }
+            VimModeCommands::RepeatFind(reverse) => {
+                let Some(find) = self.last_find else {
+                    return false;
+                };
+                let find = if reverse { find.reversed() } else { find };
+                self.find_in_line(find, times, true) > 0
+            }
+            VimModeCommands::MatchingPair => self.jump_to_pair() > 0,
+            VimModeCommands::ViewRow(row) => self.move_to_view_row(row, count) > 0,
+            VimModeCommands::Scroll(down) => {
+                self.scroll_view(down, times);
+                true
+            }
+            VimModeCommands::ScrollCursor => {
+                let Some(row) = self
+                    .read_char()
+                    .filter(|c| matches!(c, 't' | 'z' | 'b'))
+                    .and_then(|c| ViewRow::try_from(c).ok())
+                else {
+                    return false;
+                };
+                self.place_cursor_line(row);
+                true
+            }
+            VimModeCommands::StartOfNextWord => {
+                self.repeat_motion(times, Buffer::begining_of_next_word) > 0
+            }
+            VimModeCommands::EndOfCurrentWord => {
+                self.repeat_motion(times, Buffer::end_of_current_word) > 0
+            }
+            VimModeCommands::BeginingOfCurrentWord => {
+                self.repeat_motion(times, Buffer::begining_of_current_word) > 0
+            }
+            VimModeCommands::Paragraph(forward) => {
+                let motion = if forward {
+                    Buffer::find_next_paragraph
+                } else {
+                    Buffer::find_prev_paragraph
+                };
+                self.repeat_motion(times, motion) > 0
+            }
+            VimModeCommands::Sentence(forward) => {
+                let motion = if forward {
+                    Buffer::find_next_sentence
+                } else {
+                    Buffer::find_prev_sentence
+                };
+                self.repeat_motion(times, motion) > 0
+            }
+            VimModeCommands::SearchNext(reverse) => self.search_next(reverse, times) > 0,
+            VimModeCommands::SearchWord(forward) => self.search_word(forward, times) > 0,
+            _ => false,
+        }
+    }
+
+    /// / in vim mode, the search prompt over the vim mode state
+    fn open_search(&mut self, theme: &Theme) {
+        let mut search = Search::new(
+            self.cursor_position,
+            self.screen_offset,
+            theme.highlight,
+            theme.text,
+        );
+        let saved = search.run(
+            &mut self.cursor_position,
+            &mut self.screen_offset,
+            &mut self.size,
+            self.buffer,
+            self.registers.search_history(),
+            &mut self.keys,
+        );
+        self.report_history_error(saved);
+    }
+
+    fn report_history_error(&mut self, saved: Result<(), std::io::Error>) {
+        if let Err(err) = saved {
+            self.status_message = Some(format!("Search history not saved: {err}"));
+        }
+    }
+
+    /// v V Ctrl-v, highlight text then act on it
+    /// I or A on a block leaves vim mode to type on its top row
+    fn open_highlight(
+        &mut self,
+        kind: SelectionKind,
+        register: Option<char>,
+        theme: &Theme,
+    ) -> ContinueState {
+        if let Err(message) = Registers::writable(register) {
+            self.command_status_line(&message);
+            return ContinueState::ContinueVimPersistError;
+        }
+        let mut highlight = Highlight::new(
+            &mut self.cursor_position,
+            self.screen_offset,
+            &mut self.size,
+            self.buffer,
+            kind,
+        );
+        let exit = highlight.run(
+            theme.highlight,
+            theme.text,
+            parse_highlight_vim_mode,
+            &mut self.keys,
+        );
+        // a register named inside the highlight wins over one named before it
+        let register = highlight.register().or(register);
+        match exit {
+            HighlightExit::Copy(selected) => {
+                let res = self.registers.yank(register, selected);
+                self.status_message = res.err();
+            }
+            HighlightExit::Delete(selected) => {
+                let res = self.registers.delete(register, selected);
+                self.status_message = res.err();
+            }
+            HighlightExit::BlockInsert { at, rows, pad } => {
+                // type on the top row, the other rows get the text once typing ends
+                self.cursor_position.set_position(at);
+                self.cursor_position.max_width = at.width;
+                self.dot_repeat
+                    .start_typing(Repeatable::BlockInsert { at, rows, pad });
+                self.resolve_displacement();
+                return ContinueState::ExitVimMode;
+            }
+            HighlightExit::Indent { rows, right } => {
+                self.dot_repeat.set(Repeatable::Shift { rows, right });
+            }
+            HighlightExit::Revert => {}
+        }
+        // making sure the offset is correct on a delete
+        self.resolve_displacement();
+        ContinueState::ContinueVim
+    }
+
+    /// p P . u Ctrl-r, returns true when the buffer changed
+    fn put_repeat_or_undo(
+        &mut self,
+        command: &VimModeCommands,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> bool {
+        let times = count.unwrap_or(1);
+        match command {
+            VimModeCommands::Paste | VimModeCommands::PasteBefore => {
+                let before = matches!(command, VimModeCommands::PasteBefore);
+                self.put(register, before, times);
+                self.dot_repeat.set(Repeatable::Put {
+                    register,
+                    before,
+                    times,
+                });
+                true
+            }
+            VimModeCommands::Repeat => self.repeat_change(count),
+            VimModeCommands::Undo | VimModeCommands::Redo => {
+                let mut changed = false;
+                for _ in 0..times {
+                    let stepped = if matches!(command, VimModeCommands::Undo) {
+                        self.buffer.undo(&mut self.cursor_position)
+                    } else {
+                        self.buffer.redo(&mut self.cursor_position)
+                    };
+                    if !stepped {
+                        break;
+                    }
+                    changed = true;
+                }
+                self.resolve_displacement();
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    /// q starts recording a macro into the register named next, or stops the recording
+    /// @ runs the macro in the register named next
+    fn record_or_replay(&mut self, command: &VimModeCommands, times: usize) {
+        if matches!(command, VimModeCommands::Record) {
+            if self.keys.recording.is_some() {
+                self.stop_recording();
+            } else if let Some(name) = self.read_char().filter(char::is_ascii_alphanumeric) {
+                self.keys.recording = Some((name, Vec::new()));
+            }
+            return;
         }
+        let Some(keys) = self
+            .read_char()
+            .and_then(|name| self.registers.macro_keys(name))
+        else {
+            return;
+        };
+        // the macro goes ahead of any keys still queued, so a macro can run another
+        let mut queued: VecDeque<Event> = (0..times).flat_map(|_| keys.clone()).collect();
+        queued.append(&mut self.keys.replay);
+        self.keys.replay = queued;
     }
 
     fn jump_cursor_to(&mut self, line: usize) -> usize {
@@ -182,26 +495,28 @@ impl VimMode<'_> {
         self.resolve_displacement()
     }
 
-    fn jump_down(&mut self) -> usize {
+    fn jump_down(&mut self, times: usize) -> usize {
         self.cursor_position.height = std::cmp::min(
-            self.cursor_position.height.saturating_add(10),
+            self.cursor_position
+                .height
+                .saturating_add(times.saturating_mul(10)),
             self.buffer.len().saturating_sub(1),
         );
         self.resolve_displacement()
     }
 
-    fn jump_up(&mut self) -> usize {
-        self.cursor_position.height = self.cursor_position.height.saturating_sub(10);
+    fn jump_up(&mut self, times: usize) -> usize {
+        self.cursor_position.height = self
+            .cursor_position
+            .height
+            .saturating_sub(times.saturating_mul(10));
         self.resolve_displacement()
     }
 
     #[inline]
     fn cursor_and_status(&self) -> Result<(), Box<dyn Error>> {
         self.status_line()?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
+        Terminal::move_cursor_to(self.screen_cursor())?;
 
         Terminal::show_cursor()?;
         Terminal::execute()?;
@@ -210,14 +525,24 @@ impl VimMode<'_> {
 
     fn start(&self) -> Result<(), Box<dyn Error>> {
         self.status_line()?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
+        Terminal::move_cursor_to(self.screen_cursor())?;
         Terminal::execute()?;
         Ok(())
     }
 
+    /// relative numbers follow the cursor even when the text stays put
+    fn render_relative_numbers(&self) -> Result<(), Box<dyn Error>> {
+        if self.screen_offset.numbers.relative {
+            gutter::render_labels(
+                self.buffer,
+                &self.screen_offset,
+                &self.size,
+                self.cursor_position.height,
+            )?;
+        }
+        Ok(())
+    }
+
     #[inline]
     fn render_proc(&self) -> Result<(), Box<dyn Error>> {
         Terminal::hide_cursor()?;
@@ -228,20 +553,77 @@ impl VimMode<'_> {
         Ok(())
     }
 
-    fn add_from_clipboard(&mut self) {
-        if let Ok(paste_text) = ClipboardUtils::get_text_from_clipboard() {
-            self.buffer
-                .add_text_from_clipboard(&paste_text, &mut self.cursor_position);
+    /// put a register after, or before, the cursor `times` times
+    /// whole lines go below or above the cursor line
+    fn put(&mut self, register: Option<char>, before: bool, times: usize) {
+        let Some(register) = self.registers.get(register) else {
+            return;
+        };
+        match register.kind {
+            RegisterKind::Lines => {
+                let text = vec![register.text.as_str(); times].join("\n");
+                let line = if before || self.buffer.is_empty() {
+                    self.cursor_position.height
+                } else {
+                    self.cursor_position.height.saturating_add(1)
+                };
+                self.buffer.insert_lines(line, &text);
+                self.cursor_position.height =
+                    std::cmp::min(line, self.buffer.len().saturating_sub(1));
+                self.cursor_position.width =
+                    self.buffer.first_non_blank(self.cursor_position.height);
+            }
+            RegisterKind::Block => {
+                let line_len = self.buffer.line(self.cursor_position.height).len();
+                let column = if before {
+                    self.cursor_position.width
+                } else {
+                    std::cmp::min(self.cursor_position.width.saturating_add(1), line_len)
+                };
+                let rows: Vec<String> = register
+                    .text
+                    .split('\n')
+                    .map(|row| row.repeat(times))
+                    .collect();
+                let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+                self.buffer
+                    .insert_block(self.cursor_position.height, column, &rows, true);
+                self.cursor_position.width = column;
+            }
+            RegisterKind::Chars => {
+                let mut at = self.cursor_position;
+                let line_len = self.buffer.line(at.height).len();
+                if !before && line_len > 0 {
+                    at.width = std::cmp::min(at.width.saturating_add(1), line_len);
+                }
+                let end = self.buffer.insert_text(&at, &register.text.repeat(times));
+                // the cursor lands on the last char put
+                self.cursor_position.set_position(Position {
+                    width: end.width.saturating_sub(1),
+                    ..end
+                });
+            }
         }
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement();
     }
 
     #[inline]
     fn status_line(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(message) = &self.status_message {
+            Terminal::render_status_message(&self.size, message)?;
+            return Ok(());
+        }
+        if let Some((name, _)) = self.keys.recording {
+            Terminal::render_status_message(&self.size, &format!("recording @{name}"))?;
+            return Ok(());
+        }
         Terminal::render_status_line(
             &Mode::Vim,
             self.buffer.is_saved,
             &self.size,
             self.buffer.filename.as_deref(),
+            self.buffer.format,
             Some((
                 self.cursor_position.height.saturating_add(1),
                 self.buffer.len(),
@@ -254,7 +636,12 @@ impl VimMode<'_> {
         self.size = new_size;
     }
 
-    fn hand_back_state(&self, pos: &mut Position, offset: &mut ScreenOffset, size: &mut Size) {
+    fn hand_back_state(&mut self, pos: &mut Position, offset: &mut ScreenOffset, size: &mut Size) {
+        // leaving vim mode ends a recording
+        self.stop_recording();
+        // text typed outside vim mode is kept for dot repeat
+        self.buffer.history.start_typing();
+        self.buffer.history.seal(self.cursor_position);
         *pos = self.cursor_position;
         *offset = self.screen_offset;
         if *size != self.size {
@@ -263,48 +650,72 @@ impl VimMode<'_> {
     }
 
     fn render(&self) -> Result<(), Box<dyn Error>> {
-        #[allow(clippy::integer_division)]
-        for current_row in self.screen_offset.height
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(self.size.height)
-                .saturating_sub(1)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
-
-            if let Some(line) = self.buffer.text.get(current_row) {
-                Terminal::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
-                    ),
-                )?;
-            } else if self.buffer.is_empty() && (current_row == self.size.height / 3) {
-                Terminal::render_line(
-                    relative_row,
-                    Terminal::get_welcome_message(&self.size, &self.screen_offset),
-                )?;
-            } else {
-                Terminal::render_line(relative_row, "~")?;
-            }
+        wrap::render_text(
+            self.buffer,
+            &self.screen_offset,
+            &self.size,
+            self.screen_offset.height,
+            self.cursor_position.height,
+            &self.colors,
+        )?;
+        Ok(())
+    }
+
+    /// draw a buffer line on a screen row, colored by the syntax of the file
+    fn render_buffer_line(&self, row: usize, index: usize) -> Result<(), Box<dyn Error>> {
+        let offset = &self.screen_offset;
+        if offset.wrap {
+            // the line may have gained or lost a row, moving every line below it
+            return self.render();
         }
+        gutter::start_row(row, index, self.cursor_position.height, offset)?;
+        syntax::print_line(
+            self.buffer,
+            index,
+            offset.width..offset.width.saturating_add(offset.text_width(&self.size)),
+            &self.colors,
+        )?;
         Ok(())
     }
 
+    /// where the cursor is drawn
+    fn screen_cursor(&self) -> ScreenPosition {
+        wrap::screen_position(
+            self.buffer,
+            &self.cursor_position,
+            &self.screen_offset,
+            &self.size,
+        )
+    }
+
     // handing back view delta
     #[inline]
-    fn move_cursor(&mut self, dir: Direction) -> usize {
+    fn move_cursor(&mut self, dir: Direction, times: usize) -> usize {
         if self.buffer.is_empty() {
             self.cursor_position.snap_left();
             self.cursor_position.page_up();
             0
-        } else {
+        } else if matches!(dir, Direction::End) {
+            // a count on $ moves to the end of a later line
+            self.repeat_motion(times.saturating_sub(1), |buffer, pos| {
+                Direction::Down.move_cursor(pos, buffer);
+            });
             self.move_and_resolve(dir)
+        } else {
+            self.repeat_motion(times, |buffer, pos| dir.move_cursor(pos, buffer))
         }
     }
 
+    /// apply a motion `times` times
+    /// handing back view delta
+    fn repeat_motion<F>(&mut self, times: usize, motion: F) -> usize
+    where
+        F: Fn(&Buffer, &mut Position),
+    {
+        self.cursor_position = self.motion_target(times, motion);
+        self.resolve_displacement()
+    }
+
     fn resolve_displacement(&mut self) -> usize {
         let dis =
             self.cursor_position
@@ -324,17 +735,39 @@ impl VimMode<'_> {
                 );
             }
         }
+        if self.screen_offset.keep_margin(
+            &self.cursor_position,
+            &self.size,
+            *self.scrolloff,
+            self.buffer.len(),
+        ) {
+            return std::cmp::max(dis, 1);
+        }
+        if wrap::keep_in_view(
+            self.buffer,
+            &self.cursor_position,
+            &mut self.screen_offset,
+            &self.size,
+        ) {
+            return std::cmp::max(dis, 2);
+        }
         dis
     }
 
     #[inline]
-    fn determine_queue_command(&mut self, command: &QueueInitCommand) -> ContinueState {
+    fn determine_queue_command(
+        &mut self,
+        command: &QueueInitCommand,
+        count: Option<usize>,
+        register: Option<char>,
+        theme: &Theme,
+    ) -> ContinueState {
         // propogate up the result of the typed command
         // otherwise we are staying in terminal session, thus true
         match command {
-            QueueInitCommand::Colon => self.queue_colon(),
+            QueueInitCommand::Colon => self.queue_colon(theme),
             QueueInitCommand::PageUp => {
-                let valid = self.queue_page_up();
+                let valid = self.queue_page_up(count);
                 // stay in vim mode
                 if valid {
                     ContinueState::ContinueVim
@@ -343,7 +776,7 @@ impl VimMode<'_> {
                 }
             }
             QueueInitCommand::PageDown => {
-                let valid = self.queue_page_down();
+                let valid = self.queue_page_down(count);
                 // stay in vim mode
                 if valid {
                     ContinueState::ContinueVim
@@ -351,26 +784,11 @@ impl VimMode<'_> {
                     ContinueState::InvalidCommand
                 }
             }
-            QueueInitCommand::Delete => {
-                // delete the block associated with the next key press
-                if self.queue_delete() {
-                    ContinueState::ContinueVim
-                } else {
-                    ContinueState::InvalidCommand
-                }
-            }
-            QueueInitCommand::Yank => {
-                // copy the block associated with the next key press
-                if self.queue_yank() {
-                    ContinueState::ContinueVim
-                } else {
-                    ContinueState::InvalidCommand
-                }
-            }
+            QueueInitCommand::Operator(operator) => self.queue_operator(*operator, count, register),
         }
     }
 
-    fn queue_colon(&mut self) -> ContinueState {
+    fn queue_colon(&mut self, theme: &Theme) -> ContinueState {
         // return true if we are staying in vim mode after executing command
         // return false if we are ending the terminal session from here
         // in the case the command executes, propogate up the state result
@@ -378,7 +796,7 @@ impl VimMode<'_> {
         self.command_status_line(&queue);
 
         loop {
-            let Ok(read_event) = read() else { continue }; //skipping an error on read cursor action
+            let read_event = self.next_event();
             match VimColonQueue::try_from(read_event) {
                 Ok(event) => match event {
                     VimColonQueue::New(c) => queue.push(c), //queue any of these commands
@@ -395,7 +813,7 @@ impl VimMode<'_> {
                             continue;
                         };
                         // execute action
-                        return self.eval_colon_queue(&mapped);
+                        return self.eval_colon_queue(&mapped, theme);
                     }
                     VimColonQueue::Resize(size) => self.resize(size),
                     VimColonQueue::Other => continue,
@@ -406,6 +824,11 @@ impl VimMode<'_> {
         }
     }
 
+    /// a message left by the view, shown once vim mode has started
+    pub fn report(&self, message: &str) {
+        self.command_status_line(message);
+    }
+
     fn command_status_line(&self, message: &str) {
         let render =
             Terminal::render_line(self.size.height.saturating_sub(2), format!(":{message}"));
@@ -413,14 +836,17 @@ impl VimMode<'_> {
         debug_assert!(render.is_ok() & flush.is_ok());
     }
 
-    fn eval_colon_queue(&mut self, queue: &[ColonQueueActions]) -> ContinueState {
+    fn eval_colon_queue(&mut self, queue: &[ColonQueueActions], theme: &Theme) -> ContinueState {
         // return true if we are staying in vim mode after executing the command
         // false if we are ending our terminal session
         match queue.len() {
-            1 => match queue[0] {
+            1 => match &queue[0] {
                 ColonQueueActions::Write => {
                     // execute and stay in vim mode
-                    self.buffer.save();
+                    if let Err(err) = self.buffer.save() {
+                        self.command_status_line(&format!("save failed: {err}"));
+                        return ContinueState::ContinueVimPersistError;
+                    }
                 }
                 ColonQueueActions::Quit => {
                     // exit session
@@ -434,17 +860,37 @@ impl VimMode<'_> {
                     self.command_status_line("Invalid command");
                     return ContinueState::ContinueVimPersistError;
                 }
+                ColonQueueActions::Set(option) => match *option {
+                    SetOption::FileFormat(line_ending) => self.buffer.set_line_ending(line_ending),
+                    SetOption::ScrollOff(lines) => {
+                        *self.scrolloff = lines;
+                        self.resolve_displacement();
+                    }
+                    // the gutter is sized to the new numbers before the next render
+                    SetOption::Number(on) => self.screen_offset.numbers.absolute = on,
+                    SetOption::RelativeNumber(on) => self.screen_offset.numbers.relative = on,
+                    SetOption::Wrap(on) => {
+                        self.screen_offset.wrap = on;
+                        self.screen_offset.width = 0;
+                        self.resolve_displacement();
+                    }
+                },
                 ColonQueueActions::Jump(line) => {
                     // jump to the line
                     // continue state is continue vim
                     // figure out where to do the rendering if the cursor moves off screen
-                    return ContinueState::JumpCursor(line);
+                    return ContinueState::JumpCursor(*line);
                 }
+                ColonQueueActions::Substitute(command) => return self.substitute(command, theme),
             },
             2 => {
                 match queue {
                     [ColonQueueActions::Write, ColonQueueActions::Quit] => {
-                        self.buffer.save();
+                        // only exit once the file is safely on disk
+                        if let Err(err) = self.buffer.save() {
+                            self.command_status_line(&format!("save failed: {err}"));
+                            return ContinueState::ContinueVimPersistError;
+                        }
                         // exit terminal session
                         return ContinueState::ExitSession;
                     }
@@ -464,6 +910,14 @@ impl VimMode<'_> {
         if let Ok(line) = string_queue.parse::<usize>() {
             return Ok(vec![ColonQueueActions::Jump(line)]);
         }
+        if let Some(option) = string_queue.strip_prefix("set ") {
+            return Ok(vec![ColonQueueActions::Set(SetOption::try_from(
+                option.trim(),
+            )?)]);
+        }
+        if let Ok(command) = Substitute::try_from(string_queue) {
+            return Ok(vec![ColonQueueActions::Substitute(command)]);
+        }
         let mut res: Vec<ColonQueueActions> = Vec::with_capacity(6);
         for c in string_queue.chars() {
             let mapped_val = ColonQueueActions::try_from(c)?;
@@ -473,29 +927,213 @@ impl VimMode<'_> {
         Ok(res)
     }
 
-    fn queue_page_up(&mut self) -> bool {
+    /// :s, replace the matches of a pattern on a range of lines
+    /// with the c flag each match is shown and confirmed first
+    fn substitute(&mut self, command: &Substitute, theme: &Theme) -> ContinueState {
+        let Ok(pattern) = RegexBuilder::new(&command.pattern)
+            .case_insensitive(command.ignore_case)
+            .build()
+        else {
+            self.command_status_line(&format!("invalid pattern: {}", command.pattern));
+            return ContinueState::ContinueVimPersistError;
+        };
+        let lines = command
+            .range
+            .lines(self.cursor_position.height, self.buffer.len());
+        let mut substitution = Substitution::new(
+            pattern,
+            vim_replacement(&command.replacement),
+            lines,
+            command.global,
+        );
+        let mut confirm = command.confirm;
+        while let Some(hit) = substitution.next_hit(self.buffer) {
+            let answer = if confirm {
+                self.confirm_replace(&hit, &command.replacement, theme)
+            } else {
+                ConfirmReplace::Yes
+            };
+            match answer {
+                ConfirmReplace::Yes => substitution.replace(self.buffer, &hit),
+                ConfirmReplace::No => substitution.skip(&hit),
+                ConfirmReplace::All => {
+                    confirm = false;
+                    substitution.replace(self.buffer, &hit);
+                }
+                ConfirmReplace::Last => {
+                    substitution.replace(self.buffer, &hit);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        let Some(last) = substitution.last else {
+            let res = self.render_proc();
+            debug_assert!(res.is_ok());
+            self.command_status_line(&format!("pattern not found: {}", command.pattern));
+            return ContinueState::ContinueVimPersistError;
+        };
+        // the cursor goes to the start of the last line changed
+        self.cursor_position.height = last.height;
+        self.cursor_position.width = self.buffer.first_non_blank(last.height);
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement();
+        // the report would be drawn over by the render after the command, it is done here
+        self.buffer.history.seal(self.cursor_position);
+        let res = self.render_proc();
+        self.command_status_line(&substitution.report());
+        let status = self.cursor_and_status();
+        debug_assert!(res.is_ok() & status.is_ok());
+        ContinueState::ContinueVimKeepStatus
+    }
+
+    /// show a match and ask whether to replace it
+    fn confirm_replace(
+        &mut self,
+        hit: &SearchMatch,
+        replacement: &str,
+        theme: &Theme,
+    ) -> ConfirmReplace {
+        self.cursor_position.set_position(hit.start);
+        self.cursor_position.max_width = hit.start.width;
+        loop {
+            self.resolve_displacement();
+            let res = self.render_hit(hit, replacement, theme);
+            debug_assert!(res.is_ok());
+            match ConfirmReplace::try_from(self.next_event()) {
+                Ok(ConfirmReplace::Resize(size)) => self.resize(size),
+                Ok(ConfirmReplace::NoAction) | Err(_) => {}
+                Ok(answer) => return answer,
+            }
+        }
+    }
+
+    fn render_hit(
+        &self,
+        hit: &SearchMatch,
+        replacement: &str,
+        theme: &Theme,
+    ) -> Result<(), Box<dyn Error>> {
+        self.render_proc()?;
+        let line = self.buffer.line(hit.start.height);
+        let width = self.screen_offset.text_width(&self.size);
+        // only the part of the match on the row it starts on is lit
+        let (left, right) = if self.screen_offset.wrap {
+            wrap::line_rows(&line, width)
+                .into_iter()
+                .rfind(|row| row.start <= hit.start.width)
+                .map(|row| (row.start, row.end))
+                .unwrap_or_default()
+        } else {
+            let left = self.screen_offset.width;
+            (left, left.saturating_add(width))
+        };
+        let start = hit.start.width.clamp(left, right);
+        let text = line
+            .get_line_subset(start..hit.end.clamp(start, right))
+            .to_string();
+        Terminal::move_cursor_to(wrap::screen_position(
+            self.buffer,
+            &Position {
+                width: start,
+                ..hit.start
+            },
+            &self.screen_offset,
+            &self.size,
+        ))?;
+        Terminal::print_styled(text.with(theme.text).on(theme.highlight))?;
+        self.command_status_line(&format!("replace with {replacement} (y/n/a/q/l)?"));
+        self.cursor_and_status()
+    }
+
+    /// n and N, move to the next match of the last search, wrapping around the buffer
+    /// a search made with # goes up the buffer, N goes the other way
+    /// handing back view delta
+    fn search_next(&mut self, reverse: bool, times: usize) -> usize {
+        let history = self.registers.search_history();
+        let Some(entry) = history.last() else {
+            self.command_status_line("no previous search");
+            return 0;
+        };
+        let backward = history.backward != reverse;
+        let query = entry.query.clone();
+        let hits = entry
+            .pattern()
+            .map(|pattern| self.buffer.search(&pattern))
+            .unwrap_or_default();
+        let (Some(first), Some(last)) = (hits.first(), hits.last()) else {
+            self.command_status_line(&format!("pattern not found: {query}"));
+            return 0;
+        };
+        let at = |pos: &Position| (pos.height, pos.width);
+        let mut target = self.cursor_position;
+        for _ in 0..times {
+            let from = at(&target);
+            target = if backward {
+                hits.iter()
+                    .rev()
+                    .find(|hit| at(&hit.start) < from)
+                    .unwrap_or(last)
+                    .start
+            } else {
+                hits.iter()
+                    .find(|hit| at(&hit.start) > from)
+                    .unwrap_or(first)
+                    .start
+            };
+        }
+        self.cursor_position.set_position(target);
+        self.cursor_position.max_width = target.width;
+        self.resolve_displacement()
+    }
+
+    /// search for the word under or after the cursor as a whole word, * goes down and # up
+    /// handing back view delta
+    fn search_word(&mut self, forward: bool, times: usize) -> usize {
+        let Some(word) = self.buffer.keyword_at(&self.cursor_position) else {
+            return 0;
+        };
+        let entry = SearchEntry {
+            query: word,
+            whole_word: true,
+            ..SearchEntry::default()
+        };
+        let saved = self.registers.search_history().add(entry, !forward);
+        self.report_history_error(saved);
+        self.search_next(false, times)
+    }
+
+    fn queue_page_up(&mut self, count: Option<usize>) -> bool {
         // bool propogates up an invalid complex command
-        let event = Self::wait_for_successful_event();
+        let event = self.next_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
-            if matches!(code, KeyCode::Char('g')) {
-                //only handling if gg, otherwise skip
-                self.move_and_resolve(Direction::PageUp);
-                true
-            } else {
-                false
+            match code {
+                KeyCode::Char('g') => {
+                    self.page_or_jump(Direction::PageUp, count);
+                    true
+                }
+                KeyCode::Char('j') => {
+                    self.move_screen_rows(true, count.unwrap_or(1));
+                    true
+                }
+                KeyCode::Char('k') => {
+                    self.move_screen_rows(false, count.unwrap_or(1));
+                    true
+                }
+                _ => false,
             }
         } else {
             false
         }
     }
 
-    fn queue_page_down(&mut self) -> bool {
+    fn queue_page_down(&mut self, count: Option<usize>) -> bool {
         // bool propogates up an invalid complex command
-        let event = Self::wait_for_successful_event();
+        let event = self.next_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
             if matches!(code, KeyCode::Char('G')) {
                 // only handling if GG otherwise skip
-                self.move_and_resolve(Direction::PageDown);
+                self.page_or_jump(Direction::PageDown, count);
                 true
             } else {
                 false
@@ -505,85 +1143,866 @@ impl VimMode<'_> {
         }
     }
 
+    /// gj and gk, move by screen rows so a wrapped line is walked one row at a time
+    /// without wrap they are j and k
+    fn move_screen_rows(&mut self, down: bool, times: usize) -> usize {
+        if !self.screen_offset.wrap {
+            let dir = if down { Direction::Down } else { Direction::Up };
+            return self.move_cursor(dir, times);
+        }
+        let width = self.screen_offset.text_width(&self.size);
+        for _ in 0..times {
+            match wrap::visual_row(self.buffer, &self.cursor_position, down, width) {
+                Some(pos) => self.cursor_position = pos,
+                None => break,
+            }
+        }
+        self.resolve_displacement()
+    }
+
+    // with a count gg and GG jump to that line number
+    fn page_or_jump(&mut self, dir: Direction, count: Option<usize>) {
+        match count {
+            Some(line) => {
+                self.cursor_position.height =
+                    std::cmp::min(line, self.buffer.len()).saturating_sub(1);
+                self.cursor_position
+                    .resolve_width(self.buffer.line(self.cursor_position.height).grapheme_len());
+                self.resolve_displacement();
+            }
+            None => {
+                self.move_and_resolve(dir);
+            }
+        }
+    }
+
+    /// move to a char found on the cursor line, stays put when it is not there
+    fn find_in_line(&mut self, find: CharFind, times: usize, repeat: bool) -> usize {
+        let Some(column) = find.column(&self.cursor_position, times, self.buffer, repeat) else {
+            return 0;
+        };
+        self.cursor_position.width = column;
+        self.cursor_position.max_width = column;
+        self.resolve_displacement()
+    }
+
+    fn highlight_matching_bracket(&mut self, theme: &Theme) {
+        self.matched_bracket = View::render_matching_bracket(
+            self.buffer,
+            &self.cursor_position,
+            &self.screen_offset,
+            &self.size,
+            self.matched_bracket,
+            theme,
+        );
+    }
+
+    /// the last line of the buffer on screen
+    fn view_bottom(&self) -> usize {
+        std::cmp::min(
+            self.screen_offset
+                .height
+                .saturating_add(ScreenOffset::cursor_rows(&self.size))
+                .saturating_sub(1),
+            self.buffer.len().saturating_sub(1),
+        )
+    }
+
+    /// H M L, a count is the line from the top or bottom of the view
+    /// the scrolloff margin is kept unless the view shows the start or end of the buffer
+    fn move_to_view_row(&mut self, row: ViewRow, count: Option<usize>) -> usize {
+        let margin = ScreenOffset::margin(&self.size, *self.scrolloff);
+        let top = self.screen_offset.height;
+        let bottom = self.view_bottom();
+        let lines = count.unwrap_or(1).saturating_sub(1);
+        let height = match row {
+            ViewRow::Top => {
+                let lines = if top > 0 {
+                    std::cmp::max(lines, margin)
+                } else {
+                    lines
+                };
+                std::cmp::min(top.saturating_add(lines), bottom)
+            }
+            ViewRow::Middle => top.saturating_add(bottom.saturating_sub(top) / 2),
+            ViewRow::Bottom => {
+                let lines = if bottom < self.buffer.len().saturating_sub(1) {
+                    std::cmp::max(lines, margin)
+                } else {
+                    lines
+                };
+                std::cmp::max(bottom.saturating_sub(lines), top)
+            }
+        };
+        self.cursor_position.height = height;
+        self.cursor_position.width = self.buffer.first_non_blank(height);
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement()
+    }
+
+    /// Ctrl-e and Ctrl-y, move the view by lines and keep the cursor on screen
+    fn scroll_view(&mut self, down: bool, lines: usize) {
+        let last_line = self.buffer.len().saturating_sub(1);
+        self.screen_offset.height = if down {
+            std::cmp::min(self.screen_offset.height.saturating_add(lines), last_line)
+        } else {
+            self.screen_offset.height.saturating_sub(lines)
+        };
+        let margin = ScreenOffset::margin(&self.size, *self.scrolloff);
+        let top = self.screen_offset.height;
+        let first = if top > 0 {
+            top.saturating_add(margin)
+        } else {
+            top
+        };
+        let bottom = self.view_bottom();
+        let last = if bottom < last_line {
+            bottom.saturating_sub(margin)
+        } else {
+            bottom
+        };
+        let height = std::cmp::min(std::cmp::max(self.cursor_position.height, first), last);
+        if height != self.cursor_position.height {
+            self.cursor_position.height = height;
+            self.cursor_position
+                .resolve_width(self.buffer.line(height).len());
+        }
+        self.resolve_displacement();
+    }
+
+    /// zt zz zb, scroll so the cursor line is at the top, middle or bottom of the view
+    fn place_cursor_line(&mut self, row: ViewRow) {
+        let rows = ScreenOffset::cursor_rows(&self.size);
+        let margin = ScreenOffset::margin(&self.size, *self.scrolloff);
+        let height = self.cursor_position.height;
+        self.screen_offset.height = match row {
+            ViewRow::Top => height.saturating_sub(margin),
+            ViewRow::Middle => height.saturating_sub(rows.saturating_sub(1) / 2),
+            ViewRow::Bottom => height
+                .saturating_add(margin)
+                .saturating_add(1)
+                .saturating_sub(rows),
+        };
+        self.resolve_displacement();
+    }
+
+    fn jump_to_pair(&mut self) -> usize {
+        let Some(pair) = self.buffer.bracket_jump(&self.cursor_position) else {
+            return 0;
+        };
+        self.cursor_position.set_position(pair);
+        self.cursor_position.max_width = pair.width;
+        self.resolve_displacement()
+    }
+
     #[inline]
     fn move_and_resolve(&mut self, dir: Direction) -> usize {
         dir.move_cursor(&mut self.cursor_position, self.buffer);
         self.resolve_displacement()
     }
 
-    fn wait_for_successful_event() -> Event {
-        // we are waiting on a single event
-        // so wait for an ok event
+    /// the next key for vim mode, taken from a running macro before the terminal
+    fn next_event(&mut self) -> Event {
+        self.keys.next_event()
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some((name, mut keys)) = self.keys.recording.take() {
+            // dropping the q that ended the recording
+            if matches!(
+                keys.last(),
+                Some(Event::Key(KeyEvent {
+                    code: KeyCode::Char('q'),
+                    ..
+                }))
+            ) {
+                keys.pop();
+            }
+            self.registers.record_macro(name, keys);
+        }
+    }
+
+    /// wait for the key that completes an operator
+    /// a count typed between the operator and the motion multiplies the one before it
+    fn wait_for_motion_event(&mut self, count: Option<usize>) -> (Option<usize>, Event) {
+        let mut motion_count: Option<usize> = None;
         loop {
-            let Ok(read_event) = read() else { continue };
-            return read_event;
+            let event = self.next_event();
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ '0'..='9'),
+                ..
+            }) = event
+            {
+                let digit = digit_value(c);
+                if digit > 0 || motion_count.is_some() {
+                    motion_count = Some(
+                        motion_count
+                            .unwrap_or_default()
+                            .saturating_mul(10)
+                            .saturating_add(digit),
+                    );
+                    continue;
+                }
+            }
+            let count = match (count, motion_count) {
+                (Some(a), Some(b)) => Some(a.saturating_mul(b)),
+                (a, b) => a.or(b),
+            };
+            return (count, event);
         }
     }
 
-    fn queue_delete(&mut self) -> bool {
-        let event = Self::wait_for_successful_event();
-        if let Event::Key(KeyEvent { code, .. }) = event {
-            match code {
-                KeyCode::Char('w') => {
-                    let mut right = self.cursor_position;
-                    self.buffer.begining_of_next_word(&mut right);
-                    self.buffer
-                        .delete_segment(&self.cursor_position, &mut right);
+    /// position reached by applying a motion `times` times from the cursor
+    /// stops early once the motion no longer moves
+    fn motion_target<F>(&self, times: usize, motion: F) -> Position
+    where
+        F: Fn(&Buffer, &mut Position),
+    {
+        let mut target = self.cursor_position;
+        for _ in 0..times {
+            let before = target;
+            motion(self.buffer, &mut target);
+            if before == target {
+                break;
+            }
+        }
+        target
+    }
+
+    /// wait for the motion that completes an operator, then apply it
+    fn queue_operator(
+        &mut self,
+        operator: Operator,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> ContinueState {
+        let (count, event) = self.wait_for_motion_event(count);
+        let c = match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            }) => c,
+            Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            }) => return ContinueState::ContinueVim,
+            _ => return ContinueState::InvalidCommand,
+        };
+        let target = if c == 'i' || c == 'a' {
+            let Some(object) = self.read_text_object(c == 'i') else {
+                return ContinueState::InvalidCommand;
+            };
+            OperatorTarget::TextObject(object)
+        } else if let Ok(kind) = FindKind::try_from(c) {
+            // the key picks the kind of search, the char comes next
+            let Some(target) = self.read_char() else {
+                return ContinueState::InvalidCommand;
+            };
+            let find = kind.find(target);
+            self.last_find = Some(find);
+            OperatorTarget::Find(find)
+        } else if c == ';' || c == ',' {
+            let Some(find) = self.last_find else {
+                return ContinueState::InvalidCommand;
+            };
+            OperatorTarget::Find(if c == ',' { find.reversed() } else { find })
+        } else if c == operator.key() {
+            OperatorTarget::Motion(Motion::CurrentLine)
+        } else if c == 'g' {
+            // gg is the only motion starting with g
+            let Event::Key(KeyEvent {
+                code: KeyCode::Char('g'),
+                ..
+            }) = self.next_event()
+            else {
+                return ContinueState::InvalidCommand;
+            };
+            OperatorTarget::Motion(Motion::FirstLine)
+        } else if let Ok(motion) = Motion::try_from(c) {
+            OperatorTarget::Motion(motion)
+        } else {
+            return ContinueState::InvalidCommand;
+        };
+        self.run_operator(operator, target, count, register)
+    }
+
+    /// apply an operator and keep it for dot repeat
+    fn run_operator(
+        &mut self,
+        operator: Operator,
+        target: OperatorTarget,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> ContinueState {
+        let Some(region) = self.operator_region(operator, target, count) else {
+            return ContinueState::InvalidCommand;
+        };
+        let state = self.apply_operator(operator, region, register);
+        if matches!(state, ContinueState::ContinueVimPersistError) {
+            return state;
+        }
+        let command = Repeatable::Operator {
+            operator,
+            target,
+            count,
+            register,
+        };
+        match operator {
+            Operator::Yank => {}
+            Operator::Delete => self.dot_repeat.set(command),
+            // a change is complete once the new text is typed
+            Operator::Change => self.dot_repeat.start_typing(command),
+        }
+        state
+    }
+
+    /// the text an operator acts on, None when a text object or char is not found
+    fn operator_region(
+        &self,
+        operator: Operator,
+        target: OperatorTarget,
+        count: Option<usize>,
+    ) -> Option<TextRegion> {
+        match target {
+            OperatorTarget::Motion(motion) => {
+                // cw on a word only changes to the end of the word
+                let motion = if operator == Operator::Change
+                    && motion == Motion::NextWordStart
+                    && !self.on_blank()
+                {
+                    Motion::ChangeWord
+                } else {
+                    motion
+                };
+                if motion == Motion::MatchingPair
+                    && self.buffer.bracket_jump(&self.cursor_position).is_none()
+                {
+                    return None;
                 }
-                KeyCode::Char('b') => {
-                    let mut left = self.cursor_position;
-                    self.buffer.begining_of_current_word(&mut left);
-                    self.buffer.delete_segment(&left, &mut self.cursor_position);
+                Some(motion.region(&self.cursor_position, count, self.buffer))
+            }
+            OperatorTarget::TextObject(object) => object.region(&self.cursor_position, self.buffer),
+            OperatorTarget::Find(find) => {
+                find.region(&self.cursor_position, count.unwrap_or(1), self.buffer)
+            }
+        }
+    }
+
+    /// run the last change again at the cursor, a count replaces the one it was made with
+    /// returns false when there is no change to repeat
+    fn repeat_change(&mut self, count: Option<usize>) -> bool {
+        let Some(change) = self.dot_repeat.last() else {
+            return false;
+        };
+        match change.command {
+            Repeatable::Operator {
+                operator,
+                target,
+                count: made_with,
+                register,
+            } => {
+                let Some(region) = self.operator_region(operator, target, count.or(made_with))
+                else {
+                    return false;
+                };
+                self.apply_operator(operator, region, register);
+                if operator == Operator::Change {
+                    self.insert_typed(&change.inserted, 1);
                 }
-                KeyCode::Char('d') => {
-                    self.buffer.pop_line(self.cursor_position.height);
+            }
+            Repeatable::Put {
+                register,
+                before,
+                times,
+            } => self.put(register, before, count.unwrap_or(times)),
+            Repeatable::Insert(entry) => {
+                self.enter_insert(entry);
+                self.insert_typed(&change.inserted, count.unwrap_or(1));
+            }
+            Repeatable::Shift { rows, right } => self.shift_rows(rows, right),
+            Repeatable::BlockInsert { rows, pad, .. } => {
+                if !self.buffer.insert_block_rows(
+                    &change.inserted,
+                    self.cursor_position.height,
+                    self.cursor_position.width,
+                    rows,
+                    pad,
+                ) {
+                    return false;
                 }
-                KeyCode::Char('e') => {
-                    let mut right = self.cursor_position;
-                    self.buffer.end_of_current_word(&mut right);
-                    self.buffer
-                        .delete_segment(&self.cursor_position, &mut right);
+            }
+            Repeatable::ReplaceChar { with, times } => {
+                return self.replace_chars(with, count.unwrap_or(times));
+            }
+            Repeatable::Overwrite => {
+                for c in change.inserted.chars() {
+                    self.overwrite_char(c);
                 }
-                _ => return false,
+                self.step_back();
             }
-        } else {
+            Repeatable::Join(lines) => {
+                return self.join_lines(count.map_or(lines, |count| std::cmp::max(count, 2)));
+            }
+            Repeatable::ToggleCase(times) => return self.toggle_case(count.unwrap_or(times)),
+        }
+        self.resolve_displacement();
+        true
+    }
+
+    /// x X r R J ~ >> <<, edits made from the cursor without a motion
+    /// returns false when nothing could be changed
+    fn edit_at_cursor(
+        &mut self,
+        command: &VimModeCommands,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> bool {
+        let times = count.unwrap_or(1);
+        let change = match *command {
+            VimModeCommands::DeleteChar(motion) => {
+                // x and X are dl and dh, they keep the count and register for dot
+                let target = OperatorTarget::Motion(motion);
+                self.run_operator(Operator::Delete, target, count, register);
+                return true;
+            }
+            VimModeCommands::ReplaceChar => {
+                let Some(with) = self.read_char() else {
+                    return false;
+                };
+                if !self.replace_chars(with, times) {
+                    return false;
+                }
+                Repeatable::ReplaceChar { with, times }
+            }
+            VimModeCommands::Overwrite => {
+                let typed = self.overwrite_mode();
+                if !typed.is_empty() {
+                    self.dot_repeat.set_with_text(Repeatable::Overwrite, typed);
+                }
+                return true;
+            }
+            VimModeCommands::Join => {
+                // a count is the number of lines, at least two
+                let lines = std::cmp::max(times, 2);
+                if !self.join_lines(lines) {
+                    return false;
+                }
+                Repeatable::Join(lines)
+            }
+            VimModeCommands::ToggleCase => {
+                if !self.toggle_case(times) {
+                    return false;
+                }
+                Repeatable::ToggleCase(times)
+            }
+            VimModeCommands::Shift(right) => {
+                // the key is typed twice
+                let key = if right { '>' } else { '<' };
+                if self.read_char() != Some(key) {
+                    return false;
+                }
+                self.shift_rows(times, right);
+                Repeatable::Shift { rows: times, right }
+            }
+            _ => return false,
+        };
+        self.dot_repeat.set(change);
+        self.resolve_displacement();
+        true
+    }
+
+    /// r, replace `times` chars from the cursor with a char, false when the line is too short
+    fn replace_chars(&mut self, with: char, times: usize) -> bool {
+        let end_width = self.cursor_position.width.saturating_add(times);
+        if end_width > self.buffer.line(self.cursor_position.height).len() {
             return false;
+        }
+        let end = Position {
+            width: end_width,
+            ..self.cursor_position
         };
+        self.buffer
+            .replace_text(&self.cursor_position, &end, &with.to_string().repeat(times));
+        // the cursor lands on the last char replaced
+        self.cursor_position.width = end_width.saturating_sub(1);
+        self.cursor_position.max_width = self.cursor_position.width;
         true
     }
 
-    fn queue_yank(&mut self) -> bool {
-        let event = Self::wait_for_successful_event();
-        if let Event::Key(KeyEvent { code, .. }) = event {
-            let copy_string = match code {
-                KeyCode::Char('w') => {
-                    let mut right = self.cursor_position;
-                    self.buffer.begining_of_next_word(&mut right);
-                    self.buffer.get_segment(&self.cursor_position, &right)
-                }
-                KeyCode::Char('b') => {
-                    let mut left = self.cursor_position;
-                    self.buffer.begining_of_current_word(&mut left);
-
-                    self.buffer.get_segment(&left, &self.cursor_position)
-                }
-                KeyCode::Char('y') => self.buffer.text[self.cursor_position.height]
-                    .raw_string
-                    .clone(),
-                KeyCode::Char('e') => {
-                    let mut right = self.cursor_position;
-                    self.buffer.end_of_current_word(&mut right);
-                    self.buffer.get_segment(&self.cursor_position, &right)
-                }
-                _ => return false,
+    /// ~, switch the case of `times` chars from the cursor and move past them
+    fn toggle_case(&mut self, times: usize) -> bool {
+        let line_len = self.buffer.line(self.cursor_position.height).len();
+        if self.cursor_position.width >= line_len {
+            return false;
+        }
+        let end = Position {
+            width: std::cmp::min(self.cursor_position.width.saturating_add(times), line_len),
+            ..self.cursor_position
+        };
+        let text = self.buffer.get_text(&self.cursor_position, &end);
+        let toggled: String = text
+            .chars()
+            .map(|c| {
+                if c.is_lowercase() {
+                    c.to_uppercase().to_string()
+                } else {
+                    c.to_lowercase().to_string()
+                }
+            })
+            .collect();
+        if toggled != text {
+            self.buffer
+                .replace_text(&self.cursor_position, &end, &toggled);
+        }
+        // the cursor stops on the last char of the line
+        self.cursor_position.width = std::cmp::min(end.width, line_len.saturating_sub(1));
+        self.cursor_position.max_width = self.cursor_position.width;
+        true
+    }
+
+    /// J, join `lines` lines into one from the cursor line
+    /// the cursor goes where the last two lines met
+    fn join_lines(&mut self, lines: usize) -> bool {
+        let height = self.cursor_position.height;
+        let mut joined_at = None;
+        for _ in 1..lines {
+            let Some(column) = self.buffer.join_lines(height) else {
+                break;
             };
-            if !copy_string.is_empty() {
-                let res = ClipboardUtils::copy_text_to_clipboard(copy_string);
-                debug_assert!(res.is_ok());
-            }
-        } else {
+            joined_at = Some(column);
+        }
+        let Some(column) = joined_at else {
             return false;
         };
+        self.cursor_position.width = column;
+        self.cursor_position.max_width = column;
         true
     }
+
+    /// >> and <<, indent or outdent `rows` lines from the cursor line
+    fn shift_rows(&mut self, rows: usize, right: bool) {
+        let first = self.cursor_position.height;
+        self.buffer
+            .shift_lines(first, first.saturating_add(rows.saturating_sub(1)), right);
+        self.cursor_position.width = self.buffer.first_non_blank(first);
+        self.cursor_position.max_width = self.cursor_position.width;
+    }
+
+    /// R, typed chars take the place of the ones at the cursor until Esc
+    /// backspace brings back what was overwritten, returns the text typed
+    fn overwrite_mode(&mut self) -> String {
+        let mut overwritten: Vec<Option<String>> = Vec::new(); // None where the line grew
+        let mut typed = String::new();
+        loop {
+            let res = self.render_overwrite();
+            debug_assert!(res.is_ok());
+            match self.next_event() {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(c),
+                    modifiers,
+                    ..
+                }) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                    overwritten.push(self.overwrite_char(c));
+                    typed.push(c);
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Backspace,
+                    ..
+                }) => {
+                    let Some(original) = overwritten.pop() else {
+                        continue;
+                    };
+                    typed.pop();
+                    self.cursor_position.width = self.cursor_position.width.saturating_sub(1);
+                    self.cursor_position.max_width = self.cursor_position.width;
+                    let end = Position {
+                        width: self.cursor_position.width.saturating_add(1),
+                        ..self.cursor_position
+                    };
+                    let original = original.unwrap_or_default();
+                    self.buffer
+                        .replace_text(&self.cursor_position, &end, &original);
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                }) => break,
+                _ => {}
+            }
+        }
+        self.step_back();
+        typed
+    }
+
+    /// overwrite the char at the cursor, or add to the end of the line, and move past it
+    /// returns the char that was overwritten
+    fn overwrite_char(&mut self, c: char) -> Option<String> {
+        let width = self.cursor_position.width;
+        let line = self.buffer.line(self.cursor_position.height);
+        let original = (width < line.len()).then(|| {
+            line.grapheme_slice(width..width.saturating_add(1))
+                .to_string()
+        });
+        let end = Position {
+            width: width.saturating_add(1),
+            ..self.cursor_position
+        };
+        self.buffer
+            .replace_text(&self.cursor_position, &end, &c.to_string());
+        self.cursor_position.width = width.saturating_add(1);
+        self.cursor_position.max_width = self.cursor_position.width;
+        original
+    }
+
+    /// leaving a typing mode moves the cursor back onto the last char typed
+    fn step_back(&mut self) {
+        self.cursor_position.width = self.cursor_position.width.saturating_sub(1);
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement();
+    }
+
+    fn render_overwrite(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.resolve_displacement() > 0 {
+            self.render_proc()?;
+        } else {
+            self.render_buffer_line(
+                self.cursor_position
+                    .height
+                    .saturating_sub(self.screen_offset.height),
+                self.cursor_position.height,
+            )?;
+        }
+        Terminal::render_status_message(&self.size, "-- REPLACE --")?;
+        Terminal::move_cursor_to(self.screen_cursor())?;
+        Terminal::show_cursor()?;
+        Terminal::execute()?;
+        Ok(())
+    }
+
+    /// move the cursor to where typing starts, opening a new line for o and O
+    fn enter_insert(&mut self, entry: InsertEntry) {
+        let height = self.cursor_position.height;
+        let line_len = self.buffer.line(height).len();
+        match entry {
+            InsertEntry::Before => {}
+            InsertEntry::After => {
+                self.cursor_position.width =
+                    std::cmp::min(self.cursor_position.width.saturating_add(1), line_len);
+            }
+            InsertEntry::LineEnd => self.cursor_position.width = line_len,
+            InsertEntry::LineStart => {
+                self.cursor_position.width = self.buffer.first_non_blank(height);
+            }
+            InsertEntry::LineBelow => {
+                // a new line at the end of this one carries the indent down
+                self.cursor_position.width = line_len;
+                self.buffer.add_new_line(&mut self.cursor_position);
+            }
+            InsertEntry::LineAbove => {
+                let indent_len = self.buffer.first_non_blank(height);
+                let indent = self.buffer.line(height).raw_string[..indent_len].to_string();
+                let line_start = Position {
+                    height,
+                    width: 0,
+                    max_width: usize::default(),
+                };
+                self.buffer.insert_text(&line_start, &format!("{indent}\n"));
+                self.cursor_position.width = indent_len;
+            }
+        }
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement();
+    }
+
+    /// insert text typed outside vim mode at the cursor, the cursor lands on its last char
+    fn insert_typed(&mut self, text: &str, times: usize) {
+        if text.is_empty() {
+            return;
+        }
+        let end = self
+            .buffer
+            .insert_text(&self.cursor_position, &text.repeat(times));
+        self.cursor_position.set_position(Position {
+            width: end.width.saturating_sub(1),
+            ..end
+        });
+        self.cursor_position.max_width = self.cursor_position.width;
+    }
+
+    /// read the char naming a register or macro
+    fn read_char(&mut self) -> Option<char> {
+        match self.next_event() {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(name),
+                ..
+            }) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// read the key naming a text object after i or a
+    fn read_text_object(&mut self, inner: bool) -> Option<TextObject> {
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            ..
+        }) = self.next_event()
+        else {
+            return None;
+        };
+        let kind = TextObjectKind::try_from(c).ok()?;
+        Some(TextObject { kind, inner })
+    }
+
+    fn on_blank(&self) -> bool {
+        self.buffer
+            .line(self.cursor_position.height)
+            .grapheme_slice(
+                self.cursor_position.width..self.cursor_position.width.saturating_add(1),
+            )
+            .trim()
+            .is_empty()
+    }
+
+    fn apply_operator(
+        &mut self,
+        operator: Operator,
+        region: TextRegion,
+        register: Option<char>,
+    ) -> ContinueState {
+        // nothing is removed when the text has nowhere to go
+        if let Err(message) = Registers::writable(register) {
+            self.command_status_line(&message);
+            return ContinueState::ContinueVimPersistError;
+        }
+        let res = match (operator, region) {
+            (Operator::Yank, TextRegion::Chars { start, end }) => {
+                let text = self.buffer.get_text(&start, &end);
+                let res = self
+                    .registers
+                    .yank(register, Register::new(text, RegisterKind::Chars));
+                self.cursor_position.set_position(start);
+                res
+            }
+            (Operator::Yank, TextRegion::Lines { first, last }) => {
+                let lines = (first..=last)
+                    .map(|line| self.buffer.line(line).raw_string)
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                let res = self
+                    .registers
+                    .yank(register, Register::new(lines, RegisterKind::Lines));
+                self.cursor_position.height = first;
+                res
+            }
+            (Operator::Delete | Operator::Change, TextRegion::Chars { start, end }) => {
+                let text = self.buffer.remove_text(&start, &end);
+                let res = self
+                    .registers
+                    .delete(register, Register::new(text, RegisterKind::Chars));
+                self.cursor_position.set_position(start);
+                res
+            }
+            (Operator::Delete, TextRegion::Lines { first, last }) => {
+                let lines = self.buffer.remove_lines(first, last);
+                let res = self
+                    .registers
+                    .delete(register, Register::new(lines, RegisterKind::Lines));
+                self.cursor_position.height =
+                    std::cmp::min(first, self.buffer.len().saturating_sub(1));
+                self.cursor_position.width =
+                    self.buffer.first_non_blank(self.cursor_position.height);
+                res
+            }
+            (Operator::Change, TextRegion::Lines { first, last }) => {
+                // the lines are replaced by a single line keeping the indent of the first
+                let indent = self.buffer.first_non_blank(first);
+                let start = Position {
+                    height: first,
+                    width: indent,
+                    max_width: usize::default(),
+                };
+                let end = Position {
+                    height: last,
+                    width: self.buffer.line(last).len(),
+                    max_width: usize::default(),
+                };
+                let text = self.buffer.remove_text(&start, &end);
+                let res = self
+                    .registers
+                    .delete(register, Register::new(text, RegisterKind::Lines));
+                self.cursor_position.set_position(start);
+                res
+            }
+        };
+        // the text is kept in "" even when the clipboard could not take it
+        self.status_message = res.err();
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.resolve_displacement();
+        if operator == Operator::Change {
+            ContinueState::ExitVimMode
+        } else {
+            ContinueState::ContinueVim
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> Event {
+        let code = if c == '\r' {
+            KeyCode::Enter
+        } else {
+            KeyCode::Char(c)
+        };
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// run vim mode over some text with the keys typed, ending with the text left
+    fn run_keys(text: &str, keys: &str) -> Vec<String> {
+        Terminal::offscreen();
+        let mut buffer = Buffer::default();
+        buffer.insert_text(&Position::default(), text);
+        let mut registers = Registers::default();
+        let mut dot_repeat = DotRepeat::default();
+        let mut scrolloff = 0;
+        let mut size = Size {
+            height: 10,
+            width: 40,
+        };
+        let mut vim_mode = VimMode::new(
+            Position::default(),
+            ScreenOffset::default(),
+            size,
+            &mut buffer,
+            &mut registers,
+            &mut dot_repeat,
+            &mut scrolloff,
+        );
+        vim_mode.keys = KeySource::typed(keys.chars().map(key));
+        let mut cursor_position = Position::default();
+        let mut screen_offset = ScreenOffset::default();
+        assert!(vim_mode.run(
+            &mut cursor_position,
+            &mut screen_offset,
+            &mut size,
+            &Theme::default()
+        ));
+        (0..buffer.len())
+            .map(|index| buffer.line(index).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn macro_replays_a_visual_selection() {
+        // the selection keys are read by the highlight, they still go in the macro
+        assert_eq!(run_keys("abcd\nabcd", "qavldqj0@ai"), ["cd", "cd"]);
+    }
+
+    #[test]
+    fn macro_keeps_running_after_a_substitute() {
+        assert_eq!(
+            run_keys("a a\na a\na a", "qa:s/a/b/\rjq@a@ai"),
+            ["b a", "b a", "b a"]
+        );
+    }
 }
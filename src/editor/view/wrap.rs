@@ -0,0 +1,318 @@
+use super::buffer::Buffer;
+use super::gutter;
+use super::line::Line;
+use super::syntax;
+use super::theme::SyntaxColors;
+use crate::editor::terminal::{Position, ScreenOffset, ScreenPosition, Size, Terminal};
+use std::io::Error;
+use std::iter::once;
+use std::ops::Range;
+
+/// a screen row of the view and the part of a buffer line drawn on it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenRow {
+    pub row: usize,
+    pub line: Option<usize>,   // None past the end of the buffer
+    pub columns: Range<usize>, // graphemes of the line
+    pub first: bool,           // the row the line starts on, where its number goes
+}
+
+/// the rows a line takes in a wrapped view, as ranges of graphemes
+pub fn line_rows(line: &Line, width: usize) -> Vec<Range<usize>> {
+    let starts = line.row_starts(width);
+    let ends = starts.iter().skip(1).copied().chain(once(line.len()));
+    starts
+        .iter()
+        .copied()
+        .zip(ends)
+        .map(|(start, end)| start..end)
+        .collect()
+}
+
+/// lay out the top `rows` rows of the view
+/// without wrap every row shows the columns right of the offset of one line
+pub fn screen_rows(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    rows: usize,
+) -> Vec<ScreenRow> {
+    let width = offset.text_width(size);
+    let mut screen = Vec::new();
+    let mut index = offset.height;
+    while screen.len() < rows {
+        if index >= buffer.len() {
+            screen.push(ScreenRow {
+                row: screen.len(),
+                line: None,
+                columns: 0..0,
+                first: true,
+            });
+            continue;
+        }
+        let columns: Vec<Range<usize>> = if offset.wrap {
+            line_rows(&buffer.line(index), width)
+        } else {
+            once(offset.width..offset.width.saturating_add(width)).collect()
+        };
+        for (n, columns) in columns.into_iter().enumerate() {
+            if screen.len() == rows {
+                break;
+            }
+            screen.push(ScreenRow {
+                row: screen.len(),
+                line: Some(index),
+                columns,
+                first: n == 0,
+            });
+        }
+        index = index.saturating_add(1);
+    }
+    screen
+}
+
+/// the rows of text drawn by the view, the status line sits below them
+fn text_rows(size: &Size) -> usize {
+    size.height.saturating_sub(1)
+}
+
+/// where a buffer position is drawn, None when it is off screen
+pub fn screen_cell(
+    buffer: &Buffer,
+    pos: &Position,
+    offset: &ScreenOffset,
+    size: &Size,
+) -> Option<ScreenPosition> {
+    if !offset.wrap {
+        let on_screen = !pos.above_view(offset)
+            && !pos.below_view(offset, size, 1)
+            && !pos.left_of_view(offset)
+            && pos.width < offset.width.saturating_add(offset.text_width(size));
+        return on_screen.then(|| pos.relative_view_position(offset));
+    }
+    if pos.above_view(offset) || pos.below_view(offset, size, 1) {
+        return None;
+    }
+    // the last row of the line that starts at or before the position
+    let row = screen_rows(buffer, offset, size, text_rows(size))
+        .into_iter()
+        .rfind(|row| row.line == Some(pos.height) && row.columns.start <= pos.width)?;
+    let line = buffer.line(pos.height);
+    // the end of a full row has no cell of its own, the cursor stays on the last one
+    let column = line
+        .columns(row.columns.start..pos.width)
+        .min(offset.text_width(size).saturating_sub(1));
+    Some(ScreenPosition {
+        height: row.row,
+        width: offset.gutter.saturating_add(column),
+    })
+}
+
+/// where the cursor is drawn
+pub fn screen_position(
+    buffer: &Buffer,
+    pos: &Position,
+    offset: &ScreenOffset,
+    size: &Size,
+) -> ScreenPosition {
+    screen_cell(buffer, pos, offset, size).unwrap_or_else(|| pos.relative_view_position(offset))
+}
+
+/// scroll a wrapped view until the row of a position is on screen
+/// returns whether the view moved
+pub fn keep_in_view(
+    buffer: &Buffer,
+    pos: &Position,
+    offset: &mut ScreenOffset,
+    size: &Size,
+) -> bool {
+    if !offset.wrap {
+        return false;
+    }
+    let before = offset.height;
+    let width = offset.text_width(size);
+    offset.width = 0;
+    if pos.height < offset.height {
+        offset.height = pos.height;
+    }
+    // the rows of each line from the top of the view, up to the row of the position
+    let mut rows: Vec<usize> = (offset.height..pos.height)
+        .map(|index| buffer.line(index).row_starts(width).len())
+        .collect();
+    let cursor_row = buffer
+        .line(pos.height)
+        .row_starts(width)
+        .iter()
+        .filter(|start| **start <= pos.width)
+        .count();
+    rows.push(cursor_row);
+    let mut needed: usize = rows.iter().sum();
+    for top in rows.iter().take(rows.len().saturating_sub(1)) {
+        if needed <= ScreenOffset::cursor_rows(size) {
+            break;
+        }
+        needed = needed.saturating_sub(*top);
+        offset.height = offset.height.saturating_add(1);
+    }
+    offset.height != before
+}
+
+/// the position a screen row below or above, for gj and gk
+/// the screen column is kept, None at the top or bottom of the buffer
+pub fn visual_row(buffer: &Buffer, pos: &Position, down: bool, width: usize) -> Option<Position> {
+    let line = buffer.line(pos.height);
+    let rows = line_rows(&line, width);
+    let current = rows
+        .iter()
+        .rposition(|row| row.start <= pos.width)
+        .unwrap_or_default();
+    let column = line.columns(rows[current].start..pos.width);
+    let (height, target, range) = if down {
+        if let Some(next) = rows.get(current.saturating_add(1)) {
+            (pos.height, line, next.clone())
+        } else {
+            let height = pos.height.saturating_add(1);
+            let target = buffer.get_line(height)?;
+            let first = line_rows(&target, width).swap_remove(0);
+            (height, target, first)
+        }
+    } else if current > 0 {
+        let above = rows[current.saturating_sub(1)].clone();
+        (pos.height, line, above)
+    } else {
+        let height = pos.height.checked_sub(1)?;
+        let target = buffer.line(height);
+        let last = line_rows(&target, width).pop().unwrap_or_default();
+        (height, target, last)
+    };
+    let width = target.index_at_column(range, column);
+    Some(Position {
+        height,
+        width,
+        max_width: width,
+    })
+}
+
+/// draw a screen row with its line number and syntax colors
+fn render_row(
+    buffer: &Buffer,
+    screen_row: &ScreenRow,
+    offset: &ScreenOffset,
+    cursor: usize,
+    colors: &SyntaxColors,
+) -> Result<(), Error> {
+    let Some(index) = screen_row.line else {
+        return Terminal::render_line(screen_row.row, "~");
+    };
+    if screen_row.first {
+        gutter::start_row(screen_row.row, index, cursor, offset)?;
+    } else {
+        gutter::continue_row(screen_row.row, offset)?;
+    }
+    syntax::print_line(buffer, index, screen_row.columns.clone(), colors)
+}
+
+/// draw the text of the view from a buffer line down, the welcome message shows in an empty buffer
+pub fn render_text(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    from_line: usize,
+    cursor: usize,
+    colors: &SyntaxColors,
+) -> Result<(), Error> {
+    for screen_row in screen_rows(buffer, offset, size, text_rows(size)) {
+        if screen_row.line.is_some_and(|index| index < from_line) {
+            continue;
+        }
+        #[allow(clippy::integer_division)]
+        if buffer.is_empty() && screen_row.row == size.height / 3 {
+            Terminal::render_line(screen_row.row, Terminal::get_welcome_message(size, offset))?;
+            continue;
+        }
+        render_row(buffer, &screen_row, offset, cursor, colors)?;
+    }
+    Ok(())
+}
+
+/// redraw the rows of one buffer line
+pub fn render_line(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    index: usize,
+    cursor: usize,
+    colors: &SyntaxColors,
+) -> Result<(), Error> {
+    for screen_row in screen_rows(buffer, offset, size, text_rows(size)) {
+        if screen_row.line == Some(index) {
+            render_row(buffer, &screen_row, offset, cursor, colors)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(&Position::default(), text);
+        buffer
+    }
+
+    fn at(height: usize, width: usize) -> Position {
+        Position {
+            height,
+            width,
+            max_width: width,
+        }
+    }
+
+    #[test]
+    fn wrapped_layout() {
+        let buf = buffer("abcdefgh\nxy\nlong line here");
+        let size = Size {
+            height: 6,
+            width: 4,
+        };
+        let mut offset = ScreenOffset {
+            wrap: true,
+            ..ScreenOffset::default()
+        };
+        let rows: Vec<(Option<usize>, Range<usize>, bool)> = screen_rows(&buf, &offset, &size, 5)
+            .into_iter()
+            .map(|row| (row.line, row.columns, row.first))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(0), 0..4, true),
+                (Some(0), 4..8, false),
+                (Some(1), 0..2, true),
+                (Some(2), 0..4, true),
+                (Some(2), 4..8, false),
+            ]
+        );
+        let cell = screen_cell(&buf, &at(0, 5), &offset, &size).unwrap();
+        assert_eq!((cell.height, cell.width), (1, 1));
+
+        // the cursor on the last row of the last line needs the view to scroll by two lines
+        assert!(keep_in_view(&buf, &at(2, 13), &mut offset, &size));
+        assert_eq!(offset.height, 2);
+        assert!(!keep_in_view(&buf, &at(2, 13), &mut offset, &size));
+    }
+
+    #[test]
+    fn move_by_screen_rows() {
+        let buf = buffer("abcdefgh\nxy");
+        let down = |pos: Position| visual_row(&buf, &pos, true, 4);
+        let up = |pos: Position| visual_row(&buf, &pos, false, 4);
+        assert_eq!(down(at(0, 1)), Some(at(0, 5)));
+        assert_eq!(down(at(0, 6)), Some(at(1, 1)));
+        assert_eq!(down(at(1, 1)), None);
+        assert_eq!(up(at(1, 1)), Some(at(0, 5)));
+        assert_eq!(up(at(0, 3)), None);
+    }
+}